@@ -1,49 +1,85 @@
 use crate::{config, db, library};
+use crate::error::AppError;
 use chrono::Utc;
 use rusqlite::{params, Connection, Row, OptionalExtension};
 use serde::{Deserialize, Serialize};
 use std::{fs, path::PathBuf};
-use tauri::AppHandle;
+use tauri::{AppHandle, Emitter};
 use tauri_plugin_fs::FsExt;
 use tauri::Manager;
-use std::io::Write;
-use std::sync::{Arc, Mutex};
+use std::io::{Read, Write};
+use std::sync::{Arc, Condvar, Mutex};
 use crate::fa::{FAState, FASyncStatus};
+use tauri_plugin_shell::ShellExt;
+use tauri_plugin_opener::OpenerExt;
 
 
-pub fn get_root(app: &tauri::AppHandle) -> Result<std::path::PathBuf, String> {
+// The active library root, checked to be a reachable directory so commands
+// fail with Unavailable instead of an opaque SQLite open error
+pub fn get_root(app: &tauri::AppHandle) -> Result<std::path::PathBuf, AppError> {
+  let root = configured_root(app)?;
+  if !root.is_dir() {
+    return Err(AppError::Unavailable(format!(
+      "Library root {} is unavailable (drive disconnected?)",
+      root.display()
+    )));
+  }
+  Ok(root)
+}
+
+// The active library root as configured, whether or not it's reachable
+pub fn configured_root(app: &tauri::AppHandle) -> Result<std::path::PathBuf, AppError> {
   let cfg = config::load_config(app)?;
-  let root = cfg.library_root.ok_or("Library root not set yet")?;
+  let root = cfg.active_root().ok_or_else(|| AppError::NotConfigured("Library root not set yet".into()))?;
   Ok(PathBuf::from(root))
 }
 
-fn open_conn_for_root(root: &PathBuf) -> Result<Connection, String> {
-  let conn = db::open(&library::db_path(root))?;
-  db::init_schema(&conn)?;
+fn open_conn_for_root(app: &AppHandle, root: &std::path::Path) -> Result<db::PooledConn, AppError> {
+  library::acquire_lock(root)?;
+  let pool = app.state::<Arc<db::Pool>>();
+  let db_path = library::db_path(root);
+  let conn = pool.get(&db_path)?;
+  pool.ensure_schema(&conn, &db_path)?;
   Ok(conn)
 }
 
-fn settings_get(conn: &Connection, key: &str) -> Result<Option<String>, String> {
+// A pooled, migrated connection that doesn't take the instance lock; read paths only.
+// Anything that writes goes through open_conn_for_root. Still refuses a library
+// another live instance has open.
+fn pooled_conn(app: &AppHandle, root: &std::path::Path) -> Result<db::PooledConn, AppError> {
+  library::check_lock(root)?;
+  let pool = app.state::<Arc<db::Pool>>();
+  let db_path = library::db_path(root);
+  let conn = pool.get(&db_path)?;
+  pool.ensure_schema(&conn, &db_path)?;
+  Ok(conn)
+}
+
+fn settings_get(conn: &Connection, key: &str) -> Result<Option<String>, AppError> {
   let v: Option<String> = conn
     .query_row(
       "SELECT value FROM settings WHERE key=?",
       params![key],
       |r: &Row| r.get(0),
     )
-    .optional()
-    .map_err(|e| e.to_string())?;
+    .optional()?;
   Ok(v)
 }
 
-fn settings_set(conn: &Connection, key: &str, value: &str) -> Result<(), String> {
+fn settings_set(conn: &Connection, key: &str, value: &str) -> Result<(), AppError> {
   conn.execute(
     "INSERT INTO settings(key, value) VALUES(?, ?) ON CONFLICT(key) DO UPDATE SET value=excluded.value",
     params![key, value],
-  ).map_err(|e| e.to_string())?;
+  )?;
+  Ok(())
+}
+
+pub fn settings_delete(conn: &Connection, key: &str) -> Result<(), AppError> {
+  conn.execute("DELETE FROM settings WHERE key=?", params![key])?;
   Ok(())
 }
 
-fn sanitize_slug(s: &str) -> String {
+pub fn sanitize_slug(s: &str) -> String {
   let mut out = s.trim().to_lowercase().replace(' ', "_");
   for ch in ['<', '>', ';', ':', '"', '/', '\\', '|', '?', '*'] {
     out = out.replace(ch, "");
@@ -54,7 +90,181 @@ fn sanitize_slug(s: &str) -> String {
   out
 }
 
-fn pick_primary_artist(artists: &[String]) -> String {
+// Filename layout before `filename_template` existed; still what each import path falls back to
+pub const DEFAULT_FILENAME_TEMPLATE: &str = "{artist}_{source}_{id}.{ext}";
+pub const FA_UPGRADE_FILENAME_TEMPLATE: &str = "e621_{id}.{ext}";
+
+pub struct FilenameParts<'a> {
+  pub artist: &'a str,
+  pub source: &'a str,
+  pub id: &'a str,
+  pub md5: Option<&'a str>,
+  pub ext: &'a str,
+}
+
+// Interpolated values can't introduce path separators, reserved characters or dot-names
+fn sanitize_filename_value(s: &str) -> String {
+  let cleaned: String = s
+    .trim()
+    .chars()
+    .map(|c| if c.is_whitespace() { '_' } else { c })
+    .filter(|c| !matches!(c, '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*') && !c.is_control())
+    .collect();
+  cleaned.trim_matches('.').to_string()
+}
+
+// Renders `{artist}`, `{source}`, `{id}`, `{md5}` and `{ext}`. None when the template
+// is unusable: unknown token, unclosed brace, a separator in the literal text, no
+// {id}/{md5} to keep names unique, or {md5} with no hash known. `.{ext}` is appended
+// when the template doesn't place it.
+pub fn render_filename(template: &str, parts: &FilenameParts) -> Option<String> {
+  let mut out = String::new();
+  let mut unique = false;
+  let mut has_ext = false;
+  let mut rest = template.trim();
+
+  while let Some(open) = rest.find('{') {
+    let literal = &rest[..open];
+    if literal.contains(['/', '\\', '}']) {
+      return None;
+    }
+    out.push_str(literal);
+
+    let close = rest[open..].find('}')? + open;
+    let value = match &rest[open + 1..close] {
+      "artist" => sanitize_filename_value(parts.artist),
+      "source" => sanitize_filename_value(parts.source),
+      "id" => {
+        unique = true;
+        sanitize_filename_value(parts.id)
+      }
+      "md5" => {
+        unique = true;
+        sanitize_filename_value(parts.md5.filter(|m| !m.is_empty())?)
+      }
+      "ext" => {
+        has_ext = true;
+        sanitize_filename_value(parts.ext)
+      }
+      _ => return None,
+    };
+    out.push_str(&value);
+    rest = &rest[close + 1..];
+  }
+  if rest.contains(['/', '\\', '}']) {
+    return None;
+  }
+  out.push_str(rest);
+
+  if !unique {
+    return None;
+  }
+  if !has_ext {
+    out = format!("{}.{}", out, sanitize_filename_value(parts.ext));
+  }
+  let out = out.trim_matches('.').to_string();
+  if out.is_empty() { None } else { Some(out) }
+}
+
+// The user's `filename_template` if it renders, otherwise the caller's historical pattern
+pub fn filename_for(conn: &Connection, fallback: &str, parts: &FilenameParts) -> String {
+  settings_get(conn, "filename_template")
+    .ok()
+    .flatten()
+    .and_then(|t| render_filename(&t, parts))
+    .or_else(|| render_filename(fallback, parts))
+    .unwrap_or_else(|| format!("{}.{}", sanitize_filename_value(parts.id), sanitize_filename_value(parts.ext)))
+}
+
+#[tauri::command]
+pub fn get_filename_template(app: AppHandle) -> Result<Option<String>, AppError> {
+  let root = get_root(&app)?;
+  let conn = open_conn_for_root(&app, &root)?;
+  settings_get(&conn, "filename_template")
+}
+
+#[tauri::command]
+pub fn set_filename_template(app: AppHandle, template: Option<String>) -> Result<Status, AppError> {
+  let root = get_root(&app)?;
+  let conn = open_conn_for_root(&app, &root)?;
+
+  let Some(template) = template.map(|t| t.trim().to_string()).filter(|t| !t.is_empty()) else {
+    settings_delete(&conn, "filename_template")?;
+    return Ok(Status { ok: true, message: "Using the default filename pattern".into(), item_id: None });
+  };
+
+  let sample = FilenameParts { artist: "artist", source: "e621", id: "12345", md5: Some("d41d8cd98f00b204e9800998ecf8427e"), ext: "png" };
+  let Some(preview) = render_filename(&template, &sample) else {
+    return Err(AppError::InvalidInput(
+      "Template needs {id} or {md5}, and may only use {artist}, {source}, {id}, {md5} and {ext}".into(),
+    ));
+  };
+
+  settings_set(&conn, "filename_template", &template)?;
+  Ok(Status { ok: true, message: format!("Files will be named like {preview}"), item_id: None })
+}
+
+const DEFAULT_MIN_FREE_MB: u64 = 500;
+
+// Refuse to start a download when the library volume is below `min_free_mb`
+pub fn ensure_free_space(conn: &Connection, root: &std::path::Path) -> Result<(), AppError> {
+  ensure_free_space_for(conn, root, 0)
+}
+
+// Same, but the file about to be written (`incoming` bytes) must fit on top of the reserve
+pub fn ensure_free_space_for(conn: &Connection, root: &std::path::Path, incoming: u64) -> Result<(), AppError> {
+  let reserve_mb = settings_get(conn, "min_free_mb")?
+    .and_then(|v| v.trim().parse::<u64>().ok())
+    .unwrap_or(DEFAULT_MIN_FREE_MB);
+  let min_mb = reserve_mb + incoming.div_ceil(1024 * 1024);
+  let free_mb = fs4::available_space(root)? / (1024 * 1024);
+  if free_mb < min_mb {
+    return Err(AppError::DiskFull(format!(
+      "Insufficient disk space: {} MB free on the library drive, {} MB required",
+      free_mb, min_mb
+    )));
+  }
+  Ok(())
+}
+
+/// Width and height from the image header, without decoding the pixels.
+pub fn image_dimensions_of(bytes: &[u8]) -> Option<(u32, u32)> {
+  image::io::Reader::new(std::io::Cursor::new(bytes))
+    .with_guessed_format()
+    .ok()?
+    .into_dimensions()
+    .ok()
+}
+
+/// Sniff the real MIME type from the file's magic bytes.
+pub fn detect_mime(bytes: &[u8]) -> Option<String> {
+  infer::get(bytes).map(|t| t.mime_type().to_string())
+}
+
+// The extension the bytes really have, when infer recognises them and it disagrees
+// with `assumed` (jpg and jpeg count as the same)
+pub fn corrected_ext(kind: Option<infer::Type>, assumed: &str) -> Option<&'static str> {
+  let real = kind?.extension();
+  let canon = |e: &str| if e.eq_ignore_ascii_case("jpeg") { "jpg".to_string() } else { e.to_lowercase() };
+  if canon(real) == canon(assumed) { None } else { Some(real) }
+}
+
+// `base` in media/, or base_dup1, base_dup2, ... when taken
+pub fn unique_media_name(media_dir: &std::path::Path, base: &str) -> String {
+  let (stem, dot_ext) = match base.rfind('.') {
+    Some(i) => (&base[..i], &base[i..]),
+    None => (base, ""),
+  };
+  let mut filename = base.to_string();
+  let mut n = 1;
+  while media_dir.join(&filename).exists() {
+    filename = format!("{stem}_dup{n}{dot_ext}");
+    n += 1;
+  }
+  filename
+}
+
+pub fn pick_primary_artist(artists: &[String]) -> String {
   let deny = ["sound_warning", "conditional_dnp"];
   artists
     .iter()
@@ -67,6 +277,9 @@ fn pick_primary_artist(artists: &[String]) -> String {
 pub struct Status {
   pub ok: bool,
   pub message: String,
+  // the existing item an import matched instead of downloading, when there is one
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub item_id: Option<i64>,
 }
 
 
@@ -79,14 +292,21 @@ pub struct ItemDto {
   pub file_rel: String,
   pub file_abs: String,
   pub ext: Option<String>,
+  pub mime: Option<String>,
   pub tags: Vec<String>,
   pub artists: Vec<String>,
+  pub primary_artist: Option<String>,
   pub sources: Vec<String>,
   pub rating: Option<String>,
   pub fav_count: Option<i64>,
   pub score_total: Option<i64>,
   pub timestamp: Option<String>,
   pub added_at: String,
+  pub notes: Option<String>,
+  pub uploader_id: Option<i64>,
+  pub uploader_name: Option<String>,
+  pub approver_id: Option<i64>,
+  pub approver_name: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -112,12 +332,43 @@ pub struct E621PostInput {
   pub created_at: Option<String>,
   pub sources: Vec<String>,
   pub tags: E621Tags,
+  #[serde(default)]
+  pub remote_status: Option<String>,
+  // original API payload, stored in item_raw when present
+  #[serde(default)]
+  pub raw: Option<serde_json::Value>,
+  // e621's resized copy, downloaded instead of file_url when prefer_sample is on
+  #[serde(default)]
+  pub sample_url: Option<String>,
+  #[serde(default)]
+  pub uploader_id: Option<i64>,
+  #[serde(default)]
+  pub approver_id: Option<i64>,
+}
+
+// One per finished sync run, emitted as `sync-summary` and kept as the last one
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct SyncSummary {
+  pub source: String,
+  pub batch: String,
+  pub downloaded: u32,
+  pub skipped: u32,
+  pub failed: u32,
+  pub unavailable: u32,
+  // FA only: new FA files vs. posts swapped for their e621 version
+  pub imported: Option<u32>,
+  pub upgraded: Option<u32>,
+  pub cancelled: bool,
+  pub error: Option<String>,
+  pub elapsed_secs: u64,
+  pub finished_at: String,
 }
 
 #[derive(Debug, Clone, Serialize, Default)]
 pub struct SyncStatus {
   pub running: bool,
   pub cancelled: bool,
+  pub paused: bool,
   pub max_new_downloads: Option<u32>,
 
   pub scanned_pages: u32,
@@ -142,10 +393,9 @@ pub struct UnavailableDto {
 }
 
 #[tauri::command]
-pub fn e621_unavailable_list(app: AppHandle, limit: u32) -> Result<Vec<UnavailableDto>, String> {
+pub fn e621_unavailable_list(app: AppHandle, limit: u32) -> Result<Vec<UnavailableDto>, AppError> {
   let root = get_root(&app)?;
-  let conn = db::open(&library::db_path(&root))?;
-  db::init_schema(&conn)?;
+  let conn = pooled_conn(&app, &root)?;
 
   let mut stmt = conn.prepare(
     r#"
@@ -154,7 +404,7 @@ pub fn e621_unavailable_list(app: AppHandle, limit: u32) -> Result<Vec<Unavailab
     ORDER BY seen_at DESC
     LIMIT ?
     "#
-  ).map_err(|e| e.to_string())?;
+  )?;
 
   let rows = stmt.query_map([limit], |r| {
     let sources_json: String = r.get(4)?;
@@ -167,174 +417,353 @@ pub fn e621_unavailable_list(app: AppHandle, limit: u32) -> Result<Vec<Unavailab
       reason: r.get(3)?,
       sources,
     })
-  }).map_err(|e| e.to_string())?;
+  })?;
 
   let mut out = vec![];
   for row in rows {
-    out.push(row.map_err(|e| e.to_string())?);
+    out.push(row?);
   }
   Ok(out)
 }
 
+// Cancel switch for long-running maintenance jobs (thumbnail rebuilds, ...)
+#[derive(Default)]
+pub struct MaintenanceState {
+  pub cancel: Arc<crate::fa::CancelFlag>,
+}
+
 #[derive(Default)]
 pub struct SyncState {
   pub status: SyncStatus,
   pub cancel_requested: bool,
+  // signalled on resume and cancel so a paused worker wakes up
+  pub resume: Arc<Condvar>,
+  // post ids whose download the user aborted; checked between chunks
+  pub cancelled_downloads: std::collections::HashSet<String>,
+  // post ids recheck_e621_match is downloading right now, outside any sync
+  pub recheck_downloads: std::collections::HashSet<String>,
 }
 
 #[tauri::command]
-pub fn get_config(app: AppHandle) -> Result<config::AppConfig, String> {
+pub fn get_config(app: AppHandle) -> Result<config::AppConfig, AppError> {
   config::load_config(&app)
 }
 
+// Grants the webview access to the library: fs plugin reads and asset:// serving
+pub fn allow_root_scopes(app: &AppHandle, root: &std::path::Path) -> Result<(), AppError> {
+  // allow file access for chosen library root
+  if let Err(e) = app.fs_scope().allow_directory(root, true) {
+    return Err(format!("Failed to allow directory in fs scope: {e}").into());
+  }
+  // allow asset:// serving for convertFileSrc(...)
+  if let Err(e) = app.asset_protocol_scope().allow_directory(root, true) {
+    return Err(format!("Failed to allow directory in asset protocol scope: {e}").into());
+  }
+  Ok(())
+}
+
+// Same grants for the configured root without changing it, e.g. after macOS
+// revoked folder access
+#[tauri::command]
+pub fn reapply_scopes(app: AppHandle) -> Result<(), AppError> {
+  // get_root already refuses a root that isn't reachable
+  let root = get_root(&app)?;
+  allow_root_scopes(&app, &root)
+}
+
 #[tauri::command]
-pub fn set_library_root(app: AppHandle, library_root: String) -> Result<Status, String> {
+pub fn set_library_root(app: AppHandle, library_root: String) -> Result<Status, AppError> {
   let root = PathBuf::from(&library_root);
+  open_library(&app, &root)?;
+
+  let mut cfg = config::load_config(&app)?;
+  release_previous_lock(&app, &cfg, &root);
+  cfg.active = Some(cfg.add_library(&library_root));
+  config::save_config(&app, &cfg)?;
+
+  Ok(Status {
+    ok: true,
+    message: "Library root set and DB initialized".into(),
+    item_id: None,
+  })
+}
 
+// Validates `root`, lays it out, takes its lock, migrates its DB and grants the
+// webview access: everything needed before it becomes the active library
+fn open_library(app: &AppHandle, root: &std::path::Path) -> Result<(), AppError> {
   if !root.exists() {
-    return Err("Selected library root does not exist".into());
+    return Err(AppError::NotFound("Selected library root does not exist".into()));
   }
   if !root.is_dir() {
-    return Err("Selected library root is not a directory".into());
+    return Err(AppError::InvalidInput("Selected library root is not a directory".into()));
   }
 
-  library::ensure_layout(&root)?;
+  library::ensure_layout(root)?;
+  // takes the lock and migrates the DB
+  open_conn_for_root(app, root)?;
 
-  let conn = db::open(&library::db_path(&root))?;
-  db::init_schema(&conn)?;
+  allow_root_scopes(app, root)?;
+  crate::logging::set_root(root);
+  Ok(())
+}
 
-  // allow file access for chosen library root
-  if let Err(e) = app.fs_scope().allow_directory(&root, true) {
-    return Err(format!("Failed to allow directory in fs scope: {e}"));
-  }
-  // allow asset:// serving for convertFileSrc(...)
-  if let Err(e) = app.asset_protocol_scope().allow_directory(&root, true) {
-    return Err(format!("Failed to allow directory in asset protocol scope: {e}"));
+// Also closes the old library's pooled connections
+fn release_previous_lock(app: &AppHandle, cfg: &config::AppConfig, next: &std::path::Path) {
+  if let Some(old) = cfg.active_root() {
+    if next != std::path::Path::new(old) {
+      library::release_lock(std::path::Path::new(old));
+      app.state::<Arc<db::Pool>>().close(&library::db_path(std::path::Path::new(old)));
+    }
   }
+}
 
-  let mut cfg = config::load_config(&app)?;
-  cfg.library_root = Some(library_root);
-  config::save_config(&app, &cfg)?;
+const TRASHED_SKIP_MESSAGE: &str =
+  "Skipped: a trashed copy exists. Enable redownload_trashed to fetch trashed posts again (they will come back on every sync).";
 
-  Ok(Status {
-    ok: true,
-    message: "Library root set and DB initialized".into(),
+// By default trashed rows still count for dedupe so trashing keeps a post out for good.
+// With redownload_trashed on, only live rows count (the old behaviour).
+fn redownload_trashed(conn: &Connection) -> Result<bool, AppError> {
+  Ok(settings_get(conn, "redownload_trashed")?.is_some_and(|v| v == "true" || v == "1"))
+}
+
+fn dedupe_trashed_filter(conn: &Connection) -> Result<&'static str, AppError> {
+  Ok(if redownload_trashed(conn)? { " AND trashed_at IS NULL" } else { "" })
+}
+
+// Content dedupe always looks into the trash, whatever redownload_trashed says,
+// so identical bytes never end up on disk twice. A trashed match comes back as
+// ok: false with its item_id, and the UI offers to restore it.
+fn md5_match_status(conn: &Connection, md5: &str) -> Result<Option<Status>, AppError> {
+  let found: Option<(i64, bool)> = conn
+    .query_row(
+      "SELECT item_id, trashed_at IS NOT NULL FROM items WHERE md5 = ?1 OR file_md5 = ?1 ORDER BY trashed_at IS NOT NULL LIMIT 1",
+      params![md5],
+      |r: &Row| Ok((r.get(0)?, r.get(1)?)),
+    )
+    .optional()?;
+
+  Ok(match found {
+    None => None,
+    Some((id, false)) => Some(Status { ok: true, message: "Already downloaded (md5 match)".into(), item_id: Some(id) }),
+    Some((id, true)) => Some(Status {
+      ok: false,
+      message: format!("A trashed copy with the same content exists (item {id}); restore it instead of downloading again"),
+      item_id: Some(id),
+    }),
   })
 }
 
 #[tauri::command]
-pub fn add_e621_post(app: AppHandle, post: E621PostInput) -> Result<Status, String> {
+pub fn get_redownload_trashed(app: AppHandle) -> Result<bool, AppError> {
+  let root = get_root(&app)?;
+  let conn = open_conn_for_root(&app, &root)?;
+  redownload_trashed(&conn)
+}
+
+#[tauri::command]
+pub fn set_redownload_trashed(app: AppHandle, enabled: bool) -> Result<(), AppError> {
+  let root = get_root(&app)?;
+  let conn = open_conn_for_root(&app, &root)?;
+  settings_set(&conn, "redownload_trashed", if enabled { "true" } else { "false" })
+}
+
+fn sfw_mode(conn: &Connection) -> Result<bool, AppError> {
+  Ok(settings_get(conn, "sfw_mode")?.is_some_and(|v| v == "true" || v == "1"))
+}
+
+#[tauri::command]
+pub fn get_sfw_mode(app: AppHandle) -> Result<bool, AppError> {
   let root = get_root(&app)?;
+  let conn = open_conn_for_root(&app, &root)?;
+  sfw_mode(&conn)
+}
+
+#[tauri::command]
+pub fn set_sfw_mode(app: AppHandle, enabled: bool) -> Result<(), AppError> {
+  let root = get_root(&app)?;
+  let conn = open_conn_for_root(&app, &root)?;
+  settings_set(&conn, "sfw_mode", if enabled { "true" } else { "false" })
+}
+
+#[tauri::command]
+pub fn add_e621_post(app: AppHandle, post: E621PostInput) -> Result<Status, AppError> {
+  import_e621_post(&app, post, None)
+}
+
+// `sync_batch` tags rows inserted by a sync run so they can be reviewed together
+fn import_e621_post(app: &AppHandle, post: E621PostInput, sync_batch: Option<&str>) -> Result<Status, AppError> {
+  let root = get_root(app)?;
   library::ensure_layout(&root)?;
 
-  let conn = db::open(&library::db_path(&root))?;
-  db::init_schema(&conn)?;
+  let conn = open_conn_for_root(app, &root)?;
+
+  let trashed_filter = dedupe_trashed_filter(&conn)?;
 
-  // dedupe by (source, id)
-  let exists: i64 = conn
+  // dedupe by (source, id); the MIN() is NULL when absent, 1 when only a trashed copy exists
+  let existing: Option<bool> = conn
     .query_row(
-      "SELECT COUNT(*) FROM items WHERE source='e621' AND source_id=? AND trashed_at IS NULL",
+      &format!("SELECT MIN(trashed_at IS NOT NULL) FROM items WHERE source='e621' AND source_id=?{trashed_filter}"),
       params![post.id.to_string()],
       |r: &Row| r.get(0),
-    )
-    .map_err(|e| e.to_string())?;
-  if exists > 0 {
-    return Ok(Status { ok: true, message: "Already downloaded".into() });
+    )?;
+  // a re-seen post still reports its current upstream status
+  if existing.is_some() {
+    if let Some(status) = &post.remote_status {
+      conn.execute(
+        "UPDATE items SET remote_status = ? WHERE source = 'e621' AND source_id = ?",
+        params![status, post.id.to_string()],
+      )?;
+    }
+  }
+  match existing {
+    Some(true) => return Ok(Status { ok: true, message: TRASHED_SKIP_MESSAGE.into(), item_id: None }),
+    Some(false) => return Ok(Status { ok: true, message: "Already downloaded".into(), item_id: None }),
+    None => {}
   }
 
   // dedupe by md5 if present
   if let Some(md5) = &post.file_md5 {
-    let md5_exists: i64 = conn
-      .query_row(
-        "SELECT COUNT(*) FROM items WHERE md5=? AND trashed_at IS NULL",
-        params![md5],
-        |r: &Row| r.get(0),
-      )
-      .map_err(|e| e.to_string())?;
-    if md5_exists > 0 {
-      return Ok(Status { ok: true, message: "Already downloaded (md5 match)".into() });
+    if let Some(status) = md5_match_status(&conn, md5)? {
+      return Ok(status);
     }
   }
 
   // filename: primaryArtist_e621_<id>.<ext>
   let primary_artist = sanitize_slug(&pick_primary_artist(&post.tags.artist));
-  let ext = post.file_ext.trim().to_lowercase();
+  let mut ext = post.file_ext.trim().to_lowercase();
   if ext.is_empty() {
-    return Err("Missing file_ext from e621".into());
+    return Err(AppError::InvalidInput("Missing file_ext from e621".into()));
   }
 
-  let base = format!("{primary_artist}_e621_{}.{}", post.id, ext);
+  // samples are downscaled stills (a video's is just its poster frame)
+  let use_sample = post.sample_url.is_some()
+    && SAMPLE_SOURCE_EXTS.contains(&ext.as_str())
+    && prefer_sample(&conn)?;
+  let (download_url, variant) = match (&post.sample_url, use_sample) {
+    (Some(url), true) => {
+      ext = url.rsplit('.').next().filter(|e| e.len() <= 4).unwrap_or("jpg").to_lowercase();
+      (url.as_str(), "sample")
+    }
+    _ => (post.file_url.as_str(), "full"),
+  };
+
+  let post_id = post.id.to_string();
   let media_dir = root.join("media");
-  let mut filename = base.clone();
-  let mut dest_path = media_dir.join(&filename);
+  let media_name = |ext: &str| unique_media_name(&media_dir, &filename_for(&conn, DEFAULT_FILENAME_TEMPLATE, &FilenameParts {
+    artist: &primary_artist,
+    source: "e621",
+    id: &post_id,
+    md5: post.file_md5.as_deref(),
+    ext,
+  }));
+  let mut filename = media_name(&ext);
 
-  // ensure unique filename
-  let mut n = 1;
-  while dest_path.exists() {
-    filename = format!("{primary_artist}_e621_{}_dup{}.{}", post.id, n, ext);
-    dest_path = media_dir.join(&filename);
-    n += 1;
-  }
+  ensure_free_space(&conn, &root)?;
 
   // temp download
   let tmp_dir = root.join(".cache").join("tmp");
-  fs::create_dir_all(&tmp_dir).map_err(|e| e.to_string())?;
+  fs::create_dir_all(&tmp_dir)?;
   let tmp_path = tmp_dir.join(format!("{filename}.part"));
 
+  // a queued download cancelled before it started never connects
+  if download_cancelled(app, &post_id) {
+    return Err(AppError::Cancelled(format!("Download of post {} cancelled", post_id)));
+  }
+
   let client = reqwest::blocking::Client::new();
   let mut resp = client
-    .get(&post.file_url)
-    .header("User-Agent", "TailBurrow/0.2.1 (local archiver)")
-    .send()
-    .map_err(|e| e.to_string())?;
+    .get(download_url)
+    .header("User-Agent", e621_user_agent(&conn))
+    .send()?;
 
   if !resp.status().is_success() {
-    return Err(format!("Download failed: HTTP {}", resp.status()));
+    return Err(AppError::Network(format!("Download failed: HTTP {}", resp.status())));
+  }
+
+  let mut file = fs::File::create(&tmp_path)?;
+  if let Err(e) = download_with_progress(app, &post_id, &mut resp, &mut file) {
+    drop(file);
+    let _ = fs::remove_file(&tmp_path);
+    return Err(e);
+  }
+  file.flush()?;
+
+  // md5 stays e621's hash of the original for dedupe; a sample's own hash goes in
+  // file_md5, as does the hash of a download e621 gave no md5 for
+  let content_md5 = if use_sample || post.file_md5.is_none() { Some(md5_file(&tmp_path)?) } else { None };
+
+  // no md5 from the API: the hash of what arrived still keeps identical content from being stored twice
+  if post.file_md5.is_none() {
+    if let Some(status) = md5_match_status(&conn, content_md5.as_deref().unwrap_or_default())? {
+      let _ = fs::remove_file(&tmp_path);
+      return Ok(status);
+    }
   }
 
-  let mut file = fs::File::create(&tmp_path).map_err(|e| e.to_string())?;
-  std::io::copy(&mut resp, &mut file).map_err(|e| e.to_string())?;
-  file.flush().map_err(|e| e.to_string())?;
+  let kind = infer::get_from_path(&tmp_path).ok().flatten();
+  let mime = kind.map(|t| t.mime_type().to_string());
 
-  fs::rename(&tmp_path, &dest_path).map_err(|e| e.to_string())?;
+  // e621's file_ext occasionally disagrees with what was actually served
+  if let Some(real) = corrected_ext(kind, &ext) {
+    ext = real.to_string();
+    filename = media_name(&ext);
+  }
+  let dest_path = media_dir.join(&filename);
+  fs::rename(&tmp_path, &dest_path)?;
 
   // --- NEW: Generate Thumbnail Immediately ---
   let file_rel = format!("media/{}", filename.replace('\\', "/"));
-  generate_and_save_thumb(&root, &file_rel); // <--- Added this call
+  generate_and_save_thumb(&root, &file_rel, thumb_width(&conn)); // <--- Added this call
   // -------------------------------------------
 
   let added_at = Utc::now().to_rfc3339();
+  let dims = image::image_dimensions(&dest_path).ok();
 
   conn.execute(
     r#"
-    INSERT INTO items(source, source_id, md5, remote_url, file_rel, ext, rating, fav_count, score_total, created_at, added_at, primary_artist)
-    VALUES('e621', ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+    INSERT INTO items(source, source_id, md5, remote_url, file_rel, ext, mime, rating, fav_count, score_total, created_at, added_at, primary_artist, sync_batch, width, height, discovered_via, remote_status, size_bytes, file_md5, variant, uploader_id, approver_id)
+    VALUES('e621', ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, 'e621', ?, ?, ?, ?, ?, ?)
     "#,
     params![
       post.id.to_string(),
       post.file_md5,
-      post.file_url,
+      download_url,
       file_rel,
       ext,
+      mime,
       post.rating,
       post.fav_count,
       post.score_total,
       post.created_at,
       added_at,
-      primary_artist
+      primary_artist,
+      sync_batch,
+      dims.map(|d| d.0),
+      dims.map(|d| d.1),
+      post.remote_status,
+      fs::metadata(&dest_path).ok().map(|m| m.len() as i64),
+      content_md5,
+      variant,
+      post.uploader_id,
+      post.approver_id
     ],
-  ).map_err(|e| e.to_string())?;
+  )?;
 
   let item_id = conn.last_insert_rowid();
 
+  if let Some(raw) = &post.raw {
+    store_item_raw(&conn, item_id, raw)?;
+  }
+
   // typed tags
-  for t in post.tags.general { let id = upsert_tag(&conn, &t, "general")?; conn.execute("INSERT OR IGNORE INTO item_tags(item_id, tag_id) VALUES(?,?)", params![item_id, id]).map_err(|e| e.to_string())?; }
-  for t in post.tags.species { let id = upsert_tag(&conn, &t, "species")?; conn.execute("INSERT OR IGNORE INTO item_tags(item_id, tag_id) VALUES(?,?)", params![item_id, id]).map_err(|e| e.to_string())?; }
-  for t in post.tags.character { let id = upsert_tag(&conn, &t, "character")?; conn.execute("INSERT OR IGNORE INTO item_tags(item_id, tag_id) VALUES(?,?)", params![item_id, id]).map_err(|e| e.to_string())?; }
-  for t in post.tags.artist { let id = upsert_tag(&conn, &t, "artist")?; conn.execute("INSERT OR IGNORE INTO item_tags(item_id, tag_id) VALUES(?,?)", params![item_id, id]).map_err(|e| e.to_string())?; }
-  for t in post.tags.meta { let id = upsert_tag(&conn, &t, "meta")?; conn.execute("INSERT OR IGNORE INTO item_tags(item_id, tag_id) VALUES(?,?)", params![item_id, id]).map_err(|e| e.to_string())?; }
-  for t in post.tags.lore { let id = upsert_tag(&conn, &t, "lore")?; conn.execute("INSERT OR IGNORE INTO item_tags(item_id, tag_id) VALUES(?,?)", params![item_id, id]).map_err(|e| e.to_string())?; }
-  for t in post.tags.copyright { let id = upsert_tag(&conn, &t, "copyright")?; conn.execute("INSERT OR IGNORE INTO item_tags(item_id, tag_id) VALUES(?,?)", params![item_id, id]).map_err(|e| e.to_string())?; }
+  for t in post.tags.general { let id = upsert_tag(&conn, &t, "general")?; conn.execute("INSERT OR IGNORE INTO item_tags(item_id, tag_id) VALUES(?,?)", params![item_id, id])?; }
+  for t in post.tags.species { let id = upsert_tag(&conn, &t, "species")?; conn.execute("INSERT OR IGNORE INTO item_tags(item_id, tag_id) VALUES(?,?)", params![item_id, id])?; }
+  for t in post.tags.character { let id = upsert_tag(&conn, &t, "character")?; conn.execute("INSERT OR IGNORE INTO item_tags(item_id, tag_id) VALUES(?,?)", params![item_id, id])?; }
+  for t in post.tags.artist { let id = upsert_tag(&conn, &t, "artist")?; conn.execute("INSERT OR IGNORE INTO item_tags(item_id, tag_id) VALUES(?,?)", params![item_id, id])?; }
+  for t in post.tags.meta { let id = upsert_tag(&conn, &t, "meta")?; conn.execute("INSERT OR IGNORE INTO item_tags(item_id, tag_id) VALUES(?,?)", params![item_id, id])?; }
+  for t in post.tags.lore { let id = upsert_tag(&conn, &t, "lore")?; conn.execute("INSERT OR IGNORE INTO item_tags(item_id, tag_id) VALUES(?,?)", params![item_id, id])?; }
+  for t in post.tags.copyright { let id = upsert_tag(&conn, &t, "copyright")?; conn.execute("INSERT OR IGNORE INTO item_tags(item_id, tag_id) VALUES(?,?)", params![item_id, id])?; }
+  db::reindex_item(&conn, item_id)?;
 
   // sources urls
   for u in post.sources {
@@ -342,35 +771,169 @@ pub fn add_e621_post(app: AppHandle, post: E621PostInput) -> Result<Status, Stri
     conn.execute(
       "INSERT OR IGNORE INTO item_sources(item_id, source_row_id) VALUES(?, ?)",
       params![item_id, sid],
-    ).map_err(|e| e.to_string())?;
+    )?;
+  }
+
+  Ok(Status { ok: true, message: "Downloaded into library".into(), item_id: None })
+}
+
+#[derive(Clone, Serialize)]
+pub struct DownloadProgress {
+  pub item_source_id: String,
+  pub downloaded: u64,
+  pub total: Option<u64>,
+}
+
+// Don't flood IPC: at most a few progress events per second per file
+const PROGRESS_EVENT_INTERVAL: std::time::Duration = std::time::Duration::from_millis(250);
+
+fn download_cancelled(app: &AppHandle, source_id: &str) -> bool {
+  let state = app.state::<Arc<Mutex<SyncState>>>();
+  let cancelled = state.lock().is_ok_and(|st| st.cancelled_downloads.contains(source_id));
+  cancelled
+}
+
+// Aborts one queued or running sync download; the rest of the sync carries on.
+// The post is recorded as unavailable with reason `cancelled_by_user`. Also stops
+// a recheck_e621_match upgrade downloading that post.
+#[tauri::command]
+pub fn cancel_download(
+  state: tauri::State<'_, Arc<Mutex<SyncState>>>,
+  source_id: String,
+) -> Result<Status, AppError> {
+  let mut st = state.lock().map_err(|_| "Sync state lock poisoned")?;
+  // outside a sync, a cancel nobody consumes would linger and fail a later add of that post
+  if !st.status.running && !st.recheck_downloads.contains(source_id.trim()) {
+    return Err(AppError::InvalidInput("No sync is running".into()));
+  }
+  st.cancelled_downloads.insert(source_id.trim().to_string());
+  Ok(Status { ok: true, message: format!("Cancelling download of post {}", source_id.trim()), item_id: None })
+}
+
+fn download_with_progress(
+  app: &AppHandle,
+  source_id: &str,
+  resp: &mut reqwest::blocking::Response,
+  file: &mut fs::File,
+) -> Result<u64, AppError> {
+  let total = resp.content_length();
+  let mut downloaded: u64 = 0;
+  let mut buf = vec![0u8; 64 * 1024];
+  let mut last_emit = std::time::Instant::now();
+
+  let emit = |downloaded: u64| {
+    let _ = app.emit("download-progress", DownloadProgress {
+      item_source_id: source_id.to_string(),
+      downloaded,
+      total,
+    });
+  };
+
+  loop {
+    if download_cancelled(app, source_id) {
+      return Err(AppError::Cancelled(format!("Download of post {} cancelled", source_id)));
+    }
+    let n = resp.read(&mut buf)?;
+    if n == 0 {
+      break;
+    }
+    file.write_all(&buf[..n])?;
+    downloaded += n as u64;
+
+    if last_emit.elapsed() >= PROGRESS_EVENT_INTERVAL {
+      emit(downloaded);
+      last_emit = std::time::Instant::now();
+    }
   }
 
-  Ok(Status { ok: true, message: "Downloaded into library".into() })
+  // always report completion
+  emit(downloaded);
+  Ok(downloaded)
 }
 
 #[derive(Serialize)]
 pub struct E621CredInfo {
   pub username: Option<String>,
   pub has_api_key: bool,
+  // RFC 3339 time of the last successful test call with the current credentials
+  pub verified_at: Option<String>,
+}
+
+// e621's API rules ask for a descriptive UA naming the app and a contact
+pub fn e621_user_agent(conn: &Connection) -> String {
+  build_user_agent(settings_get(conn, "user_agent_contact").ok().flatten())
+}
+
+pub const E621_DEFAULT_BASE_URL: &str = "https://e621.net";
+
+// Root every e621 API call is built on; e621_base_url points it at e926 or a local mock
+pub fn e621_base_url(conn: &Connection) -> String {
+  settings_get(conn, "e621_base_url")
+    .ok()
+    .flatten()
+    .map(|v| v.trim().trim_end_matches('/').to_string())
+    .filter(|v| !v.is_empty())
+    .unwrap_or_else(|| E621_DEFAULT_BASE_URL.to_string())
+}
+
+pub fn e621_api_url(conn: &Connection, endpoint: &str) -> String {
+  format!("{}/{}", e621_base_url(conn), endpoint)
+}
+
+pub fn build_user_agent(contact: Option<String>) -> String {
+  let version = env!("CARGO_PKG_VERSION");
+  match contact {
+    Some(c) if !c.trim().is_empty() => format!("Guacamole Viewer/{} (by {})", version, c.trim()),
+    _ => format!("Guacamole Viewer/{}", version),
+  }
 }
 
-fn load_e621_creds(conn: &Connection) -> Result<(String, String), String> {
+fn load_e621_creds(conn: &Connection) -> Result<(String, String), AppError> {
   let username = settings_get(conn, "e621_username")?
-    .ok_or("e621 username not set")?;
+    .ok_or_else(|| AppError::NotConfigured("e621 username not set".into()))?;
   let api_key = settings_get(conn, "e621_api_key")?
-    .ok_or("e621 api key not set")?;
+    .ok_or_else(|| AppError::NotConfigured("e621 api key not set".into()))?;
   Ok((username, api_key))
 }
 
+// Turn a non-success e621 response into an error that keeps the API's explanation
+fn e621_error(context: &str, resp: reqwest::blocking::Response) -> AppError {
+  let status = resp.status();
+  let detail = resp
+    .json::<serde_json::Value>()
+    .ok()
+    .and_then(|v| {
+      v.get("message")
+        .or_else(|| v.get("reason"))
+        .and_then(|m| m.as_str())
+        .map(|m| m.to_string())
+    });
+
+  match detail {
+    Some(d) => AppError::Network(format!("{context}: HTTP {status} ({d})")),
+    None => AppError::Network(format!("{context}: HTTP {status}")),
+  }
+}
+
+// Seconds from a 429's Retry-After header (e621 sends delta-seconds)
+fn retry_after(resp: &reqwest::blocking::Response) -> Option<std::time::Duration> {
+  resp
+    .headers()
+    .get(reqwest::header::RETRY_AFTER)
+    .and_then(|v| v.to_str().ok())
+    .and_then(|v| v.trim().parse::<u64>().ok())
+    .map(std::time::Duration::from_secs)
+}
+
 fn upsert_unavailable(
   conn: &Connection,
   source: &str,
   source_id: &str,
   reason: &str,
   sources: Vec<String>,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
   let seen_at = Utc::now().to_rfc3339();
-  let sources_json = serde_json::to_string(&sources).map_err(|e| e.to_string())?;
+  let sources_json = serde_json::to_string(&sources)?;
 
   conn.execute(
     r#"
@@ -380,28 +943,29 @@ fn upsert_unavailable(
     DO UPDATE SET seen_at=excluded.seen_at, reason=excluded.reason, sources_json=excluded.sources_json
     "#,
     params![source, source_id, seen_at, reason, sources_json],
-  ).map_err(|e| e.to_string())?;
+  )?;
 
   Ok(())
 }
 
 #[tauri::command]
-pub fn e621_get_cred_info(app: AppHandle) -> Result<E621CredInfo, String> {
+pub fn e621_get_cred_info(app: AppHandle) -> Result<E621CredInfo, AppError> {
   let root = get_root(&app)?;
-  let conn = open_conn_for_root(&root)?;
+  let conn = open_conn_for_root(&app, &root)?;
   let username = settings_get(&conn, "e621_username")?;
   let has_api_key = settings_get(&conn, "e621_api_key")?.is_some();
-  Ok(E621CredInfo { username, has_api_key })
+  let verified_at = settings_get(&conn, "e621_verified_at")?;
+  Ok(E621CredInfo { username, has_api_key, verified_at })
 }
 
 #[tauri::command]
-pub fn e621_set_credentials(app: AppHandle, username: String, api_key: String) -> Result<Status, String> {
+pub fn e621_set_credentials(app: AppHandle, username: String, api_key: String, verify: Option<bool>) -> Result<Status, AppError> {
   let root = get_root(&app)?;
-  let conn = open_conn_for_root(&root)?;
+  let conn = open_conn_for_root(&app, &root)?;
 
   let u = username.trim();
   if u.is_empty() {
-    return Err("Username cannot be empty".into());
+    return Err(AppError::InvalidInput("Username cannot be empty".into()));
   }
   settings_set(&conn, "e621_username", u)?;
 
@@ -409,69 +973,119 @@ pub fn e621_set_credentials(app: AppHandle, username: String, api_key: String) -
   if !api_key.trim().is_empty() {
     settings_set(&conn, "e621_api_key", api_key.trim())?;
   }
+  // new credentials haven't been checked yet
+  settings_delete(&conn, "e621_verified_at")?;
+
+  if verify.unwrap_or(false) {
+    // the credentials stay saved either way; ok reports whether they work
+    return Ok(match verify_e621_creds(&conn) {
+      Ok(()) => Status { ok: true, message: "Saved and verified e621 credentials".into(), item_id: None },
+      Err(e) => Status { ok: false, message: format!("Saved e621 credentials, but verification failed: {e}"), item_id: None },
+    });
+  }
 
-  Ok(Status { ok: true, message: "Saved e621 credentials".into() })
+  Ok(Status { ok: true, message: "Saved e621 credentials".into(), item_id: None })
 }
 
-#[tauri::command]
-pub fn e621_test_connection(app: AppHandle) -> Result<Status, String> {
-  let root = get_root(&app)?;
-  let conn = open_conn_for_root(&root)?;
-  let (username, api_key) = load_e621_creds(&conn)?;
+// One authenticated request; records e621_verified_at when it succeeds
+fn verify_e621_creds(conn: &Connection) -> Result<(), AppError> {
+  let (username, api_key) = load_e621_creds(conn)?;
 
   let client = reqwest::blocking::Client::new();
   let resp = client
-    .get("https://e621.net/posts.json")
+    .get(e621_api_url(conn, "posts.json"))
     .basic_auth(username, Some(api_key))
-    .header("User-Agent", "TailBurrow/0.2.1 (test)")
+    .header("User-Agent", e621_user_agent(conn))
     .query(&[("limit", "1"), ("tags", "order:id_desc")])
-    .send()
-    .map_err(|e| e.to_string())?;
+    .send()?;
 
   if !resp.status().is_success() {
-    return Err(format!("Test failed: HTTP {}", resp.status()));
+    return Err(e621_error("Test failed", resp));
   }
 
-  Ok(Status { ok: true, message: "Connected to e621 successfully".into() })
+  settings_set(conn, "e621_verified_at", &Utc::now().to_rfc3339())?;
+  Ok(())
+}
+
+#[tauri::command]
+pub fn e621_test_connection(app: AppHandle) -> Result<Status, AppError> {
+  let root = get_root(&app)?;
+  let conn = open_conn_for_root(&app, &root)?;
+  verify_e621_creds(&conn)?;
+
+  Ok(Status { ok: true, message: "Connected to e621 successfully".into(), item_id: None })
 }
 
 #[tauri::command]
-pub fn e621_fetch_posts(app: AppHandle, tags: String, limit: u32, page: Option<String>) -> Result<serde_json::Value, String> {
+pub fn e621_fetch_posts(app: AppHandle, tags: String, limit: u32, page: Option<String>) -> Result<serde_json::Value, AppError> {
   let root = get_root(&app)?;
-  let conn = open_conn_for_root(&root)?;
+  let conn = open_conn_for_root(&app, &root)?;
   let (username, api_key) = load_e621_creds(&conn)?;
 
   let client = reqwest::blocking::Client::new();
   let mut req = client
-    .get("https://e621.net/posts.json")
+    .get(e621_api_url(&conn, "posts.json"))
     .basic_auth(username, Some(api_key))
-    .header("User-Agent", "TailBurrow/0.2.1 (feeds)")
+    .header("User-Agent", e621_user_agent(&conn))
     .query(&[("tags", tags), ("limit", limit.to_string())]);
 
   if let Some(p) = page {
     req = req.query(&[("page", p)]);
   }
 
-  let resp = req.send().map_err(|e| e.to_string())?;
+  let resp = req.send()?;
   if !resp.status().is_success() {
-    return Err(format!("e621 error: HTTP {}", resp.status()));
+    return Err(e621_error("e621 error", resp));
   }
 
-  resp.json::<serde_json::Value>().map_err(|e| e.to_string())
+  Ok(resp.json::<serde_json::Value>()?)
 }
 
 #[tauri::command]
-pub fn e621_sync_status(state: tauri::State<'_, Arc<Mutex<SyncState>>>) -> Result<SyncStatus, String> {
+pub fn e621_sync_status(state: tauri::State<'_, Arc<Mutex<SyncState>>>) -> Result<SyncStatus, AppError> {
   let st = state.lock().map_err(|_| "Sync state lock poisoned")?;
   Ok(st.status.clone())
 }
 
 #[tauri::command]
-pub fn e621_sync_cancel(state: tauri::State<'_, Arc<Mutex<SyncState>>>) -> Result<Status, String> {
+pub fn e621_sync_cancel(state: tauri::State<'_, Arc<Mutex<SyncState>>>) -> Result<Status, AppError> {
   let mut st = state.lock().map_err(|_| "Sync state lock poisoned")?;
   st.cancel_requested = true;
   st.status.cancelled = true;
-  Ok(Status { ok: true, message: "Cancel requested".into() })
+  st.resume.notify_all();
+  Ok(Status { ok: true, message: "Cancel requested".into(), item_id: None })
+}
+
+#[tauri::command]
+pub fn e621_sync_pause(state: tauri::State<'_, Arc<Mutex<SyncState>>>) -> Result<Status, AppError> {
+  let mut st = state.lock().map_err(|_| "Sync state lock poisoned")?;
+  if !st.status.running {
+    return Err(AppError::InvalidInput("No sync is running".into()));
+  }
+  st.status.paused = true;
+  Ok(Status { ok: true, message: "Sync paused".into(), item_id: None })
+}
+
+#[tauri::command]
+pub fn e621_sync_resume(state: tauri::State<'_, Arc<Mutex<SyncState>>>) -> Result<Status, AppError> {
+  let mut st = state.lock().map_err(|_| "Sync state lock poisoned")?;
+  st.status.paused = false;
+  st.resume.notify_all();
+  Ok(Status { ok: true, message: "Sync resumed".into(), item_id: None })
+}
+
+// Blocks the worker while paused. Returns true if the sync was cancelled meanwhile.
+fn wait_while_paused(state: &Arc<Mutex<SyncState>>) -> Result<bool, AppError> {
+  let mut st = state.lock().map_err(|_| "Sync state lock poisoned")?;
+  let resume = st.resume.clone();
+  while st.status.paused && !st.cancel_requested {
+    // wake up now and then in case a notify was missed
+    st = resume
+      .wait_timeout(st, std::time::Duration::from_secs(1))
+      .map_err(|_| "Sync state lock poisoned")?
+      .0;
+  }
+  Ok(st.cancel_requested)
 }
 
 #[tauri::command]
@@ -479,13 +1093,140 @@ pub fn e621_sync_start(
   app: AppHandle,
   state: tauri::State<'_, Arc<Mutex<SyncState>>>,
   max_new_downloads: Option<u32>,
-) -> Result<Status, String> {
+  incremental: Option<bool>,
+  fav_user: Option<String>,
+) -> Result<Status, AppError> {
+  // whose favorites to mirror; auth always uses the stored credentials
+  let fav_user = match fav_user.map(|u| u.trim().to_string()).filter(|u| !u.is_empty()) {
+    Some(u) if u.chars().any(char::is_whitespace) => {
+      return Err(AppError::InvalidInput("Favorites username can't contain spaces".into()));
+    }
+    other => other,
+  };
+  start_e621_sync(app, state.inner().clone(), max_new_downloads, incremental.unwrap_or(false), fav_user)
+}
+
+// Highest post id downloaded by a completed sync of this favorites query
+fn high_water_key(username: &str) -> String {
+  format!("e621_high_water:fav:{}", username.to_lowercase())
+}
+
+const MAX_THROTTLED_RETRIES: u32 = 5;
+
+// Sends the request, waiting out 429s as told by Retry-After; any other status is returned as is
+fn send_with_retry(
+  build: impl Fn() -> reqwest::blocking::RequestBuilder,
+) -> Result<reqwest::blocking::Response, AppError> {
+  send_with_retry_until(build, || false)
+}
+
+// A server asking for longer than this still gets retried, just sooner
+const MAX_RETRY_AFTER: std::time::Duration = std::time::Duration::from_millis(E621_DELAY_RANGE.1);
+const RETRY_SLEEP_SLICE: std::time::Duration = std::time::Duration::from_millis(250);
+
+// send_with_retry for callers that can be cancelled: the throttle wait is slept in
+// short slices and ends with AppError::Cancelled once `cancelled` returns true
+fn send_with_retry_until(
+  build: impl Fn() -> reqwest::blocking::RequestBuilder,
+  cancelled: impl Fn() -> bool,
+) -> Result<reqwest::blocking::Response, AppError> {
+  let mut throttled_retries = 0;
+  loop {
+    let resp = build().send()?;
+    if resp.status().as_u16() == 429 && throttled_retries < MAX_THROTTLED_RETRIES {
+      throttled_retries += 1;
+      let wait = retry_after(&resp).unwrap_or(std::time::Duration::from_secs(10)).min(MAX_RETRY_AFTER);
+      let until = std::time::Instant::now() + wait;
+      while let Some(left) = until.checked_duration_since(std::time::Instant::now()).filter(|d| !d.is_zero()) {
+        if cancelled() {
+          return Err(AppError::Cancelled("Cancelled while throttled".into()));
+        }
+        std::thread::sleep(left.min(RETRY_SLEEP_SLICE));
+      }
+      continue;
+    }
+    return Ok(resp);
+  }
+}
+
+// Per-source politeness, tunable from settings within these bounds
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncPacing {
+  pub e621_concurrency: u32,
+  pub e621_delay_ms: u64,
+  pub fa_delay_ms: u64,
+}
+
+const E621_CONCURRENCY_RANGE: (u32, u32) = (1, 4);
+const E621_DELAY_RANGE: (u64, u64) = (250, 60_000);
+// FA is scraped with cookies; going below the long-standing 800ms isn't allowed
+const FA_DELAY_RANGE: (u64, u64) = (800, 60_000);
+
+impl Default for SyncPacing {
+  fn default() -> Self {
+    SyncPacing { e621_concurrency: 1, e621_delay_ms: 500, fa_delay_ms: 800 }
+  }
+}
+
+impl SyncPacing {
+  fn clamped(self) -> Self {
+    SyncPacing {
+      e621_concurrency: self.e621_concurrency.clamp(E621_CONCURRENCY_RANGE.0, E621_CONCURRENCY_RANGE.1),
+      e621_delay_ms: self.e621_delay_ms.clamp(E621_DELAY_RANGE.0, E621_DELAY_RANGE.1),
+      fa_delay_ms: self.fa_delay_ms.clamp(FA_DELAY_RANGE.0, FA_DELAY_RANGE.1),
+    }
+  }
+}
+
+pub fn load_sync_pacing(conn: &Connection) -> SyncPacing {
+  let d = SyncPacing::default();
+  let num = |key: &str| settings_get(conn, key).ok().flatten().and_then(|v| v.trim().parse::<u64>().ok());
+  SyncPacing {
+    e621_concurrency: num("e621_concurrency").map(|n| n as u32).unwrap_or(d.e621_concurrency),
+    e621_delay_ms: num("e621_delay_ms").unwrap_or(d.e621_delay_ms),
+    fa_delay_ms: num("fa_delay_ms").unwrap_or(d.fa_delay_ms),
+  }
+  .clamped()
+}
+
+#[tauri::command]
+pub fn get_sync_pacing(app: AppHandle) -> Result<SyncPacing, AppError> {
+  let root = get_root(&app)?;
+  let conn = open_conn_for_root(&app, &root)?;
+  Ok(load_sync_pacing(&conn))
+}
+
+// Out-of-range values are clamped; the stored values are returned
+#[tauri::command]
+pub fn set_sync_pacing(app: AppHandle, pacing: SyncPacing) -> Result<SyncPacing, AppError> {
+  let root = get_root(&app)?;
+  let conn = open_conn_for_root(&app, &root)?;
+  let pacing = pacing.clamped();
+  settings_set(&conn, "e621_concurrency", &pacing.e621_concurrency.to_string())?;
+  settings_set(&conn, "e621_delay_ms", &pacing.e621_delay_ms.to_string())?;
+  settings_set(&conn, "fa_delay_ms", &pacing.fa_delay_ms.to_string())?;
+  Ok(pacing)
+}
+
+// Imports between forced WAL checkpoints in the e621 worker
+const SYNC_CHECKPOINT_EVERY: u32 = 200;
+
+// Shared by the command and the auto-sync scheduler
+fn start_e621_sync(
+  app: AppHandle,
+  state: Arc<Mutex<SyncState>>,
+  max_new_downloads: Option<u32>,
+  incremental: bool,
+  fav_user: Option<String>,
+) -> Result<Status, AppError> {
+  library::acquire_lock(&get_root(&app)?)?;
   {
     let mut st = state.lock().map_err(|_| "Sync state lock poisoned")?;
     if st.status.running {
       return Err("Sync already running".into());
     }
     st.cancel_requested = false;
+    st.cancelled_downloads.clear();
     st.status = SyncStatus {
       running: true,
       cancelled: false,
@@ -495,13 +1236,15 @@ pub fn e621_sync_start(
   }
 
   let app2 = app.clone();
-  let state2 = state.inner().clone();
+  let state2 = state.clone();
+  let sync_batch = new_sync_batch("e621");
+  let started = std::time::Instant::now();
 
   std::thread::spawn(move || {
-    let result: Result<(), String> = (|| {
+    let result: Result<(), AppError> = (|| {
       let root = get_root(&app2)?;
-      let conn = db::open(&library::db_path(&root))?;
-      db::init_schema(&conn)?;
+      let conn = open_conn_for_root(&app, &root)?;
+      settings_set(&conn, "e621_last_sync_at", &Utc::now().to_rfc3339())?;
 
       // Load creds from DB settings (you already implemented e621 creds in settings)
       // This expects keys: e621_username, e621_api_key
@@ -509,29 +1252,119 @@ pub fn e621_sync_start(
         "SELECT value FROM settings WHERE key='e621_username'",
         [],
         |r: &Row| r.get(0),
-      ).map_err(|_| "e621 username not set")?;
+      ).map_err(|_| AppError::NotConfigured("e621 username not set".into()))?;
 
       let api_key: String = conn.query_row(
         "SELECT value FROM settings WHERE key='e621_api_key'",
         [],
         |r: &Row| r.get(0),
-      ).map_err(|_| "e621 api key not set")?;
+      ).map_err(|_| AppError::NotConfigured("e621 api key not set".into()))?;
 
       let client = reqwest::blocking::Client::new();
-
-      let mut page: u32 = 1;
-
-      loop {
-        // cancel check
+      let user_agent = e621_user_agent(&conn);
+      let posts_url = e621_api_url(&conn, "posts.json");
+      let trashed_filter = dedupe_trashed_filter(&conn)?;
+      let head_check = settings_get(&conn, "presync_head_check")?.is_some_and(|v| v == "true" || v == "1");
+      let skip_unsupported = skip_unsupported_formats(&conn)?;
+      let pacing = load_sync_pacing(&conn);
+      let delay = std::time::Duration::from_millis(pacing.e621_delay_ms);
+
+      // ids come newest first, so an incremental run can stop at the last run's top.
+      // `fav:` with `order:id_desc` sorts by post id, not by when it was favorited:
+      // an old post favorited since the last run sits below the mark and is only
+      // picked up by a full run.
+      let fav_user = fav_user.unwrap_or_else(|| username.clone());
+      let high_water: Option<i64> = settings_get(&conn, &high_water_key(&fav_user))?.and_then(|v| v.parse().ok());
+      let stop_at = if incremental { high_water } else { None };
+      let highest_ok = std::cell::Cell::new(high_water.unwrap_or(0));
+      // the mark can't pass a failed post, or the next incremental run would never retry it
+      let lowest_failed: std::cell::Cell<Option<i64>> = std::cell::Cell::new(None);
+      let imported_since_checkpoint = std::cell::Cell::new(0u32);
+
+      // Downloads `jobs` side by side (each import opens its own connection), then
+      // records the outcomes here on the worker's connection
+      let run_batch = |jobs: Vec<(i64, E621PostInput)>| -> Result<(), AppError> {
+        if jobs.is_empty() {
+          return Ok(());
+        }
         {
-          let st = state2.lock().map_err(|_| "Sync state lock poisoned")?;
-          if st.cancel_requested {
-            break;
+          let mut st = state2.lock().map_err(|_| "Sync state lock poisoned")?;
+          st.status.new_attempted += jobs.len() as u32;
+        }
+
+        let results: Vec<(i64, Result<Status, AppError>)> = std::thread::scope(|s| {
+          let handles: Vec<_> = jobs
+            .into_iter()
+            .map(|(id, input)| {
+              let app = &app2;
+              let batch = sync_batch.as_str();
+              (id, s.spawn(move || import_e621_post(app, input, Some(batch))))
+            })
+            .collect();
+          handles
+            .into_iter()
+            .map(|(id, h)| (id, h.join().unwrap_or_else(|_| Err(AppError::Other("Download thread panicked".into())))))
+            .collect()
+        });
+
+        // keep the WAL from growing through a long mirror
+        let done = imported_since_checkpoint.get() + results.len() as u32;
+        if done >= SYNC_CHECKPOINT_EVERY {
+          if let Err(e) = db::checkpoint(&conn) {
+            log::warn!("WAL checkpoint during sync failed: {}", e);
           }
+          imported_since_checkpoint.set(0);
+        } else {
+          imported_since_checkpoint.set(done);
         }
 
-        // stop if hit max
-        {
+        let mut disk_full = None;
+        for (post_id, res) in results {
+          match res {
+            Ok(_) => {
+              highest_ok.set(highest_ok.get().max(post_id));
+              let mut st = state2.lock().map_err(|_| "Sync state lock poisoned")?;
+              st.status.downloaded_ok += 1;
+            }
+            // every further download would fail the same way
+            Err(err @ AppError::DiskFull(_)) => disk_full = Some(err),
+            Err(AppError::Cancelled(_)) => {
+              upsert_unavailable(&conn, "e621", &post_id.to_string(), "cancelled_by_user", vec![])?;
+              let mut st = state2.lock().map_err(|_| "Sync state lock poisoned")?;
+              st.cancelled_downloads.remove(&post_id.to_string());
+              st.status.unavailable += 1;
+            }
+            Err(err) => {
+              // keep the sources in unavailable so the user can follow them
+              upsert_unavailable(&conn, "e621", &post_id.to_string(), "download_failed", vec![])?;
+              lowest_failed.set(Some(lowest_failed.get().map_or(post_id, |f| f.min(post_id))));
+              let mut st = state2.lock().map_err(|_| "Sync state lock poisoned")?;
+              st.status.failed_downloads += 1;
+              log::warn!("e621 post {} failed: {}", post_id, err);
+              st.status.last_error = Some(err.to_string());
+            }
+          }
+        }
+        match disk_full {
+          Some(err) => Err(err),
+          None => Ok(()),
+        }
+      };
+
+      // numeric pages are capped at 750 by e621, so after the first page we
+      // follow a `b<lowest id>` cursor instead
+      let mut page: u32 = 1;
+      let mut before_id: Option<i64> = None;
+      let mut reached_mark = false;
+
+      loop {
+        // pause/cancel check
+        if wait_while_paused(&state2)? {
+          break;
+        }
+
+        // stop if hit max
+        {
           let st = state2.lock().map_err(|_| "Sync state lock poisoned")?;
           if let Some(maxn) = st.status.max_new_downloads {
             if st.status.new_attempted >= maxn {
@@ -541,24 +1374,36 @@ pub fn e621_sync_start(
         }
 
         // fetch favorites page
-        let tags = format!("fav:{} order:id_desc", username);
-        let resp = client
-          .get("https://e621.net/posts.json")
-          .basic_auth(&username, Some(&api_key))
-          .header("User-Agent", "TailBurrow/0.2.1 (sync)")
-          .query(&[
-            ("tags", tags.as_str()),
-            ("limit", "320"),
-            ("page", &page.to_string()),
-          ])
-          .send()
-          .map_err(|e| e.to_string())?;
+        let tags = format!("fav:{} order:id_desc", fav_user);
+        let page_param = match before_id {
+          Some(id) => format!("b{id}"),
+          None => page.to_string(),
+        };
+        // throttled responses are retried on the same page
+        let sent = send_with_retry_until(
+          || {
+            client
+              .get(&posts_url)
+              .basic_auth(&username, Some(&api_key))
+              .header("User-Agent", &user_agent)
+              .query(&[
+                ("tags", tags.as_str()),
+                ("limit", "320"),
+                ("page", page_param.as_str()),
+              ])
+          },
+          || state2.lock().is_ok_and(|st| st.cancel_requested),
+        );
+        let resp = match sent {
+          Err(AppError::Cancelled(_)) => break,
+          other => other?,
+        };
 
         if !resp.status().is_success() {
-          return Err(format!("e621 sync API error: HTTP {}", resp.status()));
+          return Err(e621_error("e621 sync API error", resp));
         }
 
-        let json: serde_json::Value = resp.json().map_err(|e| e.to_string())?;
+        let json: serde_json::Value = resp.json()?;
         let posts = json.get("posts").and_then(|p| p.as_array()).cloned().unwrap_or_default();
 
         {
@@ -570,13 +1415,13 @@ pub fn e621_sync_start(
           break;
         }
 
+        let lowest_id = posts.iter().filter_map(|p| p.get("id").and_then(|x| x.as_i64())).min();
+        let mut pending: Vec<(i64, E621PostInput)> = vec![];
+
         for p in posts {
-          // cancel check
-          {
-            let st = state2.lock().map_err(|_| "Sync state lock poisoned")?;
-            if st.cancel_requested {
-              break;
-            }
+          // pause/cancel check
+          if wait_while_paused(&state2)? {
+            break;
           }
 
           {
@@ -588,13 +1433,17 @@ pub fn e621_sync_start(
           if post_id == 0 {
             continue;
           }
+          if stop_at.is_some_and(|mark| post_id <= mark) {
+            reached_mark = true;
+            break;
+          }
 
           // already downloaded check by (source,id)
           let exists: i64 = conn.query_row(
-            "SELECT COUNT(*) FROM items WHERE source='e621' AND source_id=? AND trashed_at IS NULL",
+            &format!("SELECT COUNT(*) FROM items WHERE source='e621' AND source_id=?{trashed_filter}"),
             params![post_id.to_string()],
             |r: &Row| r.get(0),
-          ).map_err(|e| e.to_string())?;
+          )?;
 
           if exists > 0 {
             let mut st = state2.lock().map_err(|_| "Sync state lock poisoned")?;
@@ -606,10 +1455,10 @@ pub fn e621_sync_start(
           let md5 = p.get("file").and_then(|f| f.get("md5")).and_then(|m| m.as_str()).map(|s| s.to_string());
           if let Some(ref m) = md5 {
             let md5_exists: i64 = conn.query_row(
-              "SELECT COUNT(*) FROM items WHERE md5=? AND trashed_at IS NULL",
+              &format!("SELECT COUNT(*) FROM items WHERE md5=?{trashed_filter}"),
               params![m],
               |r: &Row| r.get(0),
-            ).map_err(|e| e.to_string())?;
+            )?;
             if md5_exists > 0 {
               let mut st = state2.lock().map_err(|_| "Sync state lock poisoned")?;
               st.status.skipped_existing += 1;
@@ -617,11 +1466,11 @@ pub fn e621_sync_start(
             }
           }
 
-          // stop after N new downloads (attempted)
+          // stop after N new downloads (attempted, counting the queued batch)
           {
             let st = state2.lock().map_err(|_| "Sync state lock poisoned")?;
             if let Some(maxn) = st.status.max_new_downloads {
-              if st.status.new_attempted >= maxn {
+              if st.status.new_attempted + pending.len() as u32 >= maxn {
                 break;
               }
             }
@@ -629,7 +1478,6 @@ pub fn e621_sync_start(
 
           // file.url might be missing for deleted/blocked
           let file_url = p.get("file").and_then(|f| f.get("url")).and_then(|u| u.as_str()).map(|s| s.to_string());
-          let file_ext = p.get("file").and_then(|f| f.get("ext")).and_then(|u| u.as_str()).unwrap_or("").to_string();
 
           let sources: Vec<String> = p.get("sources")
             .and_then(|s| s.as_array())
@@ -643,58 +1491,78 @@ pub fn e621_sync_start(
             continue;
           }
 
-          // convert to your existing E621PostInput and reuse add_e621_post
-          let tags_obj = p.get("tags").cloned().unwrap_or(serde_json::Value::Null);
-
-          let vec_from = |k: &str| -> Vec<String> {
-            tags_obj.get(k)
-              .and_then(|v| v.as_array())
-              .map(|a| a.iter().filter_map(|x| x.as_str().map(|s| s.to_string())).collect())
-              .unwrap_or_default()
-          };
-
-          let post_input = E621PostInput {
-            id: post_id,
-            file_url: file_url.clone().unwrap(),
-            file_ext,
-            file_md5: md5.clone(),
-            rating: p.get("rating").and_then(|x| x.as_str()).map(|s| s.to_string()),
-            fav_count: p.get("fav_count").and_then(|x| x.as_i64()),
-            score_total: p.get("score").and_then(|s| s.get("total")).and_then(|x| x.as_i64()),
-            created_at: p.get("created_at").and_then(|x| x.as_str()).map(|s| s.to_string()),
-            sources,
-            tags: E621Tags {
-              general: vec_from("general"),
-              species: vec_from("species"),
-              character: vec_from("character"),
-              artist: vec_from("artist"),
-              meta: vec_from("meta"),
-              lore: vec_from("lore"),
-              copyright: vec_from("copyright"),
-            },
-          };
-
-          {
+          let ext = p.get("file").and_then(|f| f.get("ext")).and_then(|e| e.as_str()).unwrap_or("");
+          if skip_unsupported && is_unsupported_ext(ext) {
+            upsert_unavailable(&conn, "e621", &post_id.to_string(), "unsupported_format", sources)?;
             let mut st = state2.lock().map_err(|_| "Sync state lock poisoned")?;
-            st.status.new_attempted += 1;
+            st.status.unavailable += 1;
+            continue;
           }
 
-          match add_e621_post(app2.clone(), post_input) {
-            Ok(_) => {
-              let mut st = state2.lock().map_err(|_| "Sync state lock poisoned")?;
-              st.status.downloaded_ok += 1;
-            }
-            Err(err) => {
-              // keep the sources in unavailable so the user can follow them
-              upsert_unavailable(&conn, "e621", &post_id.to_string(), "download_failed", file_url.is_some().then(|| vec![]).unwrap_or_default())?;
-              let mut st = state2.lock().map_err(|_| "Sync state lock poisoned")?;
-              st.status.failed_downloads += 1;
-              st.status.last_error = Some(err);
+          // optional HEAD so takedowns are recorded cleanly instead of failing mid-download
+          if head_check {
+            let head = client
+              .head(file_url.as_deref().unwrap_or_default())
+              .header("User-Agent", &user_agent)
+              .send();
+            match head {
+              Ok(r) if r.status().is_success() => {
+                if let Some(len) = r.content_length() {
+                  ensure_free_space_for(&conn, &root, len)?;
+                }
+              }
+              Ok(r) => {
+                upsert_unavailable(&conn, "e621", &post_id.to_string(), &format!("head_http_{}", r.status().as_u16()), sources)?;
+                let mut st = state2.lock().map_err(|_| "Sync state lock poisoned")?;
+                st.status.unavailable += 1;
+                continue;
+              }
+              // network hiccup: let the real download decide
+              Err(_) => {}
             }
           }
+
+          let Some(post_input) = e621_post_from_json(&p) else { continue };
+
+          pending.push((post_id, post_input));
+          if pending.len() as u32 >= pacing.e621_concurrency {
+            run_batch(std::mem::take(&mut pending))?;
+            std::thread::sleep(delay);
+          }
+        }
+
+        // a cancel drops whatever was still queued
+        if !wait_while_paused(&state2)? {
+          run_batch(pending)?;
+        }
+        if reached_mark {
+          break;
+        }
+        std::thread::sleep(delay);
+
+        match lowest_id {
+          Some(id) => before_id = Some(id),
+          None => {
+            before_id = None;
+            page += 1;
+          }
         }
+      }
 
-        page += 1;
+      // Only a run that got through everything new may move the mark; a cancelled or
+      // capped run would otherwise hide the posts it never reached. With failures it
+      // stops just below the lowest failed post, so the next run retries from there.
+      let (cancelled, capped) = {
+        let st = state2.lock().map_err(|_| "Sync state lock poisoned")?;
+        let capped = st.status.max_new_downloads.is_some_and(|maxn| st.status.new_attempted >= maxn);
+        (st.cancel_requested, capped)
+      };
+      let mark = match lowest_failed.get() {
+        Some(failed) => highest_ok.get().min(failed - 1),
+        None => highest_ok.get(),
+      };
+      if !cancelled && !capped && mark > high_water.unwrap_or(0) {
+        settings_set(&conn, &high_water_key(&fav_user), &mark.to_string())?;
       }
 
       Ok(())
@@ -704,52 +1572,378 @@ pub fn e621_sync_start(
     let mut st = state2.lock().ok();
     if let Some(ref mut st) = st {
       st.status.running = false;
+      st.status.paused = false;
       if let Err(e) = result {
-        st.status.last_error = Some(e);
+        log::error!("e621 sync {} stopped: {}", sync_batch, e);
+        st.status.last_error = Some(e.to_string());
       }
+
+      let summary = SyncSummary {
+        source: "e621".into(),
+        batch: sync_batch,
+        downloaded: st.status.downloaded_ok,
+        skipped: st.status.skipped_existing,
+        failed: st.status.failed_downloads,
+        unavailable: st.status.unavailable,
+        cancelled: st.status.cancelled,
+        error: st.status.last_error.clone(),
+        elapsed_secs: started.elapsed().as_secs(),
+        ..Default::default()
+      };
+      let _ = publish_sync_summary(&app2, summary);
+    }
+  });
+
+  Ok(Status { ok: true, message: "Sync started".into(), item_id: None })
+}
+
+pub fn publish_sync_summary(app: &AppHandle, mut summary: SyncSummary) -> Result<(), AppError> {
+  summary.finished_at = Utc::now().to_rfc3339();
+  let _ = app.emit("sync-summary", &summary);
+
+  let root = get_root(app)?;
+  let conn = open_conn_for_root(app, &root)?;
+  settings_set(&conn, "last_sync_summary", &serde_json::to_string(&summary)?)
+}
+
+#[tauri::command]
+pub fn get_last_sync_summary(app: AppHandle) -> Result<Option<SyncSummary>, AppError> {
+  let root = get_root(&app)?;
+  let conn = open_conn_for_root(&app, &root)?;
+  match settings_get(&conn, "last_sync_summary")? {
+    Some(json) => Ok(serde_json::from_str(&json).ok()),
+    None => Ok(None),
+  }
+}
+
+// `flags.deleted` wins over `flags.pending`; deleted posts can still carry a file URL
+pub fn e621_remote_status(flags: Option<&serde_json::Value>) -> &'static str {
+  let flag = |k: &str| flags.and_then(|f| f.get(k)).and_then(|x| x.as_bool()).unwrap_or(false);
+  if flag("deleted") {
+    "deleted"
+  } else if flag("pending") {
+    "pending"
+  } else {
+    "active"
+  }
+}
+
+// Map a posts.json entry onto E621PostInput; None when it has no id or no downloadable file
+fn e621_post_from_json(p: &serde_json::Value) -> Option<E621PostInput> {
+  let id = p.get("id").and_then(|x| x.as_i64()).filter(|id| *id > 0)?;
+  let file = p.get("file");
+  let file_url = file.and_then(|f| f.get("url")).and_then(|u| u.as_str())?.to_string();
+
+  let str_list = |v: Option<&serde_json::Value>| -> Vec<String> {
+    v.and_then(|v| v.as_array())
+      .map(|a| a.iter().filter_map(|x| x.as_str().map(|s| s.to_string())).collect())
+      .unwrap_or_default()
+  };
+  let tags = p.get("tags");
+  let tag_list = |k: &str| str_list(tags.and_then(|t| t.get(k)));
+
+  Some(E621PostInput {
+    id,
+    file_url,
+    file_ext: file.and_then(|f| f.get("ext")).and_then(|u| u.as_str()).unwrap_or("").to_string(),
+    file_md5: file.and_then(|f| f.get("md5")).and_then(|m| m.as_str()).map(|s| s.to_string()),
+    rating: p.get("rating").and_then(|x| x.as_str()).map(|s| s.to_string()),
+    fav_count: p.get("fav_count").and_then(|x| x.as_i64()),
+    score_total: p.get("score").and_then(|s| s.get("total")).and_then(|x| x.as_i64()),
+    created_at: p.get("created_at").and_then(|x| x.as_str()).map(|s| s.to_string()),
+    sources: str_list(p.get("sources")),
+    tags: E621Tags {
+      general: tag_list("general"),
+      species: tag_list("species"),
+      character: tag_list("character"),
+      artist: tag_list("artist"),
+      meta: tag_list("meta"),
+      lore: tag_list("lore"),
+      copyright: tag_list("copyright"),
+    },
+    remote_status: Some(e621_remote_status(p.get("flags")).to_string()),
+    raw: Some(p.clone()),
+    sample_url: p.get("sample")
+      .filter(|s| s.get("has").and_then(|h| h.as_bool()).unwrap_or(false))
+      .and_then(|s| s.get("url"))
+      .and_then(|u| u.as_str())
+      .map(|s| s.to_string()),
+    uploader_id: p.get("uploader_id").and_then(|x| x.as_i64()),
+    approver_id: p.get("approver_id").and_then(|x| x.as_i64()),
+  })
+}
+
+// Accepts e621.net/posts/<id> and the older /post/show/<id>, with or without
+// scheme, www., query string or fragment. `base_host` is the host of the
+// configured e621_base_url, so links to a mirror or local mock parse too.
+fn parse_e621_post_id(url: &str, base_host: Option<&str>) -> Option<i64> {
+  let trimmed = url.trim();
+  let parsed = reqwest::Url::parse(trimmed)
+    .or_else(|_| reqwest::Url::parse(&format!("https://{}", trimmed)))
+    .ok()?;
+  let host = parsed.host_str()?.trim_start_matches("www.");
+  let is_base = base_host.is_some_and(|b| b.trim_start_matches("www.").eq_ignore_ascii_case(host));
+  if host != "e621.net" && host != "e926.net" && !is_base {
+    return None;
+  }
+  let segments: Vec<&str> = parsed.path_segments()?.filter(|s| !s.is_empty()).collect();
+  let id = match segments.as_slice() {
+    ["posts", id] | ["post", "show", id] => *id,
+    _ => return None,
+  };
+  id.parse().ok()
+}
+
+#[tauri::command]
+pub fn e621_add_by_url(app: AppHandle, url: String) -> Result<Status, AppError> {
+  let root = get_root(&app)?;
+  let conn = open_conn_for_root(&app, &root)?;
+
+  let base = reqwest::Url::parse(&e621_base_url(&conn)).ok();
+  let post_id = parse_e621_post_id(&url, base.as_ref().and_then(|b| b.host_str()))
+    .ok_or_else(|| AppError::InvalidInput(format!("Not an e621 post URL: {}", url.trim())))?;
+  let (username, api_key) = load_e621_creds(&conn)?;
+
+  let client = reqwest::blocking::Client::new();
+  let resp = client
+    .get(e621_api_url(&conn, "posts.json"))
+    .basic_auth(username, Some(api_key))
+    .header("User-Agent", e621_user_agent(&conn))
+    .query(&[("tags", format!("id:{}", post_id)), ("limit", "1".to_string())])
+    .send()?;
+  if !resp.status().is_success() {
+    return Err(e621_error("e621 lookup failed", resp));
+  }
+
+  let json: serde_json::Value = resp.json()?;
+  let post = json
+    .get("posts")
+    .and_then(|p| p.as_array())
+    .and_then(|a| a.first())
+    .ok_or_else(|| AppError::NotFound(format!("e621 post {} not found", post_id)))?;
+  let input = e621_post_from_json(post)
+    .ok_or_else(|| AppError::NotFound(format!("e621 post {} has no downloadable file (deleted or blocked)", post_id)))?;
+
+  add_e621_post(app, input)
+}
+
+// One id per sync run, stored on every row it inserts (sortable, human-readable)
+pub fn new_sync_batch(source: &str) -> String {
+  format!("{}-{}", source, Utc::now().format("%Y%m%dT%H%M%SZ"))
+}
+
+// Deletes download leftovers in .cache/tmp; with `older_than`, only files not
+// modified within that window. Returns how many were removed.
+pub fn sweep_part_files(root: &std::path::Path, older_than: Option<std::time::Duration>) -> u32 {
+  let Ok(entries) = fs::read_dir(root.join(".cache").join("tmp")) else { return 0 };
+  let mut removed = 0;
+  for entry in entries.flatten() {
+    let path = entry.path();
+    if path.extension().and_then(|e| e.to_str()) != Some("part") {
+      continue;
+    }
+    let old_enough = match older_than {
+      None => true,
+      Some(age) => entry
+        .metadata()
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.elapsed().ok())
+        .is_some_and(|elapsed| elapsed >= age),
+    };
+    if old_enough && fs::remove_file(&path).is_ok() {
+      removed += 1;
+    }
+  }
+  removed
+}
+
+// A .part untouched this long isn't being written to anymore
+pub const STALE_PART_AGE: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+#[tauri::command]
+pub fn cleanup_temp(app: AppHandle) -> Result<u32, AppError> {
+  let root = get_root(&app)?;
+  library::acquire_lock(&root)?;
+  Ok(sweep_part_files(&root, Some(STALE_PART_AGE)))
+}
+
+// How long quitting waits for running syncs to wind down
+const SHUTDOWN_GRACE: std::time::Duration = std::time::Duration::from_secs(10);
+
+// 0 = running, 1 = winding down, 2 = wound down and free to exit
+static SHUTDOWN_STAGE: std::sync::atomic::AtomicU8 = std::sync::atomic::AtomicU8::new(0);
+
+// Handles ExitRequested. The first request is held back while `shutdown` runs on its
+// own thread, so the event loop (and the window) stays responsive for the grace
+// period; that thread then exits for real. Requests in the meantime are held too.
+pub fn on_exit_requested(app: &AppHandle, api: &tauri::ExitRequestApi, code: Option<i32>) {
+  use std::sync::atomic::Ordering;
+  match SHUTDOWN_STAGE.compare_exchange(0, 1, Ordering::SeqCst, Ordering::SeqCst) {
+    Ok(_) => {
+      api.prevent_exit();
+      let app = app.clone();
+      std::thread::spawn(move || {
+        shutdown(&app);
+        SHUTDOWN_STAGE.store(2, Ordering::SeqCst);
+        app.exit(code.unwrap_or(0));
+      });
+    }
+    Err(1) => api.prevent_exit(),
+    Err(_) => {}
+  }
+}
+
+// Stop the workers (the e621 worker finishes the file it's on), then checkpoint the
+// WAL and drop temp files. Skipped when another instance owns the library.
+fn shutdown(app: &AppHandle) {
+  let Ok(root) = get_root(app) else { return };
+  if !library::holds_lock(&root) {
+    return;
+  }
+
+  let sync_state = app.state::<Arc<Mutex<SyncState>>>().inner().clone();
+  if let Ok(mut st) = sync_state.lock() {
+    st.cancel_requested = true;
+    st.resume.notify_all();
+  }
+  let fa_state = app.state::<FAState>();
+  fa_state.cancel.cancel();
+  app.state::<MaintenanceState>().cancel.cancel();
+
+  let deadline = std::time::Instant::now() + SHUTDOWN_GRACE;
+  while std::time::Instant::now() < deadline {
+    let e621_running = sync_state.lock().map(|st| st.status.running).unwrap_or(false);
+    let fa_running = fa_state.status.lock().map(|st| st.running).unwrap_or(false);
+    if !e621_running && !fa_running {
+      break;
+    }
+    std::thread::sleep(std::time::Duration::from_millis(100));
+  }
+
+  if let Ok(conn) = pooled_conn(app, &root) {
+    let _ = db::checkpoint(&conn);
+  }
+  sweep_part_files(&root, None);
+}
+
+pub fn spawn_lock_heartbeat(app: AppHandle) {
+  std::thread::spawn(move || loop {
+    std::thread::sleep(library::LOCK_HEARTBEAT);
+    if let Ok(root) = get_root(&app) {
+      library::refresh_lock(&root);
     }
   });
+}
+
+// Cap for unattended runs so a long absence doesn't turn into one huge sync
+const AUTO_SYNC_MAX_NEW_DOWNLOADS: u32 = 100;
+
+pub fn spawn_auto_sync_scheduler(app: AppHandle, state: Arc<Mutex<SyncState>>) {
+  std::thread::spawn(move || loop {
+    std::thread::sleep(std::time::Duration::from_secs(60));
+    let _ = auto_sync_tick(&app, &state);
+  });
+}
+
+fn auto_sync_tick(app: &AppHandle, state: &Arc<Mutex<SyncState>>) -> Result<(), AppError> {
+  // no library loaded yet
+  let root = match get_root(app) {
+    Ok(r) => r,
+    Err(_) => return Ok(()),
+  };
+  if !root.is_dir() {
+    return Ok(());
+  }
+
+  let conn = open_conn_for_root(app, &root)?;
+  let hours = match settings_get(&conn, "auto_sync_interval_hours")?.and_then(|v| v.parse::<u32>().ok()) {
+    Some(h) if h > 0 => h,
+    _ => return Ok(()),
+  };
+  if load_e621_creds(&conn).is_err() {
+    return Ok(());
+  }
+
+  if let Some(last) = settings_get(&conn, "e621_last_sync_at")? {
+    if let Ok(last) = chrono::DateTime::parse_from_rfc3339(&last) {
+      if Utc::now().signed_duration_since(last) < chrono::Duration::hours(hours as i64) {
+        return Ok(());
+      }
+    }
+  }
+
+  {
+    let st = state.lock().map_err(|_| "Sync state lock poisoned")?;
+    if st.status.running {
+      return Ok(());
+    }
+  }
+
+  start_e621_sync(app.clone(), state.clone(), Some(AUTO_SYNC_MAX_NEW_DOWNLOADS), false, None)?;
+  Ok(())
+}
 
-  Ok(Status { ok: true, message: "Sync started".into() })
+#[tauri::command]
+pub fn get_auto_sync_interval(app: AppHandle) -> Result<Option<u32>, AppError> {
+  let root = get_root(&app)?;
+  let conn = open_conn_for_root(&app, &root)?;
+  Ok(settings_get(&conn, "auto_sync_interval_hours")?.and_then(|v| v.parse().ok()))
+}
+
+#[tauri::command]
+pub fn set_auto_sync_interval(app: AppHandle, hours: Option<u32>) -> Result<Status, AppError> {
+  let root = get_root(&app)?;
+  let conn = open_conn_for_root(&app, &root)?;
+
+  match hours {
+    Some(h) if h > 0 => {
+      settings_set(&conn, "auto_sync_interval_hours", &h.to_string())?;
+      Ok(Status { ok: true, message: format!("Auto-sync every {h}h"), item_id: None })
+    }
+    _ => {
+      settings_delete(&conn, "auto_sync_interval_hours")?;
+      Ok(Status { ok: true, message: "Auto-sync disabled".into(), item_id: None })
+    }
+  }
 }
 
 #[tauri::command]
-pub fn e621_favorite(app: AppHandle, post_id: i64) -> Result<Status, String> {
+pub fn e621_favorite(app: AppHandle, post_id: i64) -> Result<Status, AppError> {
   let root = get_root(&app)?;
-  let conn = open_conn_for_root(&root)?;
+  let conn = open_conn_for_root(&app, &root)?;
   let (username, api_key) = load_e621_creds(&conn)?;
 
   let client = reqwest::blocking::Client::new();
   let resp = client
-    .post("https://e621.net/favorites.json")
+    .post(e621_api_url(&conn, "favorites.json"))
     .basic_auth(username, Some(api_key))
-    .header("User-Agent", "TailBurrow/0.2.1 (favorite)")
+    .header("User-Agent", e621_user_agent(&conn))
     .header("Content-Type", "application/x-www-form-urlencoded")
     .body(format!("post_id={}", post_id))
-    .send()
-    .map_err(|e| e.to_string())?;
+    .send()?;
 
   // 422 = already favorited, acceptable for "ensure"
   if !resp.status().is_success() && resp.status().as_u16() != 422 {
-    return Err(format!("Favorite failed: HTTP {}", resp.status()));
+    return Err(e621_error("Favorite failed", resp));
   }
 
-  Ok(Status { ok: true, message: "Favorited on e621".into() })
+  Ok(Status { ok: true, message: "Favorited on e621".into(), item_id: None })
 }
 
 #[tauri::command]
-pub fn fa_set_credentials(app: tauri::AppHandle, a: String, b: String) -> Result<(), String> {
-    let path = app.path().app_config_dir().map_err(|e| e.to_string())?.join("fa_creds.json");
+pub fn fa_set_credentials(app: tauri::AppHandle, a: String, b: String) -> Result<(), AppError> {
+    let path = app.path().app_config_dir()?.join("fa_creds.json");
     
     // Ensure the config directory exists!
     if let Some(parent) = path.parent() {
         if !parent.exists() {
-            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+            std::fs::create_dir_all(parent)?;
         }
     }
 
     let json = serde_json::json!({ "a": a, "b": b });
-    std::fs::write(&path, json.to_string()).map_err(|e| e.to_string())?;
+    std::fs::write(&path, json.to_string())?;
     
     Ok(())
 }
@@ -760,24 +1954,27 @@ pub struct FaCredInfo {
 }
 
 #[tauri::command]
-pub fn fa_get_cred_info(app: tauri::AppHandle) -> Result<FaCredInfo, String> {
-    let path = app.path().app_config_dir().map_err(|e| e.to_string())?.join("fa_creds.json");
+pub fn fa_get_cred_info(app: tauri::AppHandle) -> Result<FaCredInfo, AppError> {
+    let path = app.path().app_config_dir()?.join("fa_creds.json");
     Ok(FaCredInfo { has_creds: path.exists() })
 }
 
 #[tauri::command]
-pub fn fa_start_sync(app: tauri::AppHandle, limit: Option<u32>) -> Result<(), String> {
+pub fn fa_start_sync(app: tauri::AppHandle, limit: Option<u32>) -> Result<(), AppError> {
     // Load creds
-    let path = app.path().app_config_dir().map_err(|e| e.to_string())?.join("fa_creds.json");
-    if !path.exists() { return Err("No credentials set".into()); }
+    let path = app.path().app_config_dir()?.join("fa_creds.json");
+    if !path.exists() { return Err(AppError::NotConfigured("No credentials set".into())); }
     
-    let content = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
-    let json: serde_json::Value = serde_json::from_str(&content).map_err(|e| e.to_string())?;
+    let content = std::fs::read_to_string(path)?;
+    let json: serde_json::Value = serde_json::from_str(&content)?;
     
     let a = json["a"].as_str().unwrap_or("").to_string();
     let b = json["b"].as_str().unwrap_or("").to_string();
 
     let stop_after = limit.unwrap_or(0); // 0 = unlimited
+    // takes the lock and migrates up front; the worker uses plain pooled connections
+    let root = get_root(&app)?;
+    drop(open_conn_for_root(&app, &root)?);
 
     tauri::async_runtime::spawn(async move {
         crate::fa::run_sync(app, a, b, stop_after).await;
@@ -787,9 +1984,9 @@ pub fn fa_start_sync(app: tauri::AppHandle, limit: Option<u32>) -> Result<(), St
 }
 
 #[tauri::command]
-pub fn get_trash_count(app: tauri::AppHandle) -> Result<u32, String> {
+pub fn get_trash_count(app: tauri::AppHandle) -> Result<u32, AppError> {
     let root = get_root(&app)?;
-    let conn = db::open(&library::db_path(&root))?;
+    let conn = pooled_conn(&app, &root)?;
     let count: u32 = conn.query_row(
         "SELECT COUNT(*) FROM items WHERE trashed_at IS NOT NULL",
         [],
@@ -799,21 +1996,22 @@ pub fn get_trash_count(app: tauri::AppHandle) -> Result<u32, String> {
 }
 
 #[tauri::command]
-pub fn get_trashed_items(app: tauri::AppHandle) -> Result<Vec<ItemDto>, String> {
+pub fn get_trashed_items(app: tauri::AppHandle) -> Result<Vec<ItemDto>, AppError> {
     let root = get_root(&app)?;
-    let conn = db::open(&library::db_path(&root))?;
+    let conn = pooled_conn(&app, &root)?;
 
     let mut stmt = conn.prepare(
         r#"
         SELECT
           i.item_id, i.source, i.source_id, i.remote_url, i.file_rel, i.ext,
           i.rating, i.fav_count, i.score_total, i.created_at, i.added_at,
-          '', '', '' -- We don't need tags/sources for the trash view usually
+          '', '', '', -- We don't need tags/sources for the trash view usually
+          i.primary_artist, i.mime, i.notes
         FROM items i
         WHERE i.trashed_at IS NOT NULL
         ORDER BY i.trashed_at DESC
         "#
-    ).map_err(|e| e.to_string())?;
+    )?;
 
     let rows = stmt.query_map([], |r| {
         let file_rel: String = r.get(4)?;
@@ -824,7 +2022,7 @@ pub fn get_trashed_items(app: tauri::AppHandle) -> Result<Vec<ItemDto>, String>
             source: r.get(1)?,
             source_id: r.get(2)?,
             remote_url: r.get(3)?,
-            file_rel: file_rel,
+            file_rel,
             file_abs: file_abs.to_string_lossy().to_string(),
             ext: r.get(5)?,
             rating: r.get(6)?,
@@ -834,65 +2032,173 @@ pub fn get_trashed_items(app: tauri::AppHandle) -> Result<Vec<ItemDto>, String>
             added_at: r.get(10)?,
             tags: vec![],
             artists: vec![],
+            primary_artist: r.get(14)?,
+            mime: r.get(15)?,
             sources: vec![],
+            notes: r.get(16)?,
+            uploader_id: None,
+            uploader_name: None,
+            approver_id: None,
+            approver_name: None,
         })
-    }).map_err(|e| e.to_string())?;
+    })?;
 
     let mut out = vec![];
     for row in rows {
-        out.push(row.map_err(|e| e.to_string())?);
+        out.push(row?);
     }
     Ok(out)
 }
 
 #[tauri::command]
-pub fn restore_item(app: tauri::AppHandle, item_id: i64) -> Result<(), String> {
+pub fn restore_item(app: tauri::AppHandle, item_id: i64) -> Result<(), AppError> {
     let root = get_root(&app)?;
-    let conn = db::open(&library::db_path(&root))?;
+    let conn = open_conn_for_root(&app, &root)?;
     
-    conn.execute(
-        "UPDATE items SET trashed_at = NULL WHERE item_id = ?",
-        [item_id]
-    ).map_err(|e| e.to_string())?;
+    restore_trashed(&root, &conn, &[item_id])?;
     
     Ok(())
 }
 
+// Library-relative path next to `rel` that doesn't exist yet (x.png, x_1.png, ...)
+fn free_rel(root: &std::path::Path, rel: &str) -> String {
+    if !root.join(rel).exists() {
+        return rel.to_string();
+    }
+    let (stem, ext) = match rel.rsplit_once('.') {
+        Some((stem, ext)) if !stem.ends_with('/') => (stem, format!(".{}", ext)),
+        _ => (rel, String::new()),
+    };
+    let mut n = 1;
+    loop {
+        let candidate = format!("{}_{}{}", stem, n, ext);
+        if !root.join(&candidate).exists() {
+            return candidate;
+        }
+        n += 1;
+    }
+}
+
+// Move an item's file (and its cached thumbnail) to `to_rel` and point the row at it.
+// A missing file only updates the row. Returns the path actually used.
+fn relocate_item_file(root: &std::path::Path, conn: &Connection, item_id: i64, from_rel: &str, to_rel: &str) -> Result<String, AppError> {
+    if from_rel == to_rel {
+        return Ok(to_rel.to_string());
+    }
+    let from = library::resolve_in_root(root, from_rel)?;
+    let moved = from.exists();
+    let to_rel = if moved { free_rel(root, to_rel) } else { to_rel.to_string() };
+
+    if moved {
+        let to = library::resolve_in_root(root, &to_rel)?;
+        if let Some(parent) = to.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(&from, &to)?;
+    }
+
+    if let Err(e) = conn.execute("UPDATE items SET file_rel = ? WHERE item_id = ?", params![to_rel, item_id]) {
+        if moved {
+            let _ = fs::rename(root.join(&to_rel), &from);
+        }
+        return Err(e.into());
+    }
+
+    // A recorded thumb_rel doesn't depend on file_rel; an unrecorded path-keyed
+    // thumbnail has to follow the file
+    let has_thumb_rel: bool = conn.query_row(
+        "SELECT thumb_rel IS NOT NULL FROM items WHERE item_id = ?",
+        [item_id],
+        |r| r.get(0)
+    )?;
+    if !has_thumb_rel {
+        let _ = fs::rename(thumb_path_for(root, from_rel), thumb_path_for(root, &to_rel));
+    }
+    Ok(to_rel)
+}
+
+// Moves each (item_id, file_rel) file to `to_rel(file_rel)` and runs `update` on its
+// row, all in one transaction. Files move as we go; if anything fails they're moved
+// back before the rows roll back, so a batch never ends up half applied.
+fn relocate_batch(
+    root: &std::path::Path,
+    conn: &Connection,
+    items: &[(i64, String)],
+    to_rel: impl Fn(&str) -> String,
+    update: impl Fn(&Connection, i64) -> Result<(), AppError>,
+) -> Result<(), AppError> {
+    let tx = conn.unchecked_transaction()?;
+    let mut moved: Vec<(i64, String, String)> = vec![];
+    let result = (|| -> Result<(), AppError> {
+        for (id, file_rel) in items {
+            let to = relocate_item_file(root, &tx, *id, file_rel, &to_rel(file_rel))?;
+            moved.push((*id, file_rel.clone(), to));
+            update(&tx, *id)?;
+        }
+        Ok(())
+    })();
+
+    match result.and_then(|_| Ok(tx.commit()?)) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            // rows are (or will be) rolled back: only the files need undoing
+            for (id, from, to) in moved.iter().rev() {
+                let _ = relocate_item_file(root, conn, *id, to, from);
+            }
+            Err(e)
+        }
+    }
+}
+
+// Takes the trashed ones among `ids` back out of .trash/. Returns the ids restored.
+fn restore_trashed(root: &std::path::Path, conn: &Connection, ids: &[i64]) -> Result<Vec<i64>, AppError> {
+    let mut items = vec![];
+    for id in ids {
+        let file_rel: Option<String> = conn.query_row(
+            "SELECT file_rel FROM items WHERE item_id = ? AND trashed_at IS NOT NULL",
+            [id],
+            |r| r.get(0)
+        ).optional()?;
+        items.extend(file_rel.map(|f| (*id, f)));
+    }
+
+    relocate_batch(root, conn, &items, library::untrash_rel, |tx, id| {
+        // restoring is an explicit "keep this"; verify_library quarantines it again if it's still bad
+        tx.execute("UPDATE items SET trashed_at = NULL, quarantined = 0 WHERE item_id = ?", [id])?;
+        Ok(())
+    })?;
+    Ok(items.into_iter().map(|(id, _)| id).collect())
+}
+
 #[tauri::command]
-pub fn empty_trash(app: tauri::AppHandle) -> Result<(), String> {
+pub fn empty_trash(app: tauri::AppHandle) -> Result<(), AppError> {
     let root = get_root(&app)?;
-    let conn = db::open(&library::db_path(&root))?;
+    let conn = open_conn_for_root(&app, &root)?;
 
-    let mut stmt = conn.prepare("SELECT file_rel FROM items WHERE trashed_at IS NOT NULL")
-        .map_err(|e| e.to_string())?;
+    let mut stmt = conn.prepare("SELECT file_rel, thumb_rel, fa_original_rel FROM items WHERE trashed_at IS NOT NULL")?;
     
-    let files_to_delete: Vec<String> = stmt.query_map([], |row| row.get(0))
-        .map_err(|e| e.to_string())?
+    let files_to_delete: Vec<(String, Option<String>, Option<String>)> = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
         .filter_map(Result::ok)
         .collect();
 
-    let cache_dir = root.join(".cache").join("thumbs");
-
     // 2. Delete from Disk
-    for rel_path in files_to_delete {
+    let mut thumbs = vec![];
+    for (rel_path, thumb_rel, fa_original_rel) in files_to_delete {
         // Delete Main File
         let abs_path = root.join(&rel_path);
         if abs_path.exists() {
             let _ = std::fs::remove_file(abs_path);
         }
-
-        // Delete Thumbnail
-        // Hash the relative path just like ensure_thumbnail does
-        let name_hash = format!("{:x}", md5::compute(rel_path.as_bytes()));
-        let thumb_path = cache_dir.join(format!("{}.jpg", name_hash));
-        
-        if thumb_path.exists() {
-            let _ = std::fs::remove_file(thumb_path);
+        if let Some(original) = fa_original_rel {
+            let _ = std::fs::remove_file(root.join(original));
         }
+        thumbs.push(thumb_rel.unwrap_or_else(|| legacy_thumb_rel(&rel_path)));
     }
 
-    conn.execute("DELETE FROM items WHERE trashed_at IS NOT NULL", [])
-        .map_err(|e| e.to_string())?;
+    conn.execute("DELETE FROM items WHERE trashed_at IS NOT NULL", [])?;
+
+    // Delete Thumbnails (content-keyed ones may still be used by a live duplicate)
+    drop_unreferenced_thumbs(&root, &conn, thumbs)?;
 
     Ok(())
 }
@@ -904,36 +2210,34 @@ pub fn fa_sync_status(state: tauri::State<FAState>) -> FASyncStatus {
 
 #[tauri::command]
 pub fn fa_cancel_sync(state: tauri::State<FAState>) {
-    *state.should_cancel.lock().unwrap() = true;
+    state.cancel.cancel();
 }
 
+// Forgets the active library (its files stay where they are); other known
+// libraries remain available to switch_library
 #[tauri::command]
-pub fn clear_library_root(app: tauri::AppHandle) -> Result<(), String> {
-    let path = app
-        .path()
-        .app_config_dir()
-        .map_err(|e| e.to_string())?
-        .join("config.json");
-
-    // Remove the config file entirely, or write an empty config
-    if path.exists() {
-        std::fs::remove_file(&path).map_err(|e| e.to_string())?;
+pub fn clear_library_root(app: tauri::AppHandle) -> Result<(), AppError> {
+    let mut cfg = config::load_config(&app)?;
+    if let Some(i) = cfg.active.take() {
+        if i < cfg.libraries.len() {
+            let root = cfg.libraries.remove(i);
+            library::release_lock(std::path::Path::new(&root));
+            app.state::<Arc<db::Pool>>().close(&library::db_path(std::path::Path::new(&root)));
+        }
     }
-
-    Ok(())
+    config::save_config(&app, &cfg)
 }
 
 #[tauri::command]
-pub fn update_item_tags(app: tauri::AppHandle, item_id: i64, tags: Vec<String>) -> Result<(), String> {
+pub fn update_item_tags(app: tauri::AppHandle, item_id: i64, tags: Vec<String>) -> Result<(), AppError> {
     let root = get_root(&app)?;
-    let mut conn = db::open(&library::db_path(&root))?;
+    let mut conn = open_conn_for_root(&app, &root)?;
     
     // Use a transaction to ensure all or nothing
-    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let tx = conn.transaction()?;
 
     // 1. Remove ALL existing tags for this item
-    tx.execute("DELETE FROM item_tags WHERE item_id = ?", [item_id])
-        .map_err(|e| e.to_string())?;
+    tx.execute("DELETE FROM item_tags WHERE item_id = ?", [item_id])?;
 
     // 2. Add the new list
     for tag in tags {
@@ -944,67 +2248,126 @@ pub fn update_item_tags(app: tauri::AppHandle, item_id: i64, tags: Vec<String>)
         tx.execute(
             "INSERT OR IGNORE INTO tags (name, type) VALUES (?, 'general')",
             [&clean_tag]
-        ).map_err(|e| e.to_string())?;
+        )?;
 
         // Get the tag's ID
         let tag_id: i64 = tx.query_row(
             "SELECT tag_id FROM tags WHERE name = ?",
             [&clean_tag],
             |row| row.get(0)
-        ).map_err(|e| e.to_string())?;
+        )?;
 
         // Link item to tag
         tx.execute(
             "INSERT INTO item_tags (item_id, tag_id) VALUES (?, ?)",
             [item_id, tag_id]
-        ).map_err(|e| e.to_string())?;
+        )?;
+    }
+    db::reindex_item(&tx, item_id)?;
+
+    tx.commit()?;
+    Ok(())
+}
+
+// e621's tag categories; anything else is rejected rather than stored
+const TAG_TYPES: [&str; 7] = ["general", "species", "character", "artist", "meta", "lore", "copyright"];
+
+fn normalize_tag_type(tag_type: &str) -> Result<&'static str, AppError> {
+    let t = tag_type.trim().to_lowercase();
+    TAG_TYPES
+        .iter()
+        .find(|known| **known == t)
+        .copied()
+        .ok_or_else(|| AppError::InvalidInput(format!("Unknown tag type: {}", tag_type)))
+}
+
+#[tauri::command]
+pub fn set_tag_type(app: tauri::AppHandle, tag: String, tag_type: String) -> Result<Status, AppError> {
+    let root = get_root(&app)?;
+    let conn = open_conn_for_root(&app, &root)?;
+
+    let tag_type = normalize_tag_type(&tag_type)?;
+    let name = tag.trim().to_lowercase();
+    let n = conn.execute("UPDATE tags SET type = ? WHERE name = ?", params![tag_type, name])?;
+    if n == 0 {
+        return Err(AppError::NotFound(format!("Tag not found: {}", name)));
     }
 
-    tx.commit().map_err(|e| e.to_string())?;
+    Ok(Status { ok: true, message: format!("{} is now {}", name, tag_type), item_id: None })
+}
+
+// Like update_item_tags, but each tag carries its category (name, type)
+#[tauri::command]
+pub fn update_item_tags_typed(app: tauri::AppHandle, item_id: i64, tags: Vec<(String, String)>) -> Result<(), AppError> {
+    let root = get_root(&app)?;
+    let mut conn = open_conn_for_root(&app, &root)?;
+
+    // validate everything before touching the item
+    let mut typed = vec![];
+    for (name, tag_type) in tags {
+        let clean = name.trim().to_lowercase();
+        if clean.is_empty() { continue; }
+        typed.push((clean, normalize_tag_type(&tag_type)?));
+    }
+
+    let tx = conn.transaction()?;
+    tx.execute("DELETE FROM item_tags WHERE item_id = ?", [item_id])?;
+    for (name, tag_type) in typed {
+        let tag_id = upsert_tag(&tx, &name, tag_type)?;
+        tx.execute(
+            "INSERT OR IGNORE INTO item_tags (item_id, tag_id) VALUES (?, ?)",
+            [item_id, tag_id]
+        )?;
+    }
+    db::reindex_item(&tx, item_id)?;
+    tx.commit()?;
     Ok(())
 }
 
 #[tauri::command]
-pub fn e621_clear_credentials(app: tauri::AppHandle) -> Result<(), String> {
+pub fn e621_clear_credentials(app: tauri::AppHandle) -> Result<(), AppError> {
     let path = app
         .path()
-        .app_config_dir()
-        .map_err(|e| e.to_string())?
+        .app_config_dir()?
         .join("e621_credentials.json");
 
     if path.exists() {
-        std::fs::remove_file(&path).map_err(|e| e.to_string())?;
+        std::fs::remove_file(&path)?;
     }
 
     Ok(())
 }
 
 #[tauri::command]
-pub fn get_library_stats(app: tauri::AppHandle) -> Result<u32, String> {
+pub fn get_library_stats(app: tauri::AppHandle) -> Result<u32, AppError> {
   let root = get_root(&app)?;
-  let conn = db::open(&library::db_path(&root))?;
+  let conn = pooled_conn(&app, &root)?;
 
   let count: u32 = conn.query_row(
     "SELECT COUNT(*) FROM items WHERE trashed_at IS NULL",
     [],
     |row| row.get(0),
-  ).map_err(|e| e.to_string())?;
+  )?;
 
   Ok(count)
 }
 
+// rating must be 's', 'q', or 'e'
+fn normalize_rating(rating: &str) -> Result<&'static str, AppError> {
+    match rating.to_lowercase().as_str() {
+        "s" | "safe" => Ok("s"),
+        "q" | "questionable" => Ok("q"),
+        "e" | "explicit" => Ok("e"),
+        _ => Err(AppError::InvalidInput("Invalid rating".into())),
+    }
+}
+
 #[tauri::command]
-pub fn update_item_rating(app: tauri::AppHandle, item_id: i64, rating: String) -> Result<(), String> {
+pub fn update_item_rating(app: tauri::AppHandle, item_id: i64, rating: String) -> Result<(), AppError> {
     let root = get_root(&app)?;
-    let conn = db::open(&library::db_path(&root))?;
+    let conn = open_conn_for_root(&app, &root)?;
     
-    // rating must be 's', 'q', or 'e'
-    let r = match rating.to_lowercase().as_str() {
-        "s" | "safe" => "s",
-        "q" | "questionable" => "q",
-        "e" | "explicit" => "e",
-        _ => return Err("Invalid rating".into()),
-    };
+    let r = normalize_rating(&rating)?;
 
         // Convert ID to string first so it lives long enough
     let id_str = item_id.to_string();
@@ -1012,45 +2375,64 @@ pub fn update_item_rating(app: tauri::AppHandle, item_id: i64, rating: String) -
     conn.execute(
         "UPDATE items SET rating = ? WHERE item_id = ?",
         rusqlite::params![r, id_str],
-    ).map_err(|e| e.to_string())?;
+    )?;
     
     Ok(())
 }
 
 #[tauri::command]
-pub fn update_item_sources(app: tauri::AppHandle, item_id: i64, sources: Vec<String>) -> Result<(), String> {
+pub fn update_items_rating(app: tauri::AppHandle, ids: Vec<i64>, rating: String) -> Result<u32, AppError> {
+    // validate once up front so a bad rating never partially applies
+    let r = normalize_rating(&rating)?;
+
+    let root = get_root(&app)?;
+    let mut conn = open_conn_for_root(&app, &root)?;
+    let tx = conn.transaction()?;
+
+    let mut changed = 0;
+    for id in ids {
+        changed += tx.execute(
+            "UPDATE items SET rating = ? WHERE item_id = ? AND rating IS NOT ?",
+            rusqlite::params![r, id, r],
+        )? as u32;
+    }
+
+    tx.commit()?;
+    Ok(changed)
+}
+
+#[tauri::command]
+pub fn update_item_sources(app: tauri::AppHandle, item_id: i64, sources: Vec<String>) -> Result<(), AppError> {
     let root = get_root(&app)?;
-    let mut conn = db::open(&library::db_path(&root))?;
-    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let mut conn = open_conn_for_root(&app, &root)?;
+    let tx = conn.transaction()?;
 
     // 1. Unlink all existing sources for this item
-    tx.execute("DELETE FROM item_sources WHERE item_id = ?", [item_id])
-        .map_err(|e| e.to_string())?;
+    tx.execute("DELETE FROM item_sources WHERE item_id = ?", [item_id])?;
 
     // 2. Add new sources
     for url in sources {
-        let clean_url = url.trim();
+        let clean_url = normalize_url(&url);
         if clean_url.is_empty() { continue; }
 
         // Insert Source URL if new
-        tx.execute("INSERT OR IGNORE INTO sources (url) VALUES (?)", [clean_url])
-            .map_err(|e| e.to_string())?;
+        tx.execute("INSERT OR IGNORE INTO sources (url) VALUES (?)", [&clean_url])?;
         
         // Get Source ID
         let source_row_id: i64 = tx.query_row(
             "SELECT source_row_id FROM sources WHERE url = ?", 
-            [clean_url], 
+            [&clean_url], 
             |r| r.get(0)
-        ).map_err(|e| e.to_string())?;
+        )?;
 
-        // Link
+        // Two input spellings can normalize to the same row
         tx.execute(
-            "INSERT INTO item_sources (item_id, source_row_id) VALUES (?, ?)", 
+            "INSERT OR IGNORE INTO item_sources (item_id, source_row_id) VALUES (?, ?)", 
             [item_id, source_row_id]
-        ).map_err(|e| e.to_string())?;
+        )?;
     }
 
-    tx.commit().map_err(|e| e.to_string())?;
+    tx.commit()?;
     Ok(())
 }
 
@@ -1062,325 +2444,836 @@ pub fn list_items(
     search: Option<String>, // Tag search only
     rating: Option<String>, // 's', 'q', 'e', or 'nsfw'
     source: Option<String>, // 'e621', 'furaffinity', or 'all'
-    order: Option<String>,  // 'newest', 'oldest', 'score', 'random'
-) -> Result<Vec<ItemDto>, String> {
+    order: Option<String>,  // 'newest', 'oldest', 'score', 'random', 'artist', 'created', 'created_asc'
+) -> Result<Vec<ItemDto>, AppError> {
     let limit = limit.unwrap_or(100);
     let offset = offset.unwrap_or(0);
-    let search_query = search.unwrap_or_default();
-    let rating_filter = rating.unwrap_or("all".to_string());
-    let source_filter = source.unwrap_or("all".to_string());
-    let sort_order = order.unwrap_or("newest".to_string());
+    let root = get_root(&app)?;
+    let conn = pooled_conn(&app, &root)?;
+
+    let query = ItemQuery::build(&conn, search, rating, source, order)?;
+
+    query_items(&conn, &root, &query, limit, offset)
+}
 
+// Just enough to draw a grid cell; the rest comes from get_item when it's opened
+#[derive(Serialize)]
+pub struct ItemSummary {
+    pub item_id: i64,
+    pub file_abs: String,
+    pub ext: Option<String>,
+    pub rating: Option<String>,
+    pub source: String,
+}
+
+// list_items without the tag/artist/source subqueries, for large grids
+#[tauri::command]
+pub fn list_items_compact(
+    app: tauri::AppHandle,
+    limit: Option<u32>,
+    offset: Option<u32>,
+    search: Option<String>,
+    rating: Option<String>,
+    source: Option<String>,
+    order: Option<String>,
+) -> Result<Vec<ItemSummary>, AppError> {
+    let limit = limit.unwrap_or(100);
+    let offset = offset.unwrap_or(0);
     let root = get_root(&app)?;
-    let conn = db::open(&library::db_path(&root))?;
+    let conn = pooled_conn(&app, &root)?;
 
-    // Base SQL
-    let mut sql = String::from(
-        r#"
-        SELECT
-          i.item_id, i.source, i.source_id, i.remote_url, i.file_rel, i.ext,
-          i.rating, i.fav_count, i.score_total, i.created_at, i.added_at,
-          (SELECT GROUP_CONCAT(t.name, char(9)) FROM item_tags it JOIN tags t ON it.tag_id = t.tag_id WHERE it.item_id = i.item_id),
-          (SELECT GROUP_CONCAT(t.name, char(9)) FROM item_tags it JOIN tags t ON it.tag_id = t.tag_id WHERE it.item_id = i.item_id AND t.type = 'artist'),
-          (SELECT GROUP_CONCAT(s.url, char(9)) FROM item_sources isrc JOIN sources s ON isrc.source_row_id = s.source_row_id WHERE isrc.item_id = i.item_id)
-        FROM items i
-        WHERE i.trashed_at IS NULL
-        "#
+    let query = ItemQuery::build(&conn, search, rating, source, order)?;
+    let sql = format!(
+        "SELECT i.item_id, i.file_rel, i.ext, i.rating, i.source FROM items i WHERE i.trashed_at IS NULL{} ORDER BY {} LIMIT {} OFFSET {}",
+        query.where_sql, query.order_by, limit, offset
     );
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(&*query.sql_params(), |r| {
+        let file_rel: String = r.get(1)?;
+        Ok(ItemSummary {
+            item_id: r.get(0)?,
+            file_abs: root.join(&file_rel).to_string_lossy().to_string(),
+            ext: r.get(2)?,
+            rating: r.get(3)?,
+            source: r.get(4)?,
+        })
+    })?;
+    Ok(rows.collect::<Result<_, _>>()?)
+}
 
-    let mut params_store: Vec<String> = vec![]; 
-    let mut where_clauses: Vec<String> = vec![];
+// One page of live items matching `query`, in its order
+fn query_items(conn: &Connection, root: &std::path::Path, query: &ItemQuery, limit: u32, offset: u32) -> Result<Vec<ItemDto>, AppError> {
+    // Base SQL
+    let mut sql = format!("SELECT {ITEM_DTO_COLUMNS} FROM items i WHERE i.trashed_at IS NULL");
 
-    // --- 1. RATING FILTER ---
-    if rating_filter != "all" {
-        if rating_filter == "nsfw" {
-            where_clauses.push("(i.rating = 'q' OR i.rating = 'e')".to_string());
-        } else {
-            params_store.push(rating_filter);
-            where_clauses.push(format!("i.rating = ?{}", params_store.len()));
-        }
-    }
-
-    // --- 2. SOURCE FILTER ---
-    if source_filter != "all" {
-        params_store.push(source_filter);
-        where_clauses.push(format!("i.source = ?{}", params_store.len()));
-    }
-
-    // --- 3. TAG SEARCH ---
-    let terms: Vec<&str> = search_query.split_whitespace().collect();
-    for term in terms {
-        // --- 1. NEGATED TYPE (-type:image) ---
-        if term.starts_with("-type:") {
-            let val = term.replace("-type:", "").to_lowercase();
-            match val.as_str() {
-                "image" | "img" => where_clauses.push("NOT (i.ext IN ('jpg', 'jpeg', 'png', 'webp'))".to_string()),
-                "video" | "vid" => where_clauses.push("NOT (i.ext IN ('mp4', 'webm'))".to_string()),
-                "gif" => where_clauses.push("i.ext != 'gif'".to_string()),
-                _ => {}
-            }
-        }
-        // --- 2. POSITIVE TYPE (type:video) ---
-        else if term.starts_with("type:") {
-            let val = term.replace("type:", "").to_lowercase();
-            match val.as_str() {
-                "image" | "img" => where_clauses.push("(i.ext IN ('jpg', 'jpeg', 'png', 'webp'))".to_string()),
-                "video" | "vid" => where_clauses.push("(i.ext IN ('mp4', 'webm'))".to_string()),
-                "gif" => where_clauses.push("(i.ext = 'gif')".to_string()),
-                _ => {}
-            }
-        }
-        // --- 3. NEGATED EXTENSION (-ext:png) ---
-        else if term.starts_with("-ext:") {
-            let val = term.replace("-ext:", "").to_lowercase();
-            params_store.push(val);
-            where_clauses.push(format!("i.ext != ?{}", params_store.len()));
-        }
-        // --- 4. POSITIVE EXTENSION (ext:png) ---
-        else if term.starts_with("ext:") {
-            let val = term.replace("ext:", "").to_lowercase();
-            params_store.push(val);
-            where_clauses.push(format!("i.ext = ?{}", params_store.len()));
-        }
-        // --- 5. META TAGS (rating, source, order - ignored here, handled by params) ---
-        // We skip these so they don't get treated as generic tags
-        else if term.starts_with("rating:") {
-            let val = term.replace("rating:", "").to_lowercase();
-            // Map common aliases
-            let r = match val.as_str() {
-                "safe" | "s" => "s",
-                "questionable" | "q" => "q",
-                "explicit" | "e" => "e",
-                _ => "s"
-            };
-            params_store.push(r.to_string());
-            where_clauses.push(format!("i.rating = ?{}", params_store.len()));
-        }
-        else if term.starts_with("-rating:") {
-            let val = term.replace("-rating:", "").to_lowercase();
-            let r = match val.as_str() {
-                "safe" | "s" => "s",
-                "questionable" | "q" => "q",
-                "explicit" | "e" => "e",
-                _ => "s"
-            };
-            params_store.push(r.to_string());
-            where_clauses.push(format!("i.rating != ?{}", params_store.len()));
+    sql.push_str(&query.where_sql);
+    sql.push_str(&format!(" ORDER BY {} LIMIT {} OFFSET {}", query.order_by, limit, offset));
+
+    // Prepare & Execute
+    let db_params = query.sql_params();
+    let mut stmt = conn.prepare(&sql)?;
+    
+    let rows = stmt.query_map(&*db_params, |r| item_dto_from_row(root, r))?;
+
+    let mut out = vec![];
+    for row in rows {
+        out.push(row?);
+    }
+    Ok(out)
+}
+
+// One search word as an FTS5 string literal, so punctuation can't become query
+// syntax; a trailing * stays a prefix match
+fn fts_term(val: &str) -> Option<String> {
+    let (word, prefix) = match val.strip_suffix('*') {
+        Some(w) => (w, "*"),
+        None => (val, ""),
+    };
+    if word.is_empty() {
+        return None;
+    }
+    Some(format!("\"{}\"{}", word.replace('"', "\"\""), prefix))
+}
+
+// Columns read by item_dto_from_row, for queries over `items i`
+const ITEM_DTO_COLUMNS: &str = r#"
+          i.item_id, i.source, i.source_id, i.remote_url, i.file_rel, i.ext,
+          i.rating, i.fav_count, i.score_total, i.created_at, i.added_at,
+          (SELECT GROUP_CONCAT(t.name, char(9)) FROM item_tags it JOIN tags t ON it.tag_id = t.tag_id WHERE it.item_id = i.item_id),
+          (SELECT GROUP_CONCAT(t.name, char(9)) FROM item_tags it JOIN tags t ON it.tag_id = t.tag_id WHERE it.item_id = i.item_id AND t.type = 'artist'),
+          (SELECT GROUP_CONCAT(s.url, char(9)) FROM item_sources isrc JOIN sources s ON isrc.source_row_id = s.source_row_id WHERE isrc.item_id = i.item_id),
+          i.primary_artist, i.mime, i.notes,
+          i.uploader_id, (SELECT u.name FROM e621_users u WHERE u.user_id = i.uploader_id),
+          i.approver_id, (SELECT u.name FROM e621_users u WHERE u.user_id = i.approver_id)
+"#;
+
+fn item_dto_from_row(root: &std::path::Path, r: &Row) -> rusqlite::Result<ItemDto> {
+    let file_rel: String = r.get(4)?;
+    let file_abs = root.join(&file_rel);
+
+    let split_tab = |s: String| -> Vec<String> {
+        if s.is_empty() { vec![] } else { s.split('\t').map(|x| x.to_string()).collect() }
+    };
+
+    Ok(ItemDto {
+        item_id: r.get(0)?,
+        source: r.get(1)?,
+        source_id: r.get(2)?,
+        remote_url: r.get(3)?,
+        file_rel,
+        file_abs: file_abs.to_string_lossy().to_string(),
+        ext: r.get(5)?,
+        rating: r.get(6)?,
+        fav_count: r.get(7)?,
+        score_total: r.get(8)?,
+        timestamp: r.get(9)?,
+        added_at: r.get(10)?,
+        tags: split_tab(r.get(11).unwrap_or_default()),
+        artists: split_tab(r.get(12).unwrap_or_default()),
+        primary_artist: r.get(14)?,
+        mime: r.get(15)?,
+        sources: split_tab(r.get(13).unwrap_or_default()),
+        notes: r.get(16)?,
+        uploader_id: r.get(17)?,
+        uploader_name: r.get(18)?,
+        approver_id: r.get(19)?,
+        approver_name: r.get(20)?,
+    })
+}
+
+// untagged:true and next_untagged: nothing but artist tags (or none at all). FA
+// imports always carry their artist tag, so "zero tags" would never match them.
+const UNTAGGED_CLAUSE: &str =
+    "NOT EXISTS (SELECT 1 FROM item_tags it JOIN tags t ON it.tag_id = t.tag_id WHERE it.item_id = i.item_id AND t.type <> 'artist')";
+
+// Filter and ordering shared by list_items and anything that has to agree with
+// it on which items are visible and in what order (prev/next navigation).
+struct ItemQuery {
+    // " AND ..." appended after `WHERE i.trashed_at IS NULL`, or empty
+    where_sql: String,
+    params: Vec<String>,
+    order_by: &'static str,
+    random: bool,
+}
+
+impl ItemQuery {
+    fn build(
+        conn: &Connection,
+        search: Option<String>,
+        rating: Option<String>,
+        source: Option<String>,
+        order: Option<String>,
+    ) -> Result<Self, AppError> {
+        let search_query = search.unwrap_or_default();
+        let rating_filter = rating.unwrap_or("all".to_string());
+        let source_filter = source.unwrap_or("all".to_string());
+        let mut sort_order = order.unwrap_or("newest".to_string());
+
+        let mut params_store: Vec<String> = vec![]; 
+        let mut where_clauses: Vec<String> = vec![];
+        let expand = expand_implications(conn)?;
+        let mut quarantined = false;
+
+        // --- 0. SFW MODE ---
+        // A fixed clause ANDed with everything else, so no rating:/rating param can widen it
+        if sfw_mode(conn)? {
+            where_clauses.push("i.rating = 's'".to_string());
         }
-        else if term.starts_with("source:") || term.starts_with("order:") {
-            continue;
+
+        // --- 1. RATING FILTER ---
+        if rating_filter != "all" {
+            if rating_filter == "nsfw" {
+                where_clauses.push("(i.rating = 'q' OR i.rating = 'e')".to_string());
+            } else {
+                params_store.push(rating_filter);
+                where_clauses.push(format!("i.rating = ?{}", params_store.len()));
+            }
+        }
+
+        // --- 2. SOURCE FILTER ---
+        if source_filter != "all" {
+            params_store.push(source_filter);
+            where_clauses.push(format!("i.source = ?{}", params_store.len()));
         }
-        // --- 6. NEGATED TAG (-tag) ---
-        else if term.starts_with("-") {
-            let tag = term.trim_start_matches("-").to_lowercase();
-            params_store.push(tag);
-            where_clauses.push(format!(
-                "NOT EXISTS (SELECT 1 FROM item_tags it JOIN tags t ON it.tag_id = t.tag_id WHERE it.item_id = i.item_id AND t.name = ?{})", 
-                params_store.len()
-            ));
-        }
-        // --- 7. REGULAR TAG (tag) ---
-        else {
-            let tag = term.to_lowercase();
-            if tag.contains("*") {
-                let like_tag = tag.replace("*", "%");
-                params_store.push(like_tag);
+
+        // --- 3. TAG SEARCH ---
+        let terms: Vec<&str> = search_query.split_whitespace().collect();
+        for term in terms {
+            // --- 1. NEGATED TYPE (-type:image) ---
+            if term.starts_with("-type:") {
+                let val = term.replace("-type:", "").to_lowercase();
+                if let Some(clause) = media_type_clause(&val) {
+                    where_clauses.push(format!("NOT {}", clause));
+                }
+            }
+            // --- 2. POSITIVE TYPE (type:video) ---
+            else if term.starts_with("type:") {
+                let val = term.replace("type:", "").to_lowercase();
+                if let Some(clause) = media_type_clause(&val) {
+                    where_clauses.push(clause.to_string());
+                }
+            }
+            // --- 3. NEGATED EXTENSION (-ext:png) ---
+            else if term.starts_with("-ext:") {
+                let val = term.replace("-ext:", "").to_lowercase();
+                params_store.push(val);
+                where_clauses.push(format!("i.ext != ?{}", params_store.len()));
+            }
+            // --- 4. POSITIVE EXTENSION (ext:png) ---
+            else if term.starts_with("ext:") {
+                let val = term.replace("ext:", "").to_lowercase();
+                params_store.push(val);
+                where_clauses.push(format!("i.ext = ?{}", params_store.len()));
+            }
+            // --- 5. META TAGS (rating, source, order - ignored here, handled by params) ---
+            // We skip these so they don't get treated as generic tags
+            else if term.starts_with("rating:") {
+                let val = term.replace("rating:", "").to_lowercase();
+                // Map common aliases
+                let r = match val.as_str() {
+                    "safe" | "s" => "s",
+                    "questionable" | "q" => "q",
+                    "explicit" | "e" => "e",
+                    _ => "s"
+                };
+                params_store.push(r.to_string());
+                where_clauses.push(format!("i.rating = ?{}", params_store.len()));
+            }
+            else if term.starts_with("-rating:") {
+                let val = term.replace("-rating:", "").to_lowercase();
+                let r = match val.as_str() {
+                    "safe" | "s" => "s",
+                    "questionable" | "q" => "q",
+                    "explicit" | "e" => "e",
+                    _ => "s"
+                };
+                params_store.push(r.to_string());
+                where_clauses.push(format!("i.rating != ?{}", params_store.len()));
+            }
+            // An order: term in the search box overrides the order param
+            else if let Some(val) = term.strip_prefix("order:") {
+                sort_order = val.to_lowercase();
+            }
+            else if term.starts_with("source:") {
+                continue;
+            }
+            // --- DIMENSIONS (width:>1920, height:<500, ratio:landscape) ---
+            else if let Some(val) = term.strip_prefix("width:") {
+                if let Some(clause) = dimension_clause("width", val) {
+                    where_clauses.push(clause);
+                }
+            }
+            else if let Some(val) = term.strip_prefix("height:") {
+                if let Some(clause) = dimension_clause("height", val) {
+                    where_clauses.push(clause);
+                }
+            }
+            else if let Some(val) = term.strip_prefix("ratio:") {
+                if let Some(clause) = ratio_clause(&val.to_lowercase()) {
+                    where_clauses.push(clause);
+                }
+            }
+            // --- UPSTREAM STATUS (status:deleted, status:pending, status:active) ---
+            else if let Some(val) = term.strip_prefix("status:") {
+                params_store.push(val.to_lowercase());
+                where_clauses.push(format!("i.remote_status = ?{}", params_store.len()));
+            }
+            // --- NOTES (note:wip, note:commission*) ---
+            else if let Some(val) = term.strip_prefix("note:") {
+                if let Some(q) = fts_term(val) {
+                    params_store.push(format!("notes : {q}"));
+                    where_clauses.push(format!(
+                        "i.item_id IN (SELECT item_id FROM fts_items WHERE fts_items MATCH ?{})",
+                        params_store.len()
+                    ));
+                }
+            }
+            // --- COLLECTION (collection:best_of_2024, underscores standing in for spaces) ---
+            else if let Some(val) = term.strip_prefix("collection:") {
+                params_store.push(val.to_string());
+                let n = params_store.len();
                 where_clauses.push(format!(
-                    "EXISTS (SELECT 1 FROM item_tags it JOIN tags t ON it.tag_id = t.tag_id WHERE it.item_id = i.item_id AND t.name LIKE ?{})", 
-                    params_store.len()
+                    "EXISTS (SELECT 1 FROM collection_items ci JOIN collections c ON c.collection_id = ci.collection_id WHERE ci.item_id = i.item_id AND (c.name = ?{n} OR REPLACE(c.name, ' ', '_') = ?{n} COLLATE NOCASE))"
                 ));
-            } else {
+            }
+            // --- PROVENANCE (via:furaffinity) ---
+            else if let Some(val) = term.strip_prefix("via:") {
+                let via = match val.to_lowercase().as_str() {
+                    "fa" | "furaffinity" => "furaffinity".to_string(),
+                    other => other.to_string(),
+                };
+                params_store.push(via);
+                where_clauses.push(format!("i.discovered_via = ?{}", params_store.len()));
+            }
+            // --- SYNC BATCH (batch:<id>) ---
+            else if term.starts_with("batch:") {
+                let val = term.trim_start_matches("batch:").to_string();
+                params_store.push(val);
+                where_clauses.push(format!("i.sync_batch = ?{}", params_store.len()));
+            }
+            // --- UPLOADER (uploader:<name>, or the numeric e621 user id) ---
+            else if let Some(val) = term.strip_prefix("uploader:") {
+                params_store.push(val.to_string());
+                let n = params_store.len();
+                if val.chars().all(|c| c.is_ascii_digit()) {
+                    where_clauses.push(format!("i.uploader_id = CAST(?{n} AS INTEGER)"));
+                } else {
+                    where_clauses.push(format!(
+                        "i.uploader_id IN (SELECT user_id FROM e621_users WHERE name = ?{n} COLLATE NOCASE)"
+                    ));
+                }
+            }
+            // --- VIEWED (seen:false for never opened, seen:true for the rest) ---
+            else if let Some(val) = term.strip_prefix("seen:") {
+                match val.to_lowercase().as_str() {
+                    "false" | "no" => where_clauses.push("i.last_viewed_at IS NULL".to_string()),
+                    "true" | "yes" => where_clauses.push("i.last_viewed_at IS NOT NULL".to_string()),
+                    _ => {}
+                }
+            }
+            // --- MISSING METADATA (artist:none, untagged:true) ---
+            else if term.eq_ignore_ascii_case("artist:none") {
+                where_clauses.push(
+                    "NOT EXISTS (SELECT 1 FROM item_tags it JOIN tags t ON it.tag_id = t.tag_id WHERE it.item_id = i.item_id AND t.type = 'artist' AND t.name NOT IN ('unknown', 'unknown_artist'))".to_string()
+                );
+            }
+            else if term.eq_ignore_ascii_case("untagged:true") {
+                where_clauses.push(UNTAGGED_CLAUSE.to_string());
+            }
+            else if term.eq_ignore_ascii_case("quarantined:true") {
+                quarantined = true;
+            }
+            // --- 6. NEGATED TAG (-tag) ---
+            else if term.starts_with("-") {
+                let tag = term.trim_start_matches("-").to_lowercase();
                 params_store.push(tag);
                 where_clauses.push(format!(
-                    "EXISTS (SELECT 1 FROM item_tags it JOIN tags t ON it.tag_id = t.tag_id WHERE it.item_id = i.item_id AND t.name = ?{})", 
+                    "NOT EXISTS (SELECT 1 FROM item_tags it JOIN tags t ON it.tag_id = t.tag_id WHERE it.item_id = i.item_id AND t.name = ?{})", 
                     params_store.len()
                 ));
             }
+            // --- 7. REGULAR TAG (tag) ---
+            else {
+                let tag = term.to_lowercase();
+                if tag.contains("*") {
+                    let like_tag = tag.replace("*", "%");
+                    params_store.push(like_tag);
+                    where_clauses.push(format!(
+                        "EXISTS (SELECT 1 FROM item_tags it JOIN tags t ON it.tag_id = t.tag_id WHERE it.item_id = i.item_id AND t.name LIKE ?{})", 
+                        params_store.len()
+                    ));
+                } else if expand {
+                    // the tag itself or anything that implies it, transitively (husky -> canine -> canid)
+                    params_store.push(tag);
+                    where_clauses.push(format!(
+                        "EXISTS (SELECT 1 FROM item_tags it JOIN tags t ON it.tag_id = t.tag_id WHERE it.item_id = i.item_id AND t.name IN (
+                            WITH RECURSIVE implied(name) AS (
+                                SELECT ?{n}
+                                UNION SELECT ti.antecedent FROM tag_implications ti JOIN implied ON ti.consequent = implied.name
+                            ) SELECT name FROM implied))",
+                        n = params_store.len()
+                    ));
+                } else {
+                    params_store.push(tag);
+                    where_clauses.push(format!(
+                        "EXISTS (SELECT 1 FROM item_tags it JOIN tags t ON it.tag_id = t.tag_id WHERE it.item_id = i.item_id AND t.name = ?{})", 
+                        params_store.len()
+                    ));
+                }
+            }
         }
+
+        // quarantined items only show up when asked for, and then on their own
+        where_clauses.push(format!("i.quarantined = {}", quarantined as u8));
+
+        let where_sql = if where_clauses.is_empty() {
+            String::new()
+        } else {
+            format!(" AND {}", where_clauses.join(" AND "))
+        };
+
+        // --- 4. ORDERING ---
+        // item_id breaks ties so paging and prev/next agree on one total order
+        let order_by = match sort_order.as_str() {
+            "score" => "i.score_total DESC, i.item_id DESC",
+            "favs" | "favcount" => "i.fav_count DESC, i.item_id DESC",
+            "random" => "RANDOM()",
+            "oldest" => "i.added_at ASC, i.item_id ASC",
+            "created" => "i.created_at DESC, i.added_at DESC, i.item_id DESC",
+            "created_asc" => "i.created_at ASC, i.added_at ASC, i.item_id ASC",
+            "artist" => "i.primary_artist IS NULL, i.primary_artist ASC, i.added_at DESC, i.item_id DESC",
+            _ => "i.added_at DESC, i.item_id DESC", // Default 'newest'
+        };
+
+        Ok(ItemQuery {
+            where_sql,
+            params: params_store,
+            order_by,
+            random: sort_order == "random",
+        })
+    }
+
+    // ANDs one more condition whose single placeholder is written as `?`
+    fn restrict(&mut self, clause: &str, param: String) {
+        self.params.push(param);
+        let clause = clause.replace('?', &format!("?{}", self.params.len()));
+        self.where_sql.push_str(&format!(" AND {}", clause));
     }
 
-    // --- APPLY WHERE ---
-    if !where_clauses.is_empty() {
-        sql.push_str(" AND ");
-        sql.push_str(&where_clauses.join(" AND "));
+    fn sql_params(&self) -> Vec<&dyn rusqlite::ToSql> {
+        self.params.iter().map(|s| s as &dyn rusqlite::ToSql).collect()
     }
+}
+
+// Ratios within this band of 1.0 count as square
+const SQUARE_RATIO_TOLERANCE: f64 = 0.05;
+
+// `width:>1920`, `height:<=500`, `width:1080` -> comparison on the stored column
+fn dimension_clause(column: &str, val: &str) -> Option<String> {
+    let (op, num) = ["<=", ">=", "<", ">", "="]
+        .iter()
+        .find_map(|op| val.strip_prefix(op).map(|rest| (*op, rest)))
+        .unwrap_or(("=", val));
+    let n: u32 = num.parse().ok()?;
+    Some(format!("(i.{column} IS NOT NULL AND i.{column} {op} {n})"))
+}
 
-    // --- 4. ORDERING ---
-    let order_clause = match sort_order.as_str() {
-        "score" => "ORDER BY i.score_total DESC",
-        "favs" | "favcount" => "ORDER BY i.fav_count DESC",
-        "random" => "ORDER BY RANDOM()",
-        "oldest" => "ORDER BY i.added_at ASC",
-        _ => "ORDER BY i.added_at DESC", // Default 'newest'
+// `ratio:landscape|portrait|square`; rows without dimensions never match
+fn ratio_clause(val: &str) -> Option<String> {
+    let ratio = "CAST(i.width AS REAL) / i.height";
+    let cond = match val {
+        "landscape" | "wide" => format!("{ratio} > {}", 1.0 + SQUARE_RATIO_TOLERANCE),
+        "portrait" | "tall" => format!("{ratio} < {}", 1.0 - SQUARE_RATIO_TOLERANCE),
+        "square" => format!("{ratio} BETWEEN {} AND {}", 1.0 - SQUARE_RATIO_TOLERANCE, 1.0 + SQUARE_RATIO_TOLERANCE),
+        _ => return None,
     };
+    Some(format!("(i.width IS NOT NULL AND i.height > 0 AND {cond})"))
+}
 
-    sql.push_str(&format!(" {} LIMIT {} OFFSET {}", order_clause, limit, offset));
+// type: filter, preferring the sniffed MIME and falling back to the extension for older rows
+fn media_type_clause(val: &str) -> Option<&'static str> {
+    match val {
+        "image" | "img" => Some("(CASE WHEN i.mime IS NOT NULL THEN (i.mime LIKE 'image/%' AND i.mime != 'image/gif') ELSE i.ext IN ('jpg', 'jpeg', 'png', 'webp') END)"),
+        "video" | "vid" => Some("(CASE WHEN i.mime IS NOT NULL THEN i.mime LIKE 'video/%' ELSE i.ext IN ('mp4', 'webm') END)"),
+        "gif" => Some("(CASE WHEN i.mime IS NOT NULL THEN i.mime = 'image/gif' ELSE i.ext = 'gif' END)"),
+        _ => None,
+    }
+}
 
-    // Prepare & Execute
-    let db_params: Vec<&dyn rusqlite::ToSql> = params_store.iter().map(|s| s as &dyn rusqlite::ToSql).collect();
-    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
-    
-    let rows = stmt.query_map(&*db_params, |r| {
-        let file_rel: String = r.get(4)?;
-        let file_abs = root.join(&file_rel);
-        
-        let split_tab = |s: String| -> Vec<String> {
-            if s.is_empty() { vec![] } else { s.split('\t').map(|x| x.to_string()).collect() }
-        };
+// Cached thumbnail location, keyed by the md5 of the library-relative path
+pub fn thumb_path_for(root: &std::path::Path, file_rel: &str) -> PathBuf {
+    root.join(legacy_thumb_rel(file_rel))
+}
 
-        Ok(ItemDto {
-            item_id: r.get(0)?,
-            source: r.get(1)?,
-            source_id: r.get(2)?,
-            remote_url: r.get(3)?,
-            file_rel: file_rel,
-            file_abs: file_abs.to_string_lossy().to_string(),
-            ext: r.get(5)?,
-            rating: r.get(6)?,
-            fav_count: r.get(7)?,
-            score_total: r.get(8)?,
-            timestamp: r.get(9)?,
-            added_at: r.get(10)?,
-            tags: split_tab(r.get(11).unwrap_or_default()),
-            artists: split_tab(r.get(12).unwrap_or_default()),
-            sources: split_tab(r.get(13).unwrap_or_default()),
-        })
-    }).map_err(|e| e.to_string())?;
+// Path-keyed cache name, used when the content hash is unknown
+fn legacy_thumb_rel(file_rel: &str) -> String {
+    format!(".cache/thumbs/{:x}.jpg", md5::compute(file_rel.as_bytes()))
+}
 
-    let mut out = vec![];
-    for row in rows {
-        out.push(row.map_err(|e| e.to_string())?);
+// Content-keyed cache name: survives moves/renames and identical files share it
+pub fn thumb_rel_for(content_md5: Option<&str>, file_rel: &str) -> String {
+    match content_md5 {
+        Some(m) if !m.trim().is_empty() => format!(".cache/thumbs/{}.jpg", m.trim().to_lowercase()),
+        _ => legacy_thumb_rel(file_rel),
     }
-    Ok(out)
 }
 
-// Add this helper function
-pub fn generate_and_save_thumb(root: &std::path::Path, file_rel: &str) {
-    let path = root.join(file_rel);
-    let cache_dir = root.join(".cache").join("thumbs");
-    
-    // Create cache dir if missing
-    if !cache_dir.exists() {
-        let _ = std::fs::create_dir_all(&cache_dir);
+// Remove cached thumbnails that no remaining row points at
+fn drop_unreferenced_thumbs(root: &std::path::Path, conn: &Connection, thumb_rels: Vec<String>) -> Result<(), AppError> {
+    for thumb_rel in thumb_rels {
+        let still_used: bool = conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM items WHERE thumb_rel = ?)",
+            [&thumb_rel],
+            |r| r.get(0)
+        )?;
+        if !still_used {
+            let _ = std::fs::remove_file(root.join(&thumb_rel));
+        }
     }
+    Ok(())
+}
 
-    let name_hash = format!("{:x}", md5::compute(file_rel.as_bytes()));
-    let thumb_path = cache_dir.join(format!("{}.jpg", name_hash));
+// Animated gif/webp: use the first frame as a still preview
+fn open_first_frame(src: &std::path::Path) -> image::ImageResult<image::DynamicImage> {
+    use image::AnimationDecoder;
 
-    if thumb_path.exists() { return; }
+    let ext = src.extension().and_then(|s| s.to_str()).unwrap_or("").to_lowercase();
+    let reader = || -> image::ImageResult<std::io::BufReader<fs::File>> {
+        Ok(std::io::BufReader::new(fs::File::open(src)?))
+    };
 
-    // Try to open and resize
-    if let Ok(img) = image::open(&path) {
-        let thumb = img.resize(400, u32::MAX, image::imageops::FilterType::Lanczos3);
-        let mut bytes: Vec<u8> = Vec::new();
-        if thumb.write_to(&mut std::io::Cursor::new(&mut bytes), image::ImageOutputFormat::Jpeg(70)).is_ok() {
-            let _ = std::fs::write(thumb_path, &bytes);
+    let frame = match ext.as_str() {
+        "gif" => image::codecs::gif::GifDecoder::new(reader()?)?.into_frames().next(),
+        "webp" => {
+            let decoder = image::codecs::webp::WebPDecoder::new(reader()?)?;
+            if !decoder.has_animation() {
+                return image::open(src);
+            }
+            decoder.into_frames().next()
         }
+        _ => return image::open(src),
+    };
+
+    match frame {
+        Some(frame) => Ok(image::DynamicImage::ImageRgba8(frame?.into_buffer())),
+        None => image::open(src),
     }
 }
 
+// Thumbnail width in pixels, from the thumb_width setting
+const DEFAULT_THUMB_WIDTH: u32 = 400;
+const THUMB_WIDTH_RANGE: (u32, u32) = (100, 1600);
+
+pub fn thumb_width(conn: &Connection) -> u32 {
+    settings_get(conn, "thumb_width").ok().flatten()
+        .and_then(|v| v.trim().parse::<u32>().ok())
+        .unwrap_or(DEFAULT_THUMB_WIDTH)
+        .clamp(THUMB_WIDTH_RANGE.0, THUMB_WIDTH_RANGE.1)
+}
+
 #[tauri::command]
-pub async fn ensure_thumbnail(app: tauri::AppHandle, file_rel: String) -> Result<String, String> {
-    // Offload to a blocking thread to prevent freezing the UI
-    tauri::async_runtime::spawn_blocking(move || {
-        let root = get_root(&app)?;
-        let path = root.join(&file_rel);
-        
-        // Cache location: library_root/.cache/thumbs/
-        let cache_dir = root.join(".cache").join("thumbs");
-        if !cache_dir.exists() {
-            std::fs::create_dir_all(&cache_dir).map_err(|e| e.to_string())?;
-        }
-        
-        // Use MD5 of the relative path as filename
-        let name_hash = format!("{:x}", md5::compute(file_rel.as_bytes()));
-        let thumb_filename = format!("{}.jpg", name_hash);
-        let thumb_path = cache_dir.join(&thumb_filename);
-        
-        // 1. If thumbnail exists, return it immediately
-        if thumb_path.exists() {
-            return Ok(thumb_path.to_string_lossy().to_string());
-        }
-        
-        // 2. Skip videos/gifs for now (return empty string -> frontend uses fallback)
-        let ext = path.extension().and_then(|s| s.to_str()).unwrap_or("").to_lowercase();
-        if ["mp4", "webm", "gif"].contains(&ext.as_str()) {
-            return Ok("".to_string());
+pub fn get_thumb_width(app: tauri::AppHandle) -> Result<u32, AppError> {
+    let root = get_root(&app)?;
+    let conn = open_conn_for_root(&app, &root)?;
+    Ok(thumb_width(&conn))
+}
+
+// Only affects thumbnails generated from now on; rebuild_thumbnails redoes the rest
+#[tauri::command]
+pub fn set_thumb_width(app: tauri::AppHandle, width: u32) -> Result<u32, AppError> {
+    let root = get_root(&app)?;
+    let conn = open_conn_for_root(&app, &root)?;
+    let width = width.clamp(THUMB_WIDTH_RANGE.0, THUMB_WIDTH_RANGE.1);
+    settings_set(&conn, "thumb_width", &width.to_string())?;
+    Ok(width)
+}
+
+// Decode, downscale and encode one thumbnail. Written to a temp file and renamed
+// so the asset protocol never serves a half-written jpg.
+fn render_thumbnail(src: &std::path::Path, thumb_path: &std::path::Path, width: u32) -> Result<(), AppError> {
+    let img = open_first_frame(src).map_err(|e| AppError::Other(format!("Failed to open image: {}", e)))?;
+    let thumb = img.resize(width, u32::MAX, image::imageops::FilterType::Lanczos3);
+    // JPEG has no alpha or 16-bit support, so flatten before encoding
+    let thumb = image::DynamicImage::ImageRgb8(thumb.to_rgb8());
+
+    let mut bytes: Vec<u8> = Vec::new();
+    // Encode as JPEG (quality 70 is good enough for thumbs)
+    thumb.write_to(&mut std::io::Cursor::new(&mut bytes), image::ImageOutputFormat::Jpeg(70))?;
+
+    if let Some(dir) = thumb_path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let tmp_path = thumb_path.with_extension("jpg.part");
+    std::fs::write(&tmp_path, &bytes)?;
+    std::fs::rename(&tmp_path, thumb_path)?;
+    Ok(())
+}
+
+pub fn generate_and_save_thumb(root: &std::path::Path, file_rel: &str, width: u32) {
+    let thumb_path = thumb_path_for(root, file_rel);
+    if thumb_path.exists() { return; }
+
+    let _ = render_thumbnail(&root.join(file_rel), &thumb_path, width);
+}
+
+#[derive(Serialize, Default)]
+pub struct ThumbInfo {
+    // absolute path of the cached jpg, "" when the item has no still thumbnail
+    pub path: String,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    // "#rrggbb" average of the thumbnail
+    pub color: Option<String>,
+    // a format nothing here can render (flash), so there will never be a thumb
+    pub unsupported: bool,
+}
+
+// Formats e621 still serves that neither the thumbnailer nor the webview can show
+const UNSUPPORTED_EXTS: &[&str] = &["swf"];
+
+pub fn is_unsupported_ext(ext: &str) -> bool {
+    UNSUPPORTED_EXTS.contains(&ext.to_lowercase().as_str())
+}
+
+// Average color of an (already downscaled) image, as a css hex string
+fn average_color(img: &image::DynamicImage) -> String {
+    let px = img.thumbnail_exact(1, 1).to_rgb8();
+    let [r, g, b] = px.get_pixel(0, 0).0;
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+// Bounds how many thumbnails are decoded and encoded at once
+pub struct ThumbnailPool {
+    pub permits: Arc<tokio::sync::Semaphore>,
+}
+
+impl Default for ThumbnailPool {
+    fn default() -> Self {
+        let n = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(4);
+        ThumbnailPool { permits: Arc::new(tokio::sync::Semaphore::new(n)) }
+    }
+}
+
+// Returns the cached jpg (path for convertFileSrc) with its size and placeholder
+// color. Image bytes never cross IPC.
+#[tauri::command]
+pub async fn ensure_thumbnail(app: tauri::AppHandle, file_rel: String) -> Result<ThumbInfo, AppError> {
+    // Cache hits come straight back; renders wait for a pool slot so a grid full
+    // of new items can't tie up a blocking thread (and a decoded image) each
+    let (lookup_app, lookup_rel) = (app.clone(), file_rel.clone());
+    let cached = tauri::async_runtime::spawn_blocking(move || thumbnail_for(&lookup_app, &lookup_rel, false)).await??;
+    if let Some(info) = cached {
+        return Ok(info);
+    }
+
+    let permits = app.state::<ThumbnailPool>().permits.clone();
+    let _permit = permits.acquire_owned().await.map_err(|e| AppError::Other(e.to_string()))?;
+    // looks again first: a call for the same file may have rendered it meanwhile
+    let info = tauri::async_runtime::spawn_blocking(move || thumbnail_for(&app, &file_rel, true)).await??;
+    Ok(info.unwrap_or_default())
+}
+
+// The thumbnail for `file_rel`, or None when it would have to be rendered and
+// `render` is false
+fn thumbnail_for(app: &tauri::AppHandle, file_rel: &str, render: bool) -> Result<Option<ThumbInfo>, AppError> {
+    let root = get_root(app)?;
+    let path = library::resolve_in_root(&root, file_rel)?;
+    let conn = open_conn_for_root(app, &root)?;
+
+    // (item_id, thumb_rel, content md5, thumb_color)
+    type ThumbRow = (i64, Option<String>, Option<String>, Option<String>);
+    let row: Option<ThumbRow> = conn.query_row(
+        "SELECT item_id, thumb_rel, COALESCE(file_md5, md5), thumb_color FROM items WHERE file_rel = ?",
+        [file_rel],
+        |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?))
+    ).optional()?;
+
+    // Reads the thumb's header for its size; decodes it only when the color isn't cached yet
+    let describe = |thumb_abs: &std::path::Path, cached_color: Option<String>| -> Result<ThumbInfo, AppError> {
+        let (width, height) = match image::image_dimensions(thumb_abs) {
+            Ok((w, h)) => (Some(w), Some(h)),
+            Err(_) => (None, None),
+        };
+        let color = match cached_color {
+            Some(c) => Some(c),
+            None => {
+                let color = image::open(thumb_abs).ok().map(|img| average_color(&img));
+                if let (Some(c), Some((item_id, ..))) = (&color, &row) {
+                    conn.execute("UPDATE items SET thumb_color = ? WHERE item_id = ?", params![c, item_id])?;
+                }
+                color
+            }
+        };
+        Ok(ThumbInfo { path: thumb_abs.to_string_lossy().to_string(), width, height, color, unsupported: false })
+    };
+
+    // 1. Already generated for this item: no filesystem check needed
+    if let Some((_, Some(thumb_rel), _, color)) = &row {
+        return describe(&root.join(thumb_rel), color.clone()).map(Some);
+    }
+
+    // Also fills in dimensions for rows imported before they were recorded
+    let remember = |thumb_rel: &str| -> Result<(), AppError> {
+        if let Some((item_id, ..)) = &row {
+            let dims = image::image_dimensions(&path).ok();
+            conn.execute(
+                "UPDATE items SET thumb_rel = ?, width = COALESCE(width, ?), height = COALESCE(height, ?) WHERE item_id = ?",
+                params![thumb_rel, dims.map(|d| d.0), dims.map(|d| d.1), item_id]
+            )?;
         }
+        Ok(())
+    };
+
+    // Cache location: library_root/.cache/thumbs/, by content hash when known
+    let thumb_rel = thumb_rel_for(row.as_ref().and_then(|r| r.2.as_deref()), file_rel);
+    let thumb_path = root.join(&thumb_rel);
 
-        if !path.exists() {
-            return Err(format!("Source file not found: {:?}", path));
+    // 1b. A matching thumbnail exists on disk (imported, a duplicate's, or an
+    // older path-keyed one): record it and return it
+    for candidate in [thumb_rel.clone(), legacy_thumb_rel(file_rel)] {
+        if root.join(&candidate).exists() {
+            remember(&candidate)?;
+            return describe(&root.join(&candidate), None).map(Some);
         }
+    }
+    
+    // 2. Skip videos for now (empty path -> frontend uses fallback)
+    let ext = path.extension().and_then(|s| s.to_str()).unwrap_or("").to_lowercase();
+    if is_unsupported_ext(&ext) {
+        return Ok(Some(ThumbInfo { unsupported: true, ..Default::default() }));
+    }
+    if ["mp4", "webm"].contains(&ext.as_str()) {
+        return Ok(Some(ThumbInfo::default()));
+    }
 
-        // 3. Generate Thumbnail
-        // This is the slow part!
-        let img = image::open(&path).map_err(|e| format!("Failed to open image: {}", e))?;
-        let thumb = img.resize(400, u32::MAX, image::imageops::FilterType::Lanczos3); // Resize
+    if !path.exists() {
+        return Err(AppError::NotFound(format!("Source file not found: {:?}", path)));
+    }
 
-        let mut bytes: Vec<u8> = Vec::new();
-        // Encode as JPEG (quality 70 is good enough for thumbs)
-        thumb.write_to(&mut std::io::Cursor::new(&mut bytes), image::ImageOutputFormat::Jpeg(70))
-            .map_err(|e| e.to_string())?;
-            
-        std::fs::write(&thumb_path, &bytes).map_err(|e| e.to_string())?;
-        
-        Ok(thumb_path.to_string_lossy().to_string())
-    }).await.map_err(|e| e.to_string())?
+    // 3. Generate Thumbnail
+    // This is the slow part!
+    if !render {
+        return Ok(None);
+    }
+    render_thumbnail(&path, &thumb_path, thumb_width(&conn))?;
+    remember(&thumb_rel)?;
+    
+    describe(&thumb_path, None).map(Some)
 }
 
-fn upsert_tag(conn: &Connection, name: &str, tag_type: &str) -> Result<i64, String> {
+fn upsert_tag(conn: &Connection, name: &str, tag_type: &str) -> Result<i64, AppError> {
   conn
     .execute(
       "INSERT INTO tags(name, type) VALUES(?, ?) ON CONFLICT(name) DO UPDATE SET type=excluded.type",
       params![name, tag_type],
-    )
-    .map_err(|e| e.to_string())?;
+    )?;
 
   let id: i64 = conn
     .query_row(
       "SELECT tag_id FROM tags WHERE name=?",
       params![name],
       |r: &Row| r.get(0),
-    )
-    .map_err(|e| e.to_string())?;
+    )?;
 
   Ok(id)
 }
 
-fn upsert_source(conn: &Connection, url: &str) -> Result<i64, String> {
+// Canonical form for source URLs so trivially different spellings share one row:
+// https scheme, lowercase host, no default port, no trailing slash on the path.
+// Anything that isn't an http(s) URL is stored as given (trimmed).
+pub fn normalize_url(raw: &str) -> String {
+  let trimmed = raw.trim();
+  let mut url = match reqwest::Url::parse(trimmed) {
+    Ok(u) if u.scheme() == "http" || u.scheme() == "https" => u,
+    _ => return trimmed.to_string(),
+  };
+
+  // Url already lowercases the host and drops the scheme's default port
+  if url.port() == Some(443) || url.port() == Some(80) {
+    let _ = url.set_port(None);
+  }
+  let _ = url.set_scheme("https");
+
+  let path = url.path().trim_end_matches('/').to_string();
+  url.set_path(&path);
+
+  let mut out = url.to_string();
+  // An empty path still serializes as "/" after the host
+  if url.query().is_none() && url.fragment().is_none() {
+    while out.ends_with('/') { out.pop(); }
+  }
+  out
+}
+
+fn upsert_source(conn: &Connection, url: &str) -> Result<i64, AppError> {
+  let url = normalize_url(url);
   conn
     .execute(
       "INSERT INTO sources(url) VALUES(?) ON CONFLICT(url) DO NOTHING",
       params![url],
-    )
-    .map_err(|e| e.to_string())?;
+    )?;
 
   let id: i64 = conn
     .query_row(
       "SELECT source_row_id FROM sources WHERE url=?",
       params![url],
       |r: &Row| r.get(0),
-    )
-    .map_err(|e| e.to_string())?;
+    )?;
 
   Ok(id)
 }
 
 
 #[tauri::command]
-pub fn trash_item(app: tauri::AppHandle, item_id: i64) -> Result<(), String> {
+pub fn trash_item(app: tauri::AppHandle, item_id: i64) -> Result<Vec<i64>, AppError> {
+    trash_items(app, vec![item_id])
+}
+
+// Returns the ids that were actually moved to the trash and emits them as
+// `item-trashed` so the UI can offer an undo.
+#[tauri::command]
+pub fn trash_items(app: tauri::AppHandle, ids: Vec<i64>) -> Result<Vec<i64>, AppError> {
     let root = get_root(&app)?;
-    let conn = db::open(&library::db_path(&root))?;
+    let conn = open_conn_for_root(&app, &root)?;
     
-    // Soft delete: Set trashed_at to current timestamp
+    // Set trashed_at and move the file under .trash/ so media/ only holds live items
     let now = chrono::Local::now().to_rfc3339();
     
-    conn.execute(
-        "UPDATE items SET trashed_at = ? WHERE item_id = ?",
-        [now, item_id.to_string()] // Convert i64 to string just in case, but params usually handles it
-    ).map_err(|e| e.to_string())?;
-    
-    Ok(())
+    let mut items = vec![];
+    for id in ids {
+        let file_rel: Option<String> = conn.query_row(
+            "SELECT file_rel FROM items WHERE item_id = ? AND trashed_at IS NULL",
+            [id],
+            |r| r.get(0)
+        ).optional()?;
+        items.extend(file_rel.map(|f| (id, f)));
+    }
+
+    relocate_batch(&root, &conn, &items, library::trash_rel, |tx, id| {
+        tx.execute("UPDATE items SET trashed_at = ? WHERE item_id = ?", params![now, id])?;
+        Ok(())
+    })?;
+    let trashed: Vec<i64> = items.into_iter().map(|(id, _)| id).collect();
+
+    if !trashed.is_empty() {
+        let _ = app.emit("item-trashed", &trashed);
+    }
+    Ok(trashed)
+}
+
+#[tauri::command]
+pub fn restore_last_trashed(app: tauri::AppHandle, count: Option<u32>) -> Result<Vec<i64>, AppError> {
+    let root = get_root(&app)?;
+    let conn = open_conn_for_root(&app, &root)?;
+
+    let ids: Vec<i64> = {
+        let mut stmt = conn.prepare(
+            "SELECT item_id FROM items WHERE trashed_at IS NOT NULL ORDER BY trashed_at DESC, item_id DESC LIMIT ?"
+        )?;
+        let rows = stmt.query_map([count.unwrap_or(1)], |r| r.get(0))?;
+        rows.collect::<Result<_, _>>()?
+    };
+    restore_trashed(&root, &conn, &ids)
 }
 
 #[tauri::command]
@@ -1389,40 +3282,3117 @@ pub fn auto_clean_trash(app: tauri::AppHandle) {
 }
 
 // Prune items trashed more than 30 days ago
-pub fn prune_expired_trash(app: &tauri::AppHandle) -> Result<(), String> {
+pub fn prune_expired_trash(app: &tauri::AppHandle) -> Result<(), AppError> {
     let root = match get_root(app) {
         Ok(r) => r,
         Err(_) => return Ok(()), // No library loaded yet
     };
     
-    let conn = db::open(&library::db_path(&root)).map_err(|e| e.to_string())?;
+    let conn = open_conn_for_root(app, &root)?;
 
     // 1. Find expired files
     // SQL: Select items trashed > 30 days ago
     // We use SQLite's datetime functions. 
     // 'now' is UTC. 'trashed_at' is stored as ISO8601 string.
     let mut stmt = conn.prepare(
-        "SELECT file_rel FROM items WHERE trashed_at < datetime('now', '-30 days') AND trashed_at IS NOT NULL"
-    ).map_err(|e| e.to_string())?;
+        "SELECT file_rel, thumb_rel, fa_original_rel FROM items WHERE trashed_at < datetime('now', '-30 days') AND trashed_at IS NOT NULL"
+    )?;
 
-    let files_to_delete: Vec<String> = stmt.query_map([], |row| row.get(0))
-        .map_err(|e| e.to_string())?
+    let files_to_delete: Vec<(String, Option<String>, Option<String>)> = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
         .filter_map(Result::ok)
         .collect();
 
     // 2. Delete files from disk
-    for rel_path in files_to_delete {
-        let abs_path = root.join(rel_path);
+    let mut thumbs = vec![];
+    let mut originals = vec![];
+    for (rel_path, thumb_rel, fa_original_rel) in files_to_delete {
+        let abs_path = root.join(&rel_path);
         if abs_path.exists() {
             let _ = std::fs::remove_file(abs_path);
         }
+        originals.extend(fa_original_rel);
+        thumbs.push(thumb_rel.unwrap_or_else(|| legacy_thumb_rel(&rel_path)));
     }
 
     // 3. Delete rows from DB
     conn.execute(
         "DELETE FROM items WHERE trashed_at < datetime('now', '-30 days') AND trashed_at IS NOT NULL",
         []
-    ).map_err(|e| e.to_string())?;
+    )?;
+
+    // kept FA originals go once their rows are gone
+    for original in originals {
+        if let Ok(path) = library::resolve_in_root(&root, &original) {
+            let _ = fs::remove_file(path);
+        }
+    }
+    drop_unreferenced_thumbs(&root, &conn, thumbs)?;
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn reveal_in_explorer(app: tauri::AppHandle, item_id: i64) -> Result<(), AppError> {
+    let root = get_root(&app)?;
+    let conn = pooled_conn(&app, &root)?;
+
+    let file_rel: String = conn.query_row(
+        "SELECT file_rel FROM items WHERE item_id = ?",
+        [item_id],
+        |row| row.get(0),
+    )?;
+
+    // Never hand a path outside the library to the shell
+    let path = library::resolve_in_root(&root, &file_rel)?;
+    if !path.exists() {
+        return Err(AppError::NotFound(format!("File not found: {}", path.display())));
+    }
+
+    let cmd = if cfg!(target_os = "windows") {
+        // explorer doesn't understand the \\?\ prefix canonicalize() adds
+        let display = path.to_string_lossy().trim_start_matches(r"\\?\").to_string();
+        app.shell().command("explorer").arg(format!("/select,{display}"))
+    } else if cfg!(target_os = "macos") {
+        app.shell().command("open").arg("-R").arg(&path)
+    } else {
+        let parent = path.parent().ok_or("File has no parent folder")?;
+        app.shell().command("xdg-open").arg(parent)
+    };
+
+    cmd.spawn().map_err(|e| AppError::Other(e.to_string()))?;
+    Ok(())
+}
+
+// Canonical page for the item on the site it came from
+fn canonical_source_url(source: &str, source_id: &str) -> Option<String> {
+    match source {
+        "e621" => Some(format!("https://e621.net/posts/{}", source_id)),
+        "furaffinity" => Some(format!("https://www.furaffinity.net/view/{}/", source_id)),
+        _ => None,
+    }
+}
 
+#[tauri::command]
+pub fn open_source_page(app: tauri::AppHandle, item_id: i64) -> Result<(), AppError> {
+    let root = get_root(&app)?;
+    let conn = pooled_conn(&app, &root)?;
+
+    let (source, source_id): (String, String) = conn.query_row(
+        "SELECT source, source_id FROM items WHERE item_id = ?",
+        [item_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+
+    // manual/imported items: fall back to the first recorded source URL
+    let url = match canonical_source_url(&source, &source_id) {
+        Some(u) => u,
+        None => conn.query_row(
+            r#"
+            SELECT s.url FROM item_sources isrc
+            JOIN sources s ON isrc.source_row_id = s.source_row_id
+            WHERE isrc.item_id = ?
+            ORDER BY isrc.rowid ASC
+            LIMIT 1
+            "#,
+            [item_id],
+            |row| row.get(0),
+        ).optional()?
+        .ok_or_else(|| AppError::NotFound("Item has no source page".into()))?,
+    };
+
+    if !(url.starts_with("https://") || url.starts_with("http://")) {
+        return Err(AppError::InvalidInput(format!("Not a web URL: {}", url)));
+    }
+
+    app.opener().open_url(&url, None::<&str>).map_err(|e| AppError::Other(e.to_string()))?;
     Ok(())
-}
\ No newline at end of file
+}
+
+#[derive(Serialize)]
+pub struct SourceCount {
+    pub source: String,
+    pub count: u32,
+}
+
+#[tauri::command]
+pub fn list_sources(app: tauri::AppHandle) -> Result<Vec<SourceCount>, AppError> {
+    let root = get_root(&app)?;
+    let conn = pooled_conn(&app, &root)?;
+
+    let mut stmt = conn.prepare(
+        r#"
+        SELECT source, SUM(trashed_at IS NULL) AS active
+        FROM items
+        GROUP BY source
+        ORDER BY active DESC, source ASC
+        "#
+    )?;
+
+    let rows = stmt.query_map([], |r| {
+        Ok(SourceCount {
+            source: r.get(0)?,
+            count: r.get(1)?,
+        })
+    })?;
+
+    let mut out = vec![];
+    for row in rows {
+        out.push(row?);
+    }
+    Ok(out)
+}
+
+// Drops the cached thumb for one file so the next ensure_thumbnail regenerates it
+pub fn remove_cached_thumb(root: &std::path::Path, file_rel: &str) -> bool {
+    std::fs::remove_file(thumb_path_for(root, file_rel)).is_ok()
+}
+
+// Drop an item's cached thumbnail, wherever it is recorded, and forget it on
+// every row that shared it
+fn remove_item_thumb(root: &std::path::Path, conn: &Connection, item_id: i64) -> Result<bool, AppError> {
+    let (file_rel, thumb_rel): (String, Option<String>) = conn.query_row(
+        "SELECT file_rel, thumb_rel FROM items WHERE item_id = ?",
+        [item_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    let mut removed = remove_cached_thumb(root, &file_rel);
+    if let Some(thumb_rel) = thumb_rel {
+        removed |= std::fs::remove_file(root.join(&thumb_rel)).is_ok();
+        conn.execute("UPDATE items SET thumb_rel = NULL, thumb_color = NULL WHERE thumb_rel = ?", [&thumb_rel])?;
+    }
+    Ok(removed)
+}
+
+#[tauri::command]
+pub fn clear_thumbnail_cache(app: tauri::AppHandle, item_id: Option<i64>) -> Result<u32, AppError> {
+    let root = get_root(&app)?;
+
+    let conn = open_conn_for_root(&app, &root)?;
+    if let Some(id) = item_id {
+        return Ok(remove_item_thumb(&root, &conn, id)? as u32);
+    }
+
+    conn.execute("UPDATE items SET thumb_rel = NULL, thumb_color = NULL", [])?;
+    let cache_dir = root.join(".cache").join("thumbs");
+    if !cache_dir.exists() {
+        return Ok(0);
+    }
+
+    let mut removed = 0;
+    for entry in std::fs::read_dir(&cache_dir)? {
+        let path = entry?.path();
+        if path.is_file() && std::fs::remove_file(&path).is_ok() {
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+#[derive(Serialize)]
+pub struct SyncBatchDto {
+    pub batch: String,
+    pub count: u32,
+    pub first_added: String,
+    pub last_added: String,
+}
+
+#[tauri::command]
+pub fn list_sync_batches(app: tauri::AppHandle) -> Result<Vec<SyncBatchDto>, AppError> {
+    let root = get_root(&app)?;
+    let conn = open_conn_for_root(&app, &root)?;
+
+    let mut stmt = conn.prepare(
+        r#"
+        SELECT sync_batch, COUNT(*), MIN(added_at), MAX(added_at)
+        FROM items
+        WHERE sync_batch IS NOT NULL AND trashed_at IS NULL
+        GROUP BY sync_batch
+        ORDER BY MAX(added_at) DESC
+        "#
+    )?;
+
+    let rows = stmt.query_map([], |r| {
+        Ok(SyncBatchDto {
+            batch: r.get(0)?,
+            count: r.get(1)?,
+            first_added: r.get(2)?,
+            last_added: r.get(3)?,
+        })
+    })?;
+
+    let mut out = vec![];
+    for row in rows {
+        out.push(row?);
+    }
+    Ok(out)
+}
+
+#[derive(Deserialize, Clone, Copy)]
+pub enum ExportMode {
+    Copy,
+    Hardlink,
+}
+
+#[tauri::command]
+pub fn export_items(app: tauri::AppHandle, ids: Vec<i64>, dest: String, mode: ExportMode) -> Result<u32, AppError> {
+    let root = get_root(&app)?;
+    let conn = open_conn_for_root(&app, &root)?;
+    export_ids(&root, &conn, ids, dest, mode)
+}
+
+// Everything list_items would return for this filter, unpaginated
+#[tauri::command]
+pub fn export_search(
+    app: tauri::AppHandle,
+    search: Option<String>,
+    rating: Option<String>,
+    source: Option<String>,
+    order: Option<String>,
+    dest: String,
+    mode: ExportMode,
+) -> Result<u32, AppError> {
+    let root = get_root(&app)?;
+    let conn = open_conn_for_root(&app, &root)?;
+    let ids = search_item_ids(&conn, search, rating, source, order)?;
+    export_ids(&root, &conn, ids, dest, mode)
+}
+
+// Ids of the live items a list_items filter matches, in its order, unpaginated
+fn search_item_ids(
+    conn: &Connection,
+    search: Option<String>,
+    rating: Option<String>,
+    source: Option<String>,
+    order: Option<String>,
+) -> Result<Vec<i64>, AppError> {
+    let query = ItemQuery::build(conn, search, rating, source, order)?;
+    let sql = format!(
+        "SELECT i.item_id FROM items i WHERE i.trashed_at IS NULL{} ORDER BY {}",
+        query.where_sql, query.order_by
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(&*query.sql_params(), |r| r.get(0))?;
+    Ok(rows.collect::<Result<_, _>>()?)
+}
+
+fn export_ids(root: &std::path::Path, conn: &Connection, ids: Vec<i64>, dest: String, mode: ExportMode) -> Result<u32, AppError> {
+    let dest = PathBuf::from(dest);
+    fs::create_dir_all(&dest)?;
+
+    let mut exported = 0u32;
+    for id in ids {
+        let row: Option<(String, String, String, String, Option<String>)> = conn.query_row(
+            "SELECT source, source_id, file_rel, ext, primary_artist FROM items WHERE item_id = ?",
+            [id],
+            |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?, r.get(4)?))
+        ).optional()?;
+        let Some((source, source_id, file_rel, ext, artist)) = row else { continue };
+
+        let src = match library::resolve_in_root(root, &file_rel) {
+            Ok(p) if p.is_file() => p,
+            _ => {
+                log::warn!("export_items: skipping item {}: missing file {}", id, file_rel);
+                continue;
+            }
+        };
+
+        let artist = sanitize_slug(artist.as_deref().unwrap_or(""));
+        let target = dest.join(format!("{}_{}_{}.{}", artist, source, source_id, ext));
+        // Names are unique per source post, so an existing file is a previous export
+        if target.exists() {
+            continue;
+        }
+
+        // Hardlinks fail across volumes; fall back to a plain copy
+        let linked = matches!(mode, ExportMode::Hardlink) && fs::hard_link(&src, &target).is_ok();
+        if !linked {
+            if let Err(e) = fs::copy(&src, &target) {
+                log::warn!("export_items: skipping item {}: {}", id, e);
+                continue;
+            }
+        }
+        exported += 1;
+    }
+
+    Ok(exported)
+}
+
+// e621 rejects searches with more than 40 tags, and each id counts as one
+const E621_ID_CHUNK: usize = 40;
+// Pause between consecutive e621 API calls (their limit is 2/s)
+const E621_REQUEST_INTERVAL: std::time::Duration = std::time::Duration::from_millis(1000);
+
+#[tauri::command]
+pub async fn refresh_scores(app: tauri::AppHandle, limit: Option<u32>) -> Result<u32, AppError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let root = get_root(&app)?;
+        let conn = open_conn_for_root(&app, &root)?;
+        let (username, api_key) = load_e621_creds(&conn)?;
+
+        let ids: Vec<i64> = {
+            let mut stmt = conn.prepare(
+                "SELECT CAST(source_id AS INTEGER) FROM items WHERE source = 'e621' AND trashed_at IS NULL ORDER BY item_id LIMIT ?"
+            )?;
+            let rows = stmt.query_map([limit.map(i64::from).unwrap_or(-1)], |r| r.get(0))?;
+            rows.collect::<Result<_, _>>()?
+        };
+
+        let client = reqwest::blocking::Client::new();
+        let user_agent = e621_user_agent(&conn);
+        let posts_url = e621_api_url(&conn, "posts.json");
+        let mut refreshed = 0u32;
+
+        for (n, chunk) in ids.chunks(E621_ID_CHUNK).enumerate() {
+            if n > 0 {
+                std::thread::sleep(E621_REQUEST_INTERVAL);
+            }
+
+            let id_list = chunk.iter().map(|id| id.to_string()).collect::<Vec<_>>().join(",");
+            // status:any so posts deleted upstream still come back and get flagged
+            let tags = format!("id:{} status:any", id_list);
+            let limit = chunk.len().to_string();
+
+            let resp = send_with_retry(|| {
+                client
+                    .get(&posts_url)
+                    .basic_auth(&username, Some(&api_key))
+                    .header("User-Agent", &user_agent)
+                    .query(&[("tags", tags.as_str()), ("limit", limit.as_str())])
+            })?;
+            if !resp.status().is_success() {
+                return Err(e621_error("e621 refresh error", resp));
+            }
+
+            let json: serde_json::Value = resp.json()?;
+            let posts = json.get("posts").and_then(|p| p.as_array()).cloned().unwrap_or_default();
+
+            for p in posts {
+                let Some(id) = p.get("id").and_then(|x| x.as_i64()) else { continue };
+                let score = p.get("score");
+                let n = conn.execute(
+                    "UPDATE items SET fav_count = ?, score_total = ?, score_up = ?, score_down = ?, remote_status = ?, uploader_id = ?, approver_id = ? WHERE source = 'e621' AND source_id = ?",
+                    params![
+                        p.get("fav_count").and_then(|x| x.as_i64()),
+                        score.and_then(|s| s.get("total")).and_then(|x| x.as_i64()),
+                        score.and_then(|s| s.get("up")).and_then(|x| x.as_i64()),
+                        score.and_then(|s| s.get("down")).and_then(|x| x.as_i64()),
+                        e621_remote_status(p.get("flags")),
+                        p.get("uploader_id").and_then(|x| x.as_i64()),
+                        p.get("approver_id").and_then(|x| x.as_i64()),
+                        id.to_string(),
+                    ],
+                )?;
+                if n > 0 {
+                    refreshed += 1;
+                    let item_id: Option<i64> = conn.query_row(
+                        "SELECT item_id FROM items WHERE source = 'e621' AND source_id = ?",
+                        [id.to_string()],
+                        |r| r.get(0),
+                    ).optional()?;
+                    if let Some(item_id) = item_id {
+                        store_item_raw(&conn, item_id, &p)?;
+                    }
+                }
+            }
+        }
+
+        Ok(refreshed)
+    }).await?
+}
+
+// Streams the file so large videos don't have to fit in memory
+pub fn md5_file(path: &std::path::Path) -> Result<String, AppError> {
+    let mut file = fs::File::open(path)?;
+    let mut ctx = md5::Context::new();
+    std::io::copy(&mut file, &mut ctx)?;
+    Ok(format!("{:x}", ctx.compute()))
+}
+
+#[derive(Clone, Serialize)]
+pub struct TaskProgress {
+    pub done: u32,
+    pub total: u32,
+}
+
+// Older versions left md5 (and sometimes file_md5) empty, which lets duplicates
+// slip past dedupe. Hash what's on disk and fill whichever column is missing.
+#[tauri::command]
+pub async fn backfill_md5(app: tauri::AppHandle) -> Result<u32, AppError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let root = get_root(&app)?;
+        let conn = open_conn_for_root(&app, &root)?;
+
+        let rows: Vec<(i64, String)> = {
+            let mut stmt = conn.prepare(
+                "SELECT item_id, file_rel FROM items WHERE trashed_at IS NULL AND (md5 IS NULL OR file_md5 IS NULL)"
+            )?;
+            let rows = stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?)))?;
+            rows.collect::<Result<_, _>>()?
+        };
+
+        let total = rows.len() as u32;
+        let mut updated = 0u32;
+        for (n, (item_id, file_rel)) in rows.into_iter().enumerate() {
+            if let Ok(path) = library::resolve_in_root(&root, &file_rel) {
+                if let Ok(hash) = md5_file(&path) {
+                    // md5 is unique: when another row already holds this hash (the FA/e621
+                    // duplicate this is meant to surface) only file_md5 gets filled
+                    let res = conn.execute(
+                        r#"
+                        UPDATE items SET
+                          md5 = CASE WHEN md5 IS NULL AND NOT EXISTS (SELECT 1 FROM items WHERE md5 = ?1) THEN ?1 ELSE md5 END,
+                          file_md5 = COALESCE(file_md5, ?1)
+                        WHERE item_id = ?2
+                        "#,
+                        params![hash, item_id],
+                    );
+                    match res {
+                        Ok(_) => updated += 1,
+                        Err(e) => log::warn!("backfill_md5: item {} not updated: {}", item_id, e),
+                    }
+                }
+            }
+
+            let done = n as u32 + 1;
+            if done.is_multiple_of(25) || done == total {
+                let _ = app.emit("backfill-progress", TaskProgress { done, total });
+            }
+        }
+
+        Ok(updated)
+    }).await?
+}
+
+// One JSON object per line, written row by row so memory stays flat on big libraries
+#[tauri::command]
+pub async fn export_jsonl(app: tauri::AppHandle, dest: String) -> Result<u32, AppError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let root = get_root(&app)?;
+        let conn = open_conn_for_root(&app, &root)?;
+
+        let total: u32 = conn.query_row("SELECT COUNT(*) FROM items WHERE trashed_at IS NULL", [], |r| r.get(0))?;
+        let mut out = std::io::BufWriter::new(fs::File::create(&dest)?);
+
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT
+              i.item_id, i.source, i.source_id, i.md5, i.file_rel, i.ext, i.mime, i.rating,
+              i.fav_count, i.score_total, i.score_up, i.score_down, i.created_at, i.added_at,
+              i.primary_artist, i.width, i.height,
+              (SELECT GROUP_CONCAT(t.type || ':' || t.name, char(9)) FROM item_tags it JOIN tags t ON it.tag_id = t.tag_id WHERE it.item_id = i.item_id),
+              (SELECT GROUP_CONCAT(s.url, char(9)) FROM item_sources isrc JOIN sources s ON isrc.source_row_id = s.source_row_id WHERE isrc.item_id = i.item_id)
+            FROM items i
+            WHERE i.trashed_at IS NULL
+            ORDER BY i.item_id
+            "#
+        )?;
+        let mut rows = stmt.query([])?;
+
+        let mut written = 0u32;
+        while let Some(r) = rows.next()? {
+            // "type:name" pairs -> { "artist": [...], "general": [...], ... }
+            let mut tags: std::collections::BTreeMap<String, Vec<String>> = Default::default();
+            let tag_str: Option<String> = r.get(17)?;
+            for pair in tag_str.as_deref().unwrap_or("").split('\t').filter(|p| !p.is_empty()) {
+                let (tag_type, name) = pair.split_once(':').unwrap_or(("general", pair));
+                tags.entry(tag_type.to_string()).or_default().push(name.to_string());
+            }
+            let sources: Vec<String> = r.get::<_, Option<String>>(18)?
+                .map(|s| s.split('\t').map(|x| x.to_string()).collect())
+                .unwrap_or_default();
+
+            let obj = serde_json::json!({
+                "item_id": r.get::<_, i64>(0)?,
+                "source": r.get::<_, String>(1)?,
+                "source_id": r.get::<_, String>(2)?,
+                "md5": r.get::<_, Option<String>>(3)?,
+                "file_rel": r.get::<_, String>(4)?,
+                "ext": r.get::<_, Option<String>>(5)?,
+                "mime": r.get::<_, Option<String>>(6)?,
+                "rating": r.get::<_, Option<String>>(7)?,
+                "fav_count": r.get::<_, Option<i64>>(8)?,
+                "score_total": r.get::<_, Option<i64>>(9)?,
+                "score_up": r.get::<_, Option<i64>>(10)?,
+                "score_down": r.get::<_, Option<i64>>(11)?,
+                "created_at": r.get::<_, Option<String>>(12)?,
+                "added_at": r.get::<_, String>(13)?,
+                "primary_artist": r.get::<_, Option<String>>(14)?,
+                "width": r.get::<_, Option<i64>>(15)?,
+                "height": r.get::<_, Option<i64>>(16)?,
+                "tags": tags,
+                "sources": sources,
+            });
+            serde_json::to_writer(&mut out, &obj)?;
+            out.write_all(b"\n")?;
+
+            written += 1;
+            if written.is_multiple_of(500) || written == total {
+                let _ = app.emit("export-progress", TaskProgress { done: written, total });
+            }
+        }
+
+        out.flush()?;
+        Ok(written)
+    }).await?
+}
+
+#[derive(Serialize)]
+pub struct DupGroup {
+    pub md5: String,
+    pub item_ids: Vec<i64>,
+}
+
+// Exact duplicates: live rows whose content hash matches. file_md5 is preferred
+// since older rows may only have one of the two columns filled.
+#[tauri::command]
+pub fn find_duplicate_items(app: tauri::AppHandle) -> Result<Vec<DupGroup>, AppError> {
+    let root = get_root(&app)?;
+    let conn = open_conn_for_root(&app, &root)?;
+
+    let mut stmt = conn.prepare(
+        r#"
+        SELECT COALESCE(file_md5, md5) AS h, GROUP_CONCAT(item_id)
+        FROM items
+        WHERE trashed_at IS NULL AND COALESCE(file_md5, md5) IS NOT NULL
+        GROUP BY h
+        HAVING COUNT(*) > 1
+        ORDER BY COUNT(*) DESC, h
+        "#
+    )?;
+
+    let rows = stmt.query_map([], |r| {
+        let ids: String = r.get(1)?;
+        Ok(DupGroup {
+            md5: r.get(0)?,
+            item_ids: ids.split(',').filter_map(|x| x.parse().ok()).collect(),
+        })
+    })?;
+
+    let mut out = vec![];
+    for row in rows {
+        out.push(row?);
+    }
+    Ok(out)
+}
+
+// Folds the tags and sources of `drop_ids` into `keep_id`, then deletes the dropped
+// rows and their files. Every dropped item must have the same content hash.
+#[tauri::command]
+pub fn merge_duplicate_items(app: tauri::AppHandle, keep_id: i64, drop_ids: Vec<i64>) -> Result<u32, AppError> {
+    let root = get_root(&app)?;
+    let mut conn = open_conn_for_root(&app, &root)?;
+
+    let hash_of = |conn: &Connection, id: i64| -> Result<Option<String>, AppError> {
+        Ok(conn.query_row("SELECT COALESCE(file_md5, md5) FROM items WHERE item_id = ?", [id], |r| r.get(0))?)
+    };
+    let keep_hash = hash_of(&conn, keep_id)?
+        .ok_or_else(|| AppError::InvalidInput("The kept item has no md5".into()))?;
+    let (keep_file, keep_original): (String, Option<String>) = conn.query_row(
+        "SELECT file_rel, fa_original_rel FROM items WHERE item_id = ?",
+        [keep_id],
+        |r| Ok((r.get(0)?, r.get(1)?)),
+    )?;
+
+    for id in &drop_ids {
+        if *id == keep_id || hash_of(&conn, *id)?.as_deref() != Some(keep_hash.as_str()) {
+            return Err(AppError::InvalidInput(format!("Item {} is not a duplicate of {}", id, keep_id)));
+        }
+    }
+
+    let tx = conn.transaction()?;
+    let mut files = vec![];
+    let mut thumbs = vec![];
+    for id in &drop_ids {
+        let (file_rel, thumb_rel, original): (String, Option<String>, Option<String>) = tx.query_row(
+            "SELECT file_rel, thumb_rel, fa_original_rel FROM items WHERE item_id = ?",
+            [id],
+            |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?))
+        )?;
+
+        tx.execute("INSERT OR IGNORE INTO item_tags (item_id, tag_id) SELECT ?1, tag_id FROM item_tags WHERE item_id = ?2", [keep_id, *id])?;
+        tx.execute("INSERT OR IGNORE INTO item_sources (item_id, source_row_id) SELECT ?1, source_row_id FROM item_sources WHERE item_id = ?2", [keep_id, *id])?;
+        tx.execute("DELETE FROM item_tags WHERE item_id = ?", [id])?;
+        tx.execute("DELETE FROM item_sources WHERE item_id = ?", [id])?;
+        tx.execute("UPDATE items SET notes = COALESCE(notes, (SELECT notes FROM items WHERE item_id = ?2)) WHERE item_id = ?1", [keep_id, *id])?;
+        tx.execute("DELETE FROM items WHERE item_id = ?", [id])?;
+
+        if file_rel != keep_file {
+            files.push(file_rel.clone());
+        }
+        files.extend(original.filter(|o| Some(o) != keep_original.as_ref()));
+        thumbs.push(thumb_rel.unwrap_or_else(|| legacy_thumb_rel(&file_rel)));
+    }
+    db::reindex_item(&tx, keep_id)?;
+    tx.commit()?;
+
+    // Files go only after the rows are gone, so a failed merge never loses data
+    for file_rel in files {
+        if let Ok(path) = library::resolve_in_root(&root, &file_rel) {
+            let _ = fs::remove_file(path);
+        }
+    }
+    drop_unreferenced_thumbs(&root, &conn, thumbs)?;
+
+    Ok(drop_ids.len() as u32)
+}
+
+#[derive(Serialize, Default)]
+pub struct HealthReport {
+    pub root_configured: bool,
+    pub root_exists: bool,
+    pub db_opens: bool,
+    pub integrity_ok: bool,
+    // first line of PRAGMA integrity_check when it isn't "ok", or the open error
+    pub db_error: Option<String>,
+    pub wal_bytes: u64,
+    pub item_count: u32,
+    pub trash_count: u32,
+    pub orphan_files: u32,
+    pub has_e621_creds: bool,
+    pub has_fa_creds: bool,
+}
+
+// Library-relative paths of every file under `dir` (recursively)
+fn collect_files(root: &std::path::Path, dir: &std::path::Path, out: &mut Vec<String>) {
+    let Ok(entries) = fs::read_dir(dir) else { return };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_files(root, &path, out);
+        } else if let Ok(rel) = path.strip_prefix(root) {
+            out.push(rel.to_string_lossy().replace('\\', "/"));
+        }
+    }
+}
+
+// Everything the UI needs for a diagnostics panel in one call. Problems are
+// reported as fields, not errors, so a broken library still gets a report.
+#[tauri::command]
+pub async fn library_health(app: tauri::AppHandle) -> Result<HealthReport, AppError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut report = HealthReport {
+            has_fa_creds: app.path().app_config_dir()?.join("fa_creds.json").exists(),
+            ..Default::default()
+        };
+
+        let root = match configured_root(&app) {
+            Ok(r) => r,
+            Err(_) => return Ok(report),
+        };
+        report.root_configured = true;
+        report.root_exists = root.is_dir();
+        if !report.root_exists {
+            return Ok(report);
+        }
+
+        let db_path = library::db_path(&root);
+        report.wal_bytes = fs::metadata(db_path.with_extension("sqlite-wal")).map(|m| m.len()).unwrap_or(0);
+
+        let conn = match db::open(&db_path) {
+            Ok(c) => c,
+            Err(e) => {
+                report.db_error = Some(e.to_string());
+                return Ok(report);
+            }
+        };
+        report.db_opens = true;
+
+        let integrity: String = conn.query_row("PRAGMA integrity_check", [], |r| r.get(0))?;
+        report.integrity_ok = integrity == "ok";
+        if !report.integrity_ok {
+            report.db_error = Some(integrity);
+        }
+
+        report.item_count = conn.query_row("SELECT COUNT(*) FROM items WHERE trashed_at IS NULL", [], |r| r.get(0))?;
+        report.trash_count = conn.query_row("SELECT COUNT(*) FROM items WHERE trashed_at IS NOT NULL", [], |r| r.get(0))?;
+        report.has_e621_creds = load_e621_creds(&conn).is_ok();
+
+        let known: std::collections::HashSet<String> = {
+            let mut stmt = conn.prepare("SELECT file_rel FROM items")?;
+            let rows = stmt.query_map([], |r| r.get(0))?;
+            rows.collect::<Result<_, _>>()?
+        };
+        let mut files = vec![];
+        collect_files(&root, &root.join("media"), &mut files);
+        collect_files(&root, &root.join(".trash").join("media"), &mut files);
+        report.orphan_files = files.iter().filter(|f| !known.contains(*f)).count() as u32;
+
+        Ok(report)
+    }).await?
+}
+
+#[derive(Serialize)]
+pub struct FavDiff {
+    // favorited on e621 but never downloaded
+    pub only_remote: Vec<i64>,
+    // still in the library but no longer favorited
+    pub only_local: Vec<i64>,
+}
+
+#[tauri::command]
+pub async fn e621_favorites_diff(app: tauri::AppHandle) -> Result<FavDiff, AppError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let root = get_root(&app)?;
+        let conn = open_conn_for_root(&app, &root)?;
+        let (username, api_key) = load_e621_creds(&conn)?;
+        let user_agent = e621_user_agent(&conn);
+        let posts_url = e621_api_url(&conn, "posts.json");
+        let delay = std::time::Duration::from_millis(load_sync_pacing(&conn).e621_delay_ms);
+
+        let client = reqwest::blocking::Client::new();
+        let tags = format!("fav:{} order:id_desc", username);
+        let mut remote = std::collections::BTreeSet::new();
+        let mut before_id: Option<i64> = None;
+
+        loop {
+            let page = before_id.map(|id| format!("b{id}")).unwrap_or_else(|| "1".into());
+            let resp = send_with_retry(|| {
+                client
+                    .get(&posts_url)
+                    .basic_auth(&username, Some(&api_key))
+                    .header("User-Agent", &user_agent)
+                    .query(&[("tags", tags.as_str()), ("limit", "320"), ("page", page.as_str())])
+            })?;
+            if !resp.status().is_success() {
+                return Err(e621_error("e621 favorites error", resp));
+            }
+
+            let json: serde_json::Value = resp.json()?;
+            let ids: Vec<i64> = json.get("posts")
+                .and_then(|p| p.as_array())
+                .map(|a| a.iter().filter_map(|p| p.get("id").and_then(|x| x.as_i64())).collect())
+                .unwrap_or_default();
+            match ids.iter().min() {
+                Some(lowest) => before_id = Some(*lowest),
+                None => break,
+            }
+            remote.extend(ids);
+            std::thread::sleep(delay);
+        }
+
+        // trashed rows count as downloaded (they were removed on purpose) but
+        // aren't reported as local leftovers
+        let mut local_all = std::collections::BTreeSet::new();
+        let mut local_live = std::collections::BTreeSet::new();
+        {
+            let mut stmt = conn.prepare("SELECT CAST(source_id AS INTEGER), trashed_at IS NULL FROM items WHERE source = 'e621'")?;
+            let rows = stmt.query_map([], |r| Ok((r.get::<_, i64>(0)?, r.get::<_, bool>(1)?)))?;
+            for row in rows {
+                let (id, live) = row?;
+                local_all.insert(id);
+                if live {
+                    local_live.insert(id);
+                }
+            }
+        }
+
+        Ok(FavDiff {
+            only_remote: remote.difference(&local_all).copied().collect(),
+            only_local: local_live.difference(&remote).copied().collect(),
+        })
+    }).await?
+}
+
+#[tauri::command]
+pub fn cancel_maintenance(state: tauri::State<'_, MaintenanceState>) {
+    state.cancel.cancel();
+}
+
+// Wipes the thumbnail cache and renders every live still/animated image again at
+// the current thumb_width. Videos have no thumbnails and are skipped.
+#[tauri::command]
+pub async fn rebuild_thumbnails(app: tauri::AppHandle) -> Result<u32, AppError> {
+    let cancel = app.state::<MaintenanceState>().cancel.clone();
+    cancel.reset();
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let root = get_root(&app)?;
+        let conn = open_conn_for_root(&app, &root)?;
+        let width = thumb_width(&conn);
+
+        let cache_dir = root.join(".cache").join("thumbs");
+        if cache_dir.exists() {
+            for entry in fs::read_dir(&cache_dir)? {
+                let path = entry?.path();
+                if path.is_file() {
+                    let _ = fs::remove_file(&path);
+                }
+            }
+        }
+        conn.execute("UPDATE items SET thumb_rel = NULL, thumb_color = NULL", [])?;
+
+        let rows: Vec<(i64, String, Option<String>)> = {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT i.item_id, i.file_rel, COALESCE(i.file_md5, i.md5) FROM items i WHERE i.trashed_at IS NULL AND NOT {} AND LOWER(i.ext) NOT IN ({}) ORDER BY i.added_at DESC",
+                media_type_clause("video").unwrap_or("0"),
+                UNSUPPORTED_EXTS.iter().map(|e| format!("'{e}'")).collect::<Vec<_>>().join(", ")
+            ))?;
+            let rows = stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))?;
+            rows.collect::<Result<_, _>>()?
+        };
+
+        let total = rows.len() as u32;
+        let mut rebuilt = 0u32;
+        for (n, (item_id, file_rel, md5)) in rows.into_iter().enumerate() {
+            if cancel.is_cancelled() {
+                break;
+            }
+
+            let thumb_rel = thumb_rel_for(md5.as_deref(), &file_rel);
+            let thumb_path = root.join(&thumb_rel);
+            // identical files share one thumbnail; only the first one renders it
+            let ok = thumb_path.exists()
+                || library::resolve_in_root(&root, &file_rel)
+                    .map(|src| render_thumbnail(&src, &thumb_path, width).is_ok())
+                    .unwrap_or(false);
+            if ok {
+                conn.execute("UPDATE items SET thumb_rel = ?, thumb_color = NULL WHERE item_id = ?", params![thumb_rel, item_id])?;
+                rebuilt += 1;
+            }
+
+            let done = n as u32 + 1;
+            if done.is_multiple_of(25) || done == total {
+                let _ = app.emit("thumbnail-progress", TaskProgress { done, total });
+            }
+        }
+
+        Ok(rebuilt)
+    }).await?
+}
+
+// Settings that make up a portable profile. Credentials (e621_username,
+// e621_api_key), e621_base_url (the host those credentials are sent to) and
+// per-library state (last sync times/summaries) never leave the DB, and import
+// ignores anything not listed here.
+const PROFILE_KEYS: &[&str] = &[
+    "auto_sync_interval_hours",
+    "e621_concurrency",
+    "e621_delay_ms",
+    "expand_implications",
+    "fa_delay_ms",
+    "filename_template",
+    "min_free_mb",
+    "presync_head_check",
+    "redownload_trashed",
+    "sfw_mode",
+    "keep_fa_original",
+    "prefer_sample",
+    "skip_unsupported_formats",
+    "thumb_width",
+    "user_agent_contact",
+];
+
+#[derive(Serialize, Deserialize)]
+pub struct SettingsProfile {
+    pub version: u32,
+    pub settings: std::collections::BTreeMap<String, String>,
+}
+
+#[tauri::command]
+pub fn export_settings(app: tauri::AppHandle, dest: String) -> Result<u32, AppError> {
+    let root = get_root(&app)?;
+    let conn = open_conn_for_root(&app, &root)?;
+
+    let mut settings = std::collections::BTreeMap::new();
+    for key in PROFILE_KEYS {
+        if let Some(value) = settings_get(&conn, key)? {
+            settings.insert(key.to_string(), value);
+        }
+    }
+
+    let count = settings.len() as u32;
+    let profile = SettingsProfile { version: 1, settings };
+    fs::write(&dest, serde_json::to_string_pretty(&profile)?)?;
+    Ok(count)
+}
+
+#[tauri::command]
+pub fn import_settings(app: tauri::AppHandle, src: String) -> Result<u32, AppError> {
+    let root = get_root(&app)?;
+    let conn = open_conn_for_root(&app, &root)?;
+
+    let profile: SettingsProfile = serde_json::from_str(&fs::read_to_string(&src)?)
+        .map_err(|e| AppError::InvalidInput(format!("Not a settings profile: {}", e)))?;
+
+    let mut imported = 0u32;
+    for (key, value) in profile.settings {
+        if PROFILE_KEYS.contains(&key.as_str()) {
+            settings_set(&conn, &key, &value)?;
+            imported += 1;
+        }
+    }
+    Ok(imported)
+}
+
+#[derive(Serialize, Default)]
+pub struct Neighbors {
+    pub prev_id: Option<i64>,
+    pub prev_file_abs: Option<String>,
+    pub next_id: Option<i64>,
+    pub next_file_abs: Option<String>,
+}
+
+// Prev/next of an item within the same filter and order as list_items.
+// Random order has no stable neighbors, and an item outside the filter has none.
+#[tauri::command]
+pub fn item_neighbors(
+    app: tauri::AppHandle,
+    item_id: i64,
+    search: Option<String>,
+    rating: Option<String>,
+    source: Option<String>,
+    order: Option<String>,
+) -> Result<Neighbors, AppError> {
+    let root = get_root(&app)?;
+    let conn = pooled_conn(&app, &root)?;
+
+    let query = ItemQuery::build(&conn, search, rating, source, order)?;
+    if query.random {
+        return Ok(Neighbors::default());
+    }
+
+    let sql = format!(
+        "SELECT prev_id, next_id FROM (
+            SELECT i.item_id,
+                   LAG(i.item_id) OVER (ORDER BY {order}) AS prev_id,
+                   LEAD(i.item_id) OVER (ORDER BY {order}) AS next_id
+            FROM items i
+            WHERE i.trashed_at IS NULL{filter}
+         ) WHERE item_id = ?{n}",
+        order = query.order_by,
+        filter = query.where_sql,
+        n = query.params.len() + 1,
+    );
+
+    let mut db_params = query.sql_params();
+    db_params.push(&item_id);
+    let found: Option<(Option<i64>, Option<i64>)> = conn
+        .query_row(&sql, &*db_params, |r| Ok((r.get(0)?, r.get(1)?)))
+        .optional()?;
+
+    let Some((prev_id, next_id)) = found else {
+        return Ok(Neighbors::default());
+    };
+
+    let file_abs = |id: Option<i64>| -> Result<Option<String>, AppError> {
+        let Some(id) = id else { return Ok(None) };
+        let rel: String = conn.query_row("SELECT file_rel FROM items WHERE item_id = ?", [id], |r| r.get(0))?;
+        Ok(Some(root.join(rel).to_string_lossy().to_string()))
+    };
+
+    Ok(Neighbors {
+        prev_file_abs: file_abs(prev_id)?,
+        prev_id,
+        next_file_abs: file_abs(next_id)?,
+        next_id,
+    })
+}
+
+// Everything by one artist, matched on an artist-typed tag or the primary_artist
+// column. Soft-trashes by default; `permanent` deletes rows and files outright.
+#[tauri::command]
+pub fn trash_by_artist(app: tauri::AppHandle, artist: String, permanent: Option<bool>) -> Result<u32, AppError> {
+    let root = get_root(&app)?;
+    let conn = open_conn_for_root(&app, &root)?;
+
+    let tag = artist.trim().to_lowercase().replace(' ', "_");
+    if tag.is_empty() {
+        return Err(AppError::InvalidInput("Artist name is empty".into()));
+    }
+
+    let items: Vec<(i64, String, Option<String>)> = {
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT i.item_id, i.file_rel, i.thumb_rel FROM items i
+            WHERE i.trashed_at IS NULL
+              AND (i.primary_artist = ?1
+                   OR EXISTS (SELECT 1 FROM item_tags it JOIN tags t ON it.tag_id = t.tag_id
+                              WHERE it.item_id = i.item_id AND t.type = 'artist' AND t.name = ?1))
+            "#,
+        )?;
+        let rows = stmt.query_map([&tag], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))?;
+        rows.collect::<Result<_, _>>()?
+    };
+    if items.is_empty() {
+        return Ok(0);
+    }
+
+    if permanent.unwrap_or(false) {
+        let tx = conn.unchecked_transaction()?;
+        let mut files = vec![];
+        for (id, _, _) in &items {
+            let original: Option<String> = tx.query_row("SELECT fa_original_rel FROM items WHERE item_id = ?", [id], |r| r.get(0))?;
+            files.extend(original);
+            tx.execute("DELETE FROM items WHERE item_id = ?", [id])?;
+        }
+        tx.commit()?;
+
+        // Rows are gone, so files go only after the commit
+        let mut thumbs = vec![];
+        for (_, file_rel, thumb_rel) in &items {
+            files.push(file_rel.clone());
+            thumbs.push(thumb_rel.clone().unwrap_or_else(|| legacy_thumb_rel(file_rel)));
+        }
+        for rel in files {
+            if let Ok(path) = library::resolve_in_root(&root, &rel) {
+                let _ = fs::remove_file(path);
+            }
+        }
+        drop_unreferenced_thumbs(&root, &conn, thumbs)?;
+
+        // same event as the trash branch: either way the items leave the grid
+        let ids: Vec<i64> = items.iter().map(|(id, _, _)| *id).collect();
+        let _ = app.emit("item-trashed", &ids);
+        return Ok(ids.len() as u32);
+    }
+
+    let now = chrono::Local::now().to_rfc3339();
+    let files: Vec<(i64, String)> = items.iter().map(|(id, file_rel, _)| (*id, file_rel.clone())).collect();
+    relocate_batch(&root, &conn, &files, library::trash_rel, |tx, id| {
+        tx.execute("UPDATE items SET trashed_at = ? WHERE item_id = ?", params![now, id])?;
+        Ok(())
+    })?;
+
+    let ids: Vec<i64> = items.iter().map(|(id, _, _)| *id).collect();
+    let _ = app.emit("item-trashed", &ids);
+    Ok(ids.len() as u32)
+}
+
+#[derive(Serialize)]
+pub struct TagCount {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub tag_type: String,
+    pub count: u32,
+}
+
+// Tags that most often share a live item with `tag` (the tag itself excluded)
+#[tauri::command]
+pub fn related_tags(app: tauri::AppHandle, tag: String, limit: Option<u32>) -> Result<Vec<TagCount>, AppError> {
+    let root = get_root(&app)?;
+    let conn = pooled_conn(&app, &root)?;
+
+    let tag = tag.trim().to_lowercase();
+    let sfw = if sfw_mode(&conn)? { " AND i.rating = 's'" } else { "" };
+
+    let mut stmt = conn.prepare(&format!(
+        r#"
+        SELECT t.name, t.type, COUNT(*) AS n
+        FROM item_tags base
+        JOIN tags bt ON bt.tag_id = base.tag_id
+        JOIN items i ON i.item_id = base.item_id
+        JOIN item_tags it ON it.item_id = base.item_id AND it.tag_id != base.tag_id
+        JOIN tags t ON t.tag_id = it.tag_id
+        WHERE bt.name = ?1 AND i.trashed_at IS NULL{sfw}
+        GROUP BY t.tag_id
+        ORDER BY n DESC, t.name ASC
+        LIMIT ?2
+        "#
+    ))?;
+
+    let rows = stmt.query_map(params![tag, limit.unwrap_or(25)], |r| {
+        Ok(TagCount {
+            name: r.get(0)?,
+            tag_type: r.get(1)?,
+            count: r.get(2)?,
+        })
+    })?;
+
+    let mut out = vec![];
+    for row in rows {
+        out.push(row?);
+    }
+    Ok(out)
+}
+
+// Rows imported before size_bytes was recorded get it filled from disk. Cheap
+// (one stat per row) and a no-op once every row has a size; run at startup.
+pub fn backfill_sizes(root: &std::path::Path, conn: &Connection) -> Result<u32, AppError> {
+    let missing: Vec<(i64, String)> = {
+        let mut stmt = conn.prepare("SELECT item_id, file_rel FROM items WHERE size_bytes IS NULL")?;
+        let rows = stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?)))?;
+        rows.collect::<Result<_, _>>()?
+    };
+    if missing.is_empty() {
+        return Ok(0);
+    }
+
+    let tx = conn.unchecked_transaction()?;
+    let mut filled = 0u32;
+    for (id, rel) in missing {
+        if let Some(meta) = library::resolve_in_root(root, &rel).ok().and_then(|p| fs::metadata(p).ok()) {
+            tx.execute("UPDATE items SET size_bytes = ? WHERE item_id = ?", params![meta.len() as i64, id])?;
+            filled += 1;
+        }
+    }
+    tx.commit()?;
+    Ok(filled)
+}
+
+pub fn spawn_size_backfill(app: AppHandle) {
+    std::thread::spawn(move || {
+        let result = get_root(&app)
+            .and_then(|root| open_conn_for_root(&app, &root).and_then(|conn| backfill_sizes(&root, &conn)));
+        match result {
+            Ok(0) => {}
+            Ok(n) => log::info!("Filled in size_bytes for {} items", n),
+            Err(e) => log::warn!("size_bytes backfill failed: {}", e),
+        }
+    });
+}
+
+#[derive(Serialize, Default)]
+pub struct Counts {
+    pub active_items: u32,
+    pub trashed_items: u32,
+    pub tags: u32,
+    pub artists: u32,
+    pub sources: u32,
+    pub media_bytes: u64,
+    pub trash_bytes: u64,
+    // rows with no size_bytes yet, left out of the byte totals
+    pub unknown_size_items: u32,
+}
+
+// Everything the stats dashboard shows, from one connection. Tag/artist/source
+// counts only include what live items still reference. Rows imported before
+// size_bytes was recorded are left out of the byte totals and counted in
+// unknown_size_items until backfill_sizes reaches them.
+#[tauri::command]
+pub fn library_counts(app: tauri::AppHandle) -> Result<Counts, AppError> {
+    let root = get_root(&app)?;
+    let conn = pooled_conn(&app, &root)?;
+
+    let mut counts = Counts::default();
+    (counts.active_items, counts.trashed_items, counts.media_bytes, counts.trash_bytes, counts.unknown_size_items) = conn.query_row(
+        r#"
+        SELECT SUM(trashed_at IS NULL), SUM(trashed_at IS NOT NULL),
+               COALESCE(SUM(CASE WHEN trashed_at IS NULL THEN size_bytes END), 0),
+               COALESCE(SUM(CASE WHEN trashed_at IS NOT NULL THEN size_bytes END), 0),
+               SUM(size_bytes IS NULL)
+        FROM items
+        "#,
+        [],
+        |r| Ok((
+            r.get::<_, Option<u32>>(0)?.unwrap_or(0),
+            r.get::<_, Option<u32>>(1)?.unwrap_or(0),
+            r.get::<_, i64>(2)? as u64,
+            r.get::<_, i64>(3)? as u64,
+            r.get::<_, Option<u32>>(4)?.unwrap_or(0),
+        )),
+    )?;
+    (counts.tags, counts.artists) = conn.query_row(
+        r#"
+        SELECT COUNT(DISTINCT t.tag_id), COUNT(DISTINCT CASE WHEN t.type = 'artist' THEN t.tag_id END)
+        FROM item_tags it
+        JOIN tags t ON t.tag_id = it.tag_id
+        JOIN items i ON i.item_id = it.item_id
+        WHERE i.trashed_at IS NULL
+        "#,
+        [],
+        |r| Ok((r.get(0)?, r.get(1)?)),
+    )?;
+    counts.sources = conn.query_row(
+        r#"
+        SELECT COUNT(DISTINCT isrc.source_row_id)
+        FROM item_sources isrc
+        JOIN items i ON i.item_id = isrc.item_id
+        WHERE i.trashed_at IS NULL
+        "#,
+        [],
+        |r| r.get(0),
+    )?;
+
+    Ok(counts)
+}
+
+// Live items whose file isn't on disk: a stat per row, no hashing. Tags and
+// sources are only gathered for the rows that turn out to be missing.
+#[tauri::command]
+pub async fn list_missing_files(app: tauri::AppHandle) -> Result<Vec<ItemDto>, AppError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let root = get_root(&app)?;
+        let conn = pooled_conn(&app, &root)?;
+
+        let missing: Vec<i64> = {
+            let mut stmt = conn.prepare("SELECT item_id, file_rel FROM items WHERE trashed_at IS NULL ORDER BY added_at DESC")?;
+            let rows = stmt.query_map([], |r| Ok((r.get::<_, i64>(0)?, r.get::<_, String>(1)?)))?;
+            let mut ids = vec![];
+            for row in rows {
+                let (id, rel) = row?;
+                if !root.join(&rel).exists() {
+                    ids.push(id);
+                }
+            }
+            ids
+        };
+
+        let mut stmt = conn.prepare(&format!("SELECT {ITEM_DTO_COLUMNS} FROM items i WHERE i.item_id = ?"))?;
+        let mut out = vec![];
+        for id in missing {
+            out.push(stmt.query_row([id], |r| item_dto_from_row(&root, r))?);
+        }
+        Ok(out)
+    }).await?
+}
+
+#[tauri::command]
+pub fn get_log_path(app: tauri::AppHandle) -> Result<String, AppError> {
+    let root = get_root(&app)?;
+    Ok(crate::logging::log_path(&root).to_string_lossy().to_string())
+}
+
+#[tauri::command]
+pub fn get_item(app: tauri::AppHandle, item_id: i64) -> Result<ItemDto, AppError> {
+    let root = get_root(&app)?;
+    let conn = open_conn_for_root(&app, &root)?;
+    conn.query_row(
+        &format!("SELECT {ITEM_DTO_COLUMNS} FROM items i WHERE i.item_id = ?"),
+        [item_id],
+        |r| item_dto_from_row(&root, r),
+    )
+    .optional()?
+    .ok_or_else(|| AppError::NotFound(format!("Item {} not found", item_id)))
+}
+
+// Blank notes clear the field
+#[tauri::command]
+pub fn set_item_notes(app: tauri::AppHandle, item_id: i64, notes: String) -> Result<(), AppError> {
+    let root = get_root(&app)?;
+    let conn = open_conn_for_root(&app, &root)?;
+
+    let notes = notes.trim();
+    // items_fts_notes keeps the search index in step
+    let n = conn.execute(
+        "UPDATE items SET notes = ? WHERE item_id = ?",
+        params![if notes.is_empty() { None } else { Some(notes) }, item_id],
+    )?;
+    if n == 0 {
+        return Err(AppError::NotFound(format!("Item {} not found", item_id)));
+    }
+    Ok(())
+}
+
+#[derive(Serialize)]
+pub struct CollectionDto {
+    pub collection_id: i64,
+    pub name: String,
+    pub created_at: String,
+    pub item_count: u32,
+}
+
+fn collection_name(name: &str) -> Result<String, AppError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AppError::InvalidInput("Collection name is empty".into()));
+    }
+    Ok(name.to_string())
+}
+
+fn ensure_collection(conn: &Connection, collection_id: i64) -> Result<(), AppError> {
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM collections WHERE collection_id = ?)",
+        [collection_id],
+        |r| r.get(0),
+    )?;
+    if exists { Ok(()) } else { Err(AppError::NotFound(format!("Collection {} not found", collection_id))) }
+}
+
+#[tauri::command]
+pub fn list_collections(app: tauri::AppHandle) -> Result<Vec<CollectionDto>, AppError> {
+    let root = get_root(&app)?;
+    let conn = open_conn_for_root(&app, &root)?;
+
+    let mut stmt = conn.prepare(
+        r#"
+        SELECT c.collection_id, c.name, c.created_at,
+               (SELECT COUNT(*) FROM collection_items ci JOIN items i ON i.item_id = ci.item_id
+                WHERE ci.collection_id = c.collection_id AND i.trashed_at IS NULL)
+        FROM collections c
+        ORDER BY c.name COLLATE NOCASE
+        "#
+    )?;
+    let rows = stmt.query_map([], |r| {
+        Ok(CollectionDto {
+            collection_id: r.get(0)?,
+            name: r.get(1)?,
+            created_at: r.get(2)?,
+            item_count: r.get(3)?,
+        })
+    })?;
+
+    let mut out = vec![];
+    for row in rows {
+        out.push(row?);
+    }
+    Ok(out)
+}
+
+#[tauri::command]
+pub fn create_collection(app: tauri::AppHandle, name: String) -> Result<i64, AppError> {
+    let root = get_root(&app)?;
+    let conn = open_conn_for_root(&app, &root)?;
+    let name = collection_name(&name)?;
+
+    let inserted = conn.execute(
+        "INSERT OR IGNORE INTO collections (name, created_at) VALUES (?, ?)",
+        params![name, chrono::Local::now().to_rfc3339()],
+    )?;
+    if inserted == 0 {
+        return Err(AppError::Duplicate(format!("A collection named \"{}\" already exists", name)));
+    }
+    Ok(conn.last_insert_rowid())
+}
+
+#[tauri::command]
+pub fn rename_collection(app: tauri::AppHandle, collection_id: i64, name: String) -> Result<(), AppError> {
+    let root = get_root(&app)?;
+    let conn = open_conn_for_root(&app, &root)?;
+    let name = collection_name(&name)?;
+    ensure_collection(&conn, collection_id)?;
+
+    let taken: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM collections WHERE name = ? AND collection_id != ?)",
+        params![name, collection_id],
+        |r| r.get(0),
+    )?;
+    if taken {
+        return Err(AppError::Duplicate(format!("A collection named \"{}\" already exists", name)));
+    }
+    conn.execute("UPDATE collections SET name = ? WHERE collection_id = ?", params![name, collection_id])?;
+    Ok(())
+}
+
+// Only the grouping goes; the items stay in the library
+#[tauri::command]
+pub fn delete_collection(app: tauri::AppHandle, collection_id: i64) -> Result<(), AppError> {
+    let root = get_root(&app)?;
+    let conn = open_conn_for_root(&app, &root)?;
+    ensure_collection(&conn, collection_id)?;
+    conn.execute("DELETE FROM collections WHERE collection_id = ?", [collection_id])?;
+    Ok(())
+}
+
+// Returns how many items were newly added
+#[tauri::command]
+pub fn add_to_collection(app: tauri::AppHandle, collection_id: i64, item_ids: Vec<i64>) -> Result<u32, AppError> {
+    let root = get_root(&app)?;
+    let conn = open_conn_for_root(&app, &root)?;
+    ensure_collection(&conn, collection_id)?;
+
+    let now = chrono::Local::now().to_rfc3339();
+    let tx = conn.unchecked_transaction()?;
+    let mut added = 0u32;
+    for id in item_ids {
+        added += tx.execute(
+            "INSERT OR IGNORE INTO collection_items (collection_id, item_id, added_at) SELECT ?1, item_id, ?3 FROM items WHERE item_id = ?2",
+            params![collection_id, id, now],
+        )? as u32;
+    }
+    tx.commit()?;
+    Ok(added)
+}
+
+#[tauri::command]
+pub fn remove_from_collection(app: tauri::AppHandle, collection_id: i64, item_ids: Vec<i64>) -> Result<u32, AppError> {
+    let root = get_root(&app)?;
+    let conn = open_conn_for_root(&app, &root)?;
+
+    let tx = conn.unchecked_transaction()?;
+    let mut removed = 0u32;
+    for id in item_ids {
+        removed += tx.execute(
+            "DELETE FROM collection_items WHERE collection_id = ? AND item_id = ?",
+            params![collection_id, id],
+        )? as u32;
+    }
+    tx.commit()?;
+    Ok(removed)
+}
+
+// list_items restricted to one collection, with the same filters, order and paging
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub fn list_collection_items(
+    app: tauri::AppHandle,
+    collection_id: i64,
+    limit: Option<u32>,
+    offset: Option<u32>,
+    search: Option<String>,
+    rating: Option<String>,
+    source: Option<String>,
+    order: Option<String>,
+) -> Result<Vec<ItemDto>, AppError> {
+    let root = get_root(&app)?;
+    let conn = open_conn_for_root(&app, &root)?;
+    ensure_collection(&conn, collection_id)?;
+
+    let mut query = ItemQuery::build(&conn, search, rating, source, order)?;
+    query.restrict(
+        "i.item_id IN (SELECT item_id FROM collection_items WHERE collection_id = CAST(? AS INTEGER))",
+        collection_id.to_string(),
+    );
+    query_items(&conn, &root, &query, limit.unwrap_or(100), offset.unwrap_or(0))
+}
+
+fn store_item_raw(conn: &Connection, item_id: i64, raw: &serde_json::Value) -> Result<(), AppError> {
+    conn.execute(
+        "INSERT OR REPLACE INTO item_raw (item_id, json, fetched_at) VALUES (?, ?, ?)",
+        params![item_id, serde_json::to_string(raw)?, Utc::now().to_rfc3339()],
+    )?;
+    Ok(())
+}
+
+// The stored posts.json entry; null for items imported before it was kept, or
+// added without one
+#[tauri::command]
+pub fn get_item_raw(app: tauri::AppHandle, item_id: i64) -> Result<serde_json::Value, AppError> {
+    let root = get_root(&app)?;
+    let conn = open_conn_for_root(&app, &root)?;
+    let json: Option<String> = conn
+        .query_row("SELECT json FROM item_raw WHERE item_id = ?", [item_id], |r| r.get(0))
+        .optional()?;
+    match json {
+        Some(j) => Ok(serde_json::from_str(&j)?),
+        None => Ok(serde_json::Value::Null),
+    }
+}
+
+#[tauri::command]
+pub fn checkpoint_db(app: tauri::AppHandle) -> Result<i64, AppError> {
+    let root = get_root(&app)?;
+    let conn = open_conn_for_root(&app, &root)?;
+    db::checkpoint(&conn)
+}
+
+// Off by default: expanding a tag to everything that implies it changes result sets
+fn expand_implications(conn: &Connection) -> Result<bool, AppError> {
+    Ok(settings_get(conn, "expand_implications")?.is_some_and(|v| v == "true" || v == "1"))
+}
+
+#[tauri::command]
+pub fn get_expand_implications(app: tauri::AppHandle) -> Result<bool, AppError> {
+    let root = get_root(&app)?;
+    let conn = open_conn_for_root(&app, &root)?;
+    expand_implications(&conn)
+}
+
+#[tauri::command]
+pub fn set_expand_implications(app: tauri::AppHandle, enabled: bool) -> Result<(), AppError> {
+    let root = get_root(&app)?;
+    let conn = open_conn_for_root(&app, &root)?;
+    settings_set(&conn, "expand_implications", if enabled { "true" } else { "false" })
+}
+
+const E621_IMPLICATIONS_PAGE: &str = "1000";
+
+// Replaces tag_implications with e621's active implications. Pages with a
+// b<lowest id> cursor like the favorites sync; returns the number stored.
+#[tauri::command]
+pub async fn e621_sync_implications(app: tauri::AppHandle) -> Result<u32, AppError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let root = get_root(&app)?;
+        let conn = open_conn_for_root(&app, &root)?;
+        let client = reqwest::blocking::Client::new();
+        let user_agent = e621_user_agent(&conn);
+        let implications_url = e621_api_url(&conn, "tag_implications.json");
+
+        let mut pairs: Vec<(String, String)> = vec![];
+        let mut before_id: Option<i64> = None;
+        loop {
+            let page = before_id.map(|id| format!("b{id}")).unwrap_or_else(|| "1".to_string());
+            let resp = send_with_retry(|| {
+                client
+                    .get(&implications_url)
+                    .header("User-Agent", &user_agent)
+                    .query(&[
+                        ("search[status]", "active"),
+                        ("limit", E621_IMPLICATIONS_PAGE),
+                        ("page", page.as_str()),
+                    ])
+            })?;
+            if !resp.status().is_success() {
+                return Err(e621_error("e621 implications error", resp));
+            }
+
+            // an empty result comes back as {"tag_implications": []} rather than []
+            let json: serde_json::Value = resp.json()?;
+            let rows = json.as_array().cloned().unwrap_or_default();
+            if rows.is_empty() {
+                break;
+            }
+
+            for r in &rows {
+                let name = |k: &str| r.get(k).and_then(|v| v.as_str()).map(|v| v.to_lowercase());
+                if let (Some(a), Some(c)) = (name("antecedent_name"), name("consequent_name")) {
+                    pairs.push((a, c));
+                }
+            }
+
+            let lowest = rows.iter().filter_map(|r| r.get("id").and_then(|v| v.as_i64())).min();
+            match lowest {
+                Some(id) if Some(id) != before_id => before_id = Some(id),
+                _ => break,
+            }
+            std::thread::sleep(E621_REQUEST_INTERVAL);
+        }
+
+        let tx = conn.unchecked_transaction()?;
+        tx.execute("DELETE FROM tag_implications", [])?;
+        let mut stored = 0u32;
+        for (a, c) in pairs {
+            stored += tx.execute(
+                "INSERT OR IGNORE INTO tag_implications (antecedent, consequent) VALUES (?, ?)",
+                params![a, c],
+            )? as u32;
+        }
+        tx.commit()?;
+        Ok(stored)
+    }).await?
+}
+
+#[derive(Serialize, Default)]
+pub struct RatingStats {
+    pub safe: u32,
+    pub questionable: u32,
+    pub explicit: u32,
+    pub unrated: u32,
+    // score < 0, 0–49, 50–199, 200+, and items without a score (FA)
+    pub score_negative: u32,
+    pub score_low: u32,
+    pub score_mid: u32,
+    pub score_high: u32,
+    pub score_unknown: u32,
+}
+
+// Live items by rating and by coarse score band, for the stats page
+#[tauri::command]
+pub fn rating_breakdown(app: tauri::AppHandle) -> Result<RatingStats, AppError> {
+    let root = get_root(&app)?;
+    let conn = open_conn_for_root(&app, &root)?;
+    let mut stats = RatingStats::default();
+
+    let mut stmt = conn.prepare("SELECT rating, COUNT(*) FROM items WHERE trashed_at IS NULL GROUP BY rating")?;
+    let rows = stmt.query_map([], |r| Ok((r.get::<_, Option<String>>(0)?, r.get::<_, u32>(1)?)))?;
+    for row in rows {
+        let (rating, n) = row?;
+        match rating.as_deref() {
+            Some("s") => stats.safe += n,
+            Some("q") => stats.questionable += n,
+            Some("e") => stats.explicit += n,
+            _ => stats.unrated += n,
+        }
+    }
+
+    let mut stmt = conn.prepare(
+        r#"
+        SELECT CASE
+                 WHEN score_total IS NULL THEN 'unknown'
+                 WHEN score_total < 0 THEN 'negative'
+                 WHEN score_total < 50 THEN 'low'
+                 WHEN score_total < 200 THEN 'mid'
+                 ELSE 'high'
+               END AS bucket,
+               COUNT(*)
+        FROM items
+        WHERE trashed_at IS NULL
+        GROUP BY bucket
+        "#,
+    )?;
+    let rows = stmt.query_map([], |r| Ok((r.get::<_, String>(0)?, r.get::<_, u32>(1)?)))?;
+    for row in rows {
+        let (bucket, n) = row?;
+        match bucket.as_str() {
+            "negative" => stats.score_negative = n,
+            "low" => stats.score_low = n,
+            "mid" => stats.score_mid = n,
+            "high" => stats.score_high = n,
+            _ => stats.score_unknown = n,
+        }
+    }
+
+    Ok(stats)
+}
+
+// Links each item of `source_filter` ('e621', 'furaffinity' or 'all') to its own
+// post page when it isn't already, like the FA upgrade path does on import.
+// Returns how many items gained a link.
+#[tauri::command]
+pub fn add_derived_sources(app: tauri::AppHandle, source_filter: String) -> Result<u32, AppError> {
+    let root = get_root(&app)?;
+    let conn = open_conn_for_root(&app, &root)?;
+
+    let rows: Vec<(i64, String, String)> = {
+        let mut stmt = conn.prepare(
+            "SELECT item_id, source, source_id FROM items WHERE ?1 = 'all' OR source = ?1"
+        )?;
+        let rows = stmt.query_map([source_filter.trim()], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))?;
+        rows.collect::<Result<_, _>>()?
+    };
+
+    let tx = conn.unchecked_transaction()?;
+    let mut added = 0u32;
+    for (item_id, source, source_id) in rows {
+        let Some(url) = canonical_source_url(&source, &source_id) else { continue };
+        let sid = upsert_source(&tx, &url)?;
+        added += tx.execute(
+            "INSERT OR IGNORE INTO item_sources (item_id, source_row_id) VALUES (?, ?)",
+            params![item_id, sid],
+        )? as u32;
+    }
+    tx.commit()?;
+    Ok(added)
+}
+
+fn skip_unsupported_formats(conn: &Connection) -> Result<bool, AppError> {
+    Ok(settings_get(conn, "skip_unsupported_formats")?.is_some_and(|v| v == "true" || v == "1"))
+}
+
+#[tauri::command]
+pub fn get_skip_unsupported_formats(app: tauri::AppHandle) -> Result<bool, AppError> {
+    let root = get_root(&app)?;
+    let conn = open_conn_for_root(&app, &root)?;
+    skip_unsupported_formats(&conn)
+}
+
+// When on, the sync records flash posts as unavailable ("unsupported_format")
+// instead of downloading files nothing can display
+#[tauri::command]
+pub fn set_skip_unsupported_formats(app: tauri::AppHandle, enabled: bool) -> Result<(), AppError> {
+    let root = get_root(&app)?;
+    let conn = open_conn_for_root(&app, &root)?;
+    settings_set(&conn, "skip_unsupported_formats", if enabled { "true" } else { "false" })
+}
+
+// Swaps in a better copy of an item's file, keeping its row (tags, sources,
+// collections, notes) as is
+#[tauri::command]
+pub fn replace_item_file(app: tauri::AppHandle, item_id: i64, new_file_path: String) -> Result<Status, AppError> {
+    let root = get_root(&app)?;
+    let conn = open_conn_for_root(&app, &root)?;
+
+    let src = PathBuf::from(new_file_path.trim());
+    if !src.is_file() {
+        return Err(AppError::InvalidInput(format!("Not a file: {}", src.display())));
+    }
+    let kind = infer::get_from_path(&src)?
+        .filter(|k| matches!(k.matcher_type(), infer::MatcherType::Image | infer::MatcherType::Video))
+        .ok_or_else(|| AppError::InvalidInput(format!("Not an image or video: {}", src.display())))?;
+
+    ensure_free_space_for(&conn, &root, fs::metadata(&src)?.len())?;
+
+    // stage inside the library so the final rename can't cross volumes
+    let tmp_dir = root.join(".cache").join("tmp");
+    fs::create_dir_all(&tmp_dir)?;
+    let tmp_path = tmp_dir.join(format!("replace_{item_id}.part"));
+    fs::copy(&src, &tmp_path)?;
+
+    let file_rel = swap_item_file(&root, &conn, item_id, &tmp_path, kind.extension(), kind.mime_type())?;
+    Ok(Status { ok: true, message: format!("Replaced file with {file_rel}"), item_id: Some(item_id) })
+}
+
+// Moves a staged file in as the item's content and updates the file columns.
+// The new file keeps the old name's stem so it still follows the naming scheme;
+// only the extension follows the new content. `md5` stays the upstream post's
+// hash for dedupe, `file_md5` becomes the new content's. Returns the new file_rel.
+fn swap_item_file(
+    root: &std::path::Path,
+    conn: &Connection,
+    item_id: i64,
+    staged: &std::path::Path,
+    ext: &str,
+    mime: &str,
+) -> Result<String, AppError> {
+    let old_rel: String = match conn.query_row("SELECT file_rel FROM items WHERE item_id = ?", [item_id], |r| r.get(0)) {
+        Ok(rel) => rel,
+        Err(e) => {
+            let _ = fs::remove_file(staged);
+            return Err(e.into());
+        }
+    };
+    let old_path = library::resolve_in_root(root, &old_rel)?;
+    let md5 = md5_file(staged)?;
+    let len = fs::metadata(staged)?.len();
+
+    let media_dir = root.join("media");
+    let stem = old_path.file_stem().and_then(|s| s.to_str()).unwrap_or("item").to_string();
+    let base = format!("{stem}.{ext}");
+    // same name: replace in place; otherwise don't clobber another item's file
+    let filename = if old_rel == format!("media/{base}") { base } else { unique_media_name(&media_dir, &base) };
+    let dest_path = media_dir.join(&filename);
+    if let Err(e) = fs::rename(staged, &dest_path) {
+        let _ = fs::remove_file(staged);
+        return Err(e.into());
+    }
+    let file_rel = format!("media/{filename}");
+
+    // the old thumb shows the old content
+    remove_item_thumb(root, conn, item_id)?;
+
+    let dims = image::image_dimensions(&dest_path).ok();
+    conn.execute(
+        "UPDATE items SET file_rel = ?, ext = ?, mime = ?, size_bytes = ?, width = ?, height = ?, file_md5 = ?, quarantined = 0 WHERE item_id = ?",
+        params![
+            file_rel,
+            ext,
+            mime,
+            len as i64,
+            dims.map(|d| d.0),
+            dims.map(|d| d.1),
+            md5,
+            item_id
+        ],
+    )?;
+
+    if file_rel != old_rel {
+        if let Err(e) = fs::remove_file(&old_path) {
+            log::warn!("swap_item_file: could not remove old file {}: {}", old_rel, e);
+        }
+    }
+    Ok(file_rel)
+}
+
+// Re-derives primary_artist from each item's artist tags with the same rule
+// imports use. Items without artist tags (plain FA imports) keep theirs.
+// Returns how many items changed.
+#[tauri::command]
+pub fn recompute_primary_artists(app: tauri::AppHandle) -> Result<u32, AppError> {
+    let root = get_root(&app)?;
+    let conn = open_conn_for_root(&app, &root)?;
+
+    // e621 lists artist tags alphabetically, so name order matches what imports saw
+    let rows: Vec<(i64, Option<String>, String)> = {
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT i.item_id, i.primary_artist, t.name
+            FROM items i
+            JOIN item_tags it ON it.item_id = i.item_id
+            JOIN tags t ON t.tag_id = it.tag_id
+            WHERE t.type = 'artist'
+            ORDER BY i.item_id, t.name
+            "#,
+        )?;
+        let rows = stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))?;
+        rows.collect::<Result<_, _>>()?
+    };
+
+    let mut by_item: Vec<(i64, Option<String>, Vec<String>)> = vec![];
+    for (item_id, current, name) in rows {
+        match by_item.last_mut() {
+            Some((id, _, names)) if *id == item_id => names.push(name),
+            _ => by_item.push((item_id, current, vec![name])),
+        }
+    }
+
+    let tx = conn.unchecked_transaction()?;
+    let mut changed = 0u32;
+    for (item_id, current, artists) in by_item {
+        let primary = sanitize_slug(&pick_primary_artist(&artists));
+        if current.as_deref() != Some(primary.as_str()) {
+            tx.execute("UPDATE items SET primary_artist = ? WHERE item_id = ?", params![primary, item_id])?;
+            changed += 1;
+        }
+    }
+    tx.commit()?;
+    Ok(changed)
+}
+
+// Walks the tagging backlog oldest first: the next untagged:true item after
+// `after_item_id` by added_at, or the first one when None. Goes through
+// ItemQuery so SFW mode and quarantine apply as in every other listing.
+#[tauri::command]
+pub fn next_untagged(app: tauri::AppHandle, after_item_id: Option<i64>) -> Result<Option<ItemDto>, AppError> {
+    let root = get_root(&app)?;
+    let conn = pooled_conn(&app, &root)?;
+
+    let mut query = ItemQuery::build(&conn, Some("untagged:true".into()), None, None, Some("oldest".into()))?;
+    if let Some(id) = after_item_id {
+        // item_id breaks ties between items added in the same instant
+        query.restrict(
+            "(i.added_at, i.item_id) > (SELECT added_at, item_id FROM items WHERE item_id = CAST(? AS INTEGER))",
+            id.to_string(),
+        );
+    }
+    Ok(query_items(&conn, &root, &query, 1, 0)?.into_iter().next())
+}
+
+// Re-encodes a still image in place to shrink it: "webp" (lossless; this build
+// has no lossy WebP encoder), "jpeg" at `quality`, or "png". Everything but the
+// file columns is kept. Animated formats are skipped since only the first frame
+// would survive.
+#[tauri::command]
+pub async fn recompress_item(app: tauri::AppHandle, item_id: i64, format: String, quality: u8) -> Result<Status, AppError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let root = get_root(&app)?;
+        let conn = open_conn_for_root(&app, &root)?;
+
+        let (ext, mime, target) = match format.trim().to_lowercase().as_str() {
+            "webp" => ("webp", "image/webp", image::ImageFormat::WebP),
+            "jpeg" | "jpg" => ("jpg", "image/jpeg", image::ImageFormat::Jpeg),
+            "png" => ("png", "image/png", image::ImageFormat::Png),
+            other => return Err(AppError::InvalidInput(format!("Unsupported target format: {other}"))),
+        };
+
+        let file_rel: String = conn.query_row("SELECT file_rel FROM items WHERE item_id = ?", [item_id], |r| r.get(0))?;
+        let path = library::resolve_in_root(&root, &file_rel)?;
+        let current = infer::get_from_path(&path)?;
+        let is_still = current.is_some_and(|k| {
+            k.matcher_type() == infer::MatcherType::Image && !matches!(k.extension(), "gif" | "apng")
+        });
+        if !is_still {
+            return Ok(Status { ok: false, message: format!("Item {item_id} is not a still image, skipped"), item_id: Some(item_id) });
+        }
+        let old_size = fs::metadata(&path)?.len();
+
+        let img = image::open(&path)?;
+        let mut bytes: Vec<u8> = Vec::new();
+        let mut out = std::io::Cursor::new(&mut bytes);
+        match target {
+            image::ImageFormat::WebP => {
+                // the lossless encoder only takes 8-bit RGB(A)
+                let img = image::DynamicImage::ImageRgba8(img.to_rgba8());
+                img.write_with_encoder(image::codecs::webp::WebPEncoder::new_lossless(&mut out))?;
+            }
+            image::ImageFormat::Jpeg => {
+                let img = image::DynamicImage::ImageRgb8(img.to_rgb8());
+                img.write_to(&mut out, image::ImageOutputFormat::Jpeg(quality.clamp(1, 100)))?;
+            }
+            _ => img.write_to(&mut out, image::ImageOutputFormat::Png)?,
+        }
+
+        if bytes.len() as u64 >= old_size {
+            return Ok(Status {
+                ok: false,
+                message: format!("Re-encoded file wouldn't be smaller ({} vs {} bytes), kept the original", bytes.len(), old_size),
+                item_id: Some(item_id),
+            });
+        }
+
+        let tmp_dir = root.join(".cache").join("tmp");
+        fs::create_dir_all(&tmp_dir)?;
+        let tmp_path = tmp_dir.join(format!("recompress_{item_id}.part"));
+        fs::write(&tmp_path, &bytes)?;
+
+        let new_rel = swap_item_file(&root, &conn, item_id, &tmp_path, ext, mime)?;
+        Ok(Status {
+            ok: true,
+            message: format!("Recompressed to {new_rel}: {} -> {} bytes", old_size, bytes.len()),
+            item_id: Some(item_id),
+        })
+    }).await?
+}
+
+// Credentials, their verification stamp and the host they're sent to only change
+// through the e621 commands
+const PROTECTED_SETTINGS: &[&str] = &["e621_username", "e621_api_key", "e621_verified_at", "e621_base_url"];
+// Listed with the value blanked out
+const SECRET_SETTINGS: &[&str] = &["e621_api_key"];
+
+#[derive(Serialize)]
+pub struct SettingEntry {
+    pub key: String,
+    pub value: String,
+    // false for credential keys set_setting/delete_setting refuse
+    pub editable: bool,
+}
+
+fn protected_setting(key: &str) -> Result<(), AppError> {
+    if PROTECTED_SETTINGS.contains(&key) {
+        return Err(AppError::InvalidInput(format!("{key} can't be edited here")));
+    }
+    Ok(())
+}
+
+// Raw view of the settings table for the advanced panel
+#[tauri::command]
+pub fn list_settings(app: tauri::AppHandle) -> Result<Vec<SettingEntry>, AppError> {
+    let root = get_root(&app)?;
+    let conn = open_conn_for_root(&app, &root)?;
+
+    let mut stmt = conn.prepare("SELECT key, value FROM settings ORDER BY key")?;
+    let rows = stmt.query_map([], |r| Ok((r.get::<_, String>(0)?, r.get::<_, String>(1)?)))?;
+
+    let mut out = vec![];
+    for row in rows {
+        let (key, value) = row?;
+        let value = if SECRET_SETTINGS.contains(&key.as_str()) { String::new() } else { value };
+        let editable = !PROTECTED_SETTINGS.contains(&key.as_str());
+        out.push(SettingEntry { key, value, editable });
+    }
+    Ok(out)
+}
+
+// No validation beyond the denylist: each feature parses its own key when it reads it
+#[tauri::command]
+pub fn set_setting(app: tauri::AppHandle, key: String, value: String) -> Result<(), AppError> {
+    let key = key.trim();
+    if key.is_empty() {
+        return Err(AppError::InvalidInput("Setting key cannot be empty".into()));
+    }
+    protected_setting(key)?;
+    let root = get_root(&app)?;
+    let conn = open_conn_for_root(&app, &root)?;
+    settings_set(&conn, key, &value)
+}
+
+#[tauri::command]
+pub fn delete_setting(app: tauri::AppHandle, key: String) -> Result<(), AppError> {
+    let key = key.trim();
+    protected_setting(key)?;
+    let root = get_root(&app)?;
+    let conn = open_conn_for_root(&app, &root)?;
+    settings_delete(&conn, key)
+}
+
+// Rebuilds the whole search index; the triggers keep it current after that
+#[tauri::command]
+pub async fn reindex_all(app: tauri::AppHandle) -> Result<u32, AppError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let root = get_root(&app)?;
+        let conn = open_conn_for_root(&app, &root)?;
+        db::reindex_all(&conn)
+    }).await?
+}
+
+// One random live item matching the filters. Counts the matches and reads the
+// row at a random offset, which stays cheap where ORDER BY RANDOM() would sort
+// the whole result.
+#[tauri::command]
+pub fn random_item(
+    app: tauri::AppHandle,
+    search: Option<String>,
+    rating: Option<String>,
+    source: Option<String>,
+) -> Result<Option<ItemDto>, AppError> {
+    let root = get_root(&app)?;
+    let conn = open_conn_for_root(&app, &root)?;
+    let query = ItemQuery::build(&conn, search, rating, source, None)?;
+
+    let total: i64 = conn.query_row(
+        &format!("SELECT COUNT(*) FROM items i WHERE i.trashed_at IS NULL{}", query.where_sql),
+        &*query.sql_params(),
+        |r| r.get(0),
+    )?;
+    if total == 0 {
+        return Ok(None);
+    }
+
+    let offset: i64 = conn.query_row("SELECT abs(random() % ?)", [total], |r| r.get(0))?;
+    Ok(query_items(&conn, &root, &query, 1, offset as u32)?.pop())
+}
+
+#[derive(Serialize)]
+pub struct LibraryDto {
+    pub index: usize,
+    pub path: String,
+    pub active: bool,
+    // false when the folder is gone or its drive isn't mounted
+    pub available: bool,
+}
+
+#[tauri::command]
+pub fn list_libraries(app: tauri::AppHandle) -> Result<Vec<LibraryDto>, AppError> {
+    let cfg = config::load_config(&app)?;
+    Ok(cfg.libraries.iter().enumerate().map(|(index, path)| LibraryDto {
+        index,
+        path: path.clone(),
+        active: cfg.active == Some(index),
+        available: std::path::Path::new(path).is_dir(),
+    }).collect())
+}
+
+// Remembers a library without switching to it; returns its index. Adding a
+// known path just returns the existing index.
+#[tauri::command]
+pub fn add_library(app: tauri::AppHandle, library_root: String) -> Result<usize, AppError> {
+    let root = PathBuf::from(&library_root);
+    if !root.is_dir() {
+        return Err(AppError::NotFound("Selected library root does not exist".into()));
+    }
+    library::ensure_layout(&root)?;
+
+    let mut cfg = config::load_config(&app)?;
+    let index = cfg.add_library(&library_root);
+    config::save_config(&app, &cfg)?;
+    Ok(index)
+}
+
+// Makes a known library the active one: swaps the lock, migrates its DB and
+// re-grants scopes. Commands pick it up on their next get_root.
+#[tauri::command]
+pub fn switch_library(app: tauri::AppHandle, index: usize) -> Result<Status, AppError> {
+    let mut cfg = config::load_config(&app)?;
+    let path = cfg.libraries.get(index).cloned()
+        .ok_or_else(|| AppError::NotFound(format!("No library at index {index}")))?;
+    let root = PathBuf::from(&path);
+    if cfg.active == Some(index) {
+        return Ok(Status { ok: true, message: format!("{path} is already active"), item_id: None });
+    }
+
+    let sync = app.state::<Arc<Mutex<SyncState>>>();
+    if sync.lock().map_err(|_| "Sync state lock poisoned")?.status.running {
+        return Err(AppError::InvalidInput("Wait for the e621 sync to finish before switching libraries".into()));
+    }
+
+    open_library(&app, &root)?;
+    release_previous_lock(&app, &cfg, &root);
+    cfg.active = Some(index);
+    config::save_config(&app, &cfg)?;
+
+    Ok(Status { ok: true, message: format!("Switched to {path}"), item_id: None })
+}
+
+// Originals e621 makes a downscaled sample of that is still the same picture
+const SAMPLE_SOURCE_EXTS: &[&str] = &["jpg", "jpeg", "png", "webp"];
+
+fn prefer_sample(conn: &Connection) -> Result<bool, AppError> {
+    Ok(settings_get(conn, "prefer_sample")?.is_some_and(|v| v == "true" || v == "1"))
+}
+
+#[tauri::command]
+pub fn get_prefer_sample(app: tauri::AppHandle) -> Result<bool, AppError> {
+    let root = get_root(&app)?;
+    let conn = open_conn_for_root(&app, &root)?;
+    prefer_sample(&conn)
+}
+
+// Only affects downloads from now on; items record which one they got in `variant`
+#[tauri::command]
+pub fn set_prefer_sample(app: tauri::AppHandle, enabled: bool) -> Result<(), AppError> {
+    let root = get_root(&app)?;
+    let conn = open_conn_for_root(&app, &root)?;
+    settings_set(&conn, "prefer_sample", if enabled { "true" } else { "false" })
+}
+
+#[derive(Serialize)]
+pub struct ExtMismatch {
+    pub item_id: i64,
+    pub file_rel: String,
+    pub stored_ext: String,
+    // canonical extension of what the file really is
+    pub actual_ext: String,
+}
+
+fn canonical_ext(ext: &str) -> String {
+    let ext = ext.trim().to_lowercase();
+    if ext == "jpeg" { "jpg".to_string() } else { ext }
+}
+
+// Live items whose stored ext or on-disk extension isn't the canonical one for
+// their sniffed content. Files infer can't identify are left alone.
+fn find_ext_mismatches(root: &std::path::Path, conn: &Connection) -> Result<Vec<ExtMismatch>, AppError> {
+    let rows: Vec<(i64, String, String)> = {
+        let mut stmt = conn.prepare("SELECT item_id, file_rel, ext FROM items WHERE trashed_at IS NULL ORDER BY item_id")?;
+        let rows = stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))?;
+        rows.collect::<Result<_, _>>()?
+    };
+
+    let mut out = vec![];
+    for (item_id, file_rel, stored_ext) in rows {
+        let Ok(path) = library::resolve_in_root(root, &file_rel) else { continue };
+        let Some(kind) = infer::get_from_path(&path).ok().flatten() else { continue };
+        let actual_ext = canonical_ext(kind.extension());
+        let disk_ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        if stored_ext != actual_ext || disk_ext != actual_ext {
+            out.push(ExtMismatch { item_id, file_rel, stored_ext, actual_ext });
+        }
+    }
+    Ok(out)
+}
+
+// Dry run of normalize_extensions
+#[tauri::command]
+pub async fn list_extension_mismatches(app: tauri::AppHandle) -> Result<Vec<ExtMismatch>, AppError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let root = get_root(&app)?;
+        let conn = open_conn_for_root(&app, &root)?;
+        find_ext_mismatches(&root, &conn)
+    }).await?
+}
+
+// Sets each mismatched item's ext (and mime) from its content and renames the
+// file to match, so type:/ext: filters see the truth. Returns the number fixed.
+#[tauri::command]
+pub async fn normalize_extensions(app: tauri::AppHandle) -> Result<u32, AppError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let root = get_root(&app)?;
+        let conn = open_conn_for_root(&app, &root)?;
+
+        let mut fixed = 0u32;
+        for m in find_ext_mismatches(&root, &conn)? {
+            let to_rel = match m.file_rel.rsplit_once('.') {
+                Some((stem, _)) if !stem.ends_with('/') => format!("{stem}.{}", m.actual_ext),
+                _ => format!("{}.{}", m.file_rel, m.actual_ext),
+            };
+            let new_rel = match relocate_item_file(&root, &conn, m.item_id, &m.file_rel, &to_rel) {
+                Ok(rel) => rel,
+                Err(e) => {
+                    log::warn!("normalize_extensions: skipping item {}: {}", m.item_id, e);
+                    continue;
+                }
+            };
+            let mime = infer::get_from_path(root.join(&new_rel)).ok().flatten().map(|k| k.mime_type().to_string());
+            conn.execute(
+                "UPDATE items SET ext = ?, mime = COALESCE(?, mime) WHERE item_id = ?",
+                params![m.actual_ext, mime, m.item_id],
+            )?;
+            fixed += 1;
+        }
+        Ok(fixed)
+    }).await?
+}
+
+#[derive(Serialize, Default)]
+pub struct TagsByType {
+    pub artist: Vec<String>,
+    pub copyright: Vec<String>,
+    pub character: Vec<String>,
+    pub species: Vec<String>,
+    pub general: Vec<String>,
+    pub lore: Vec<String>,
+    pub meta: Vec<String>,
+    // anything else, e.g. e621's 'invalid' category
+    pub other: Vec<String>,
+}
+
+// One item's tags grouped for the tag editor, each group sorted by name
+#[tauri::command]
+pub fn item_tags_typed(app: tauri::AppHandle, item_id: i64) -> Result<TagsByType, AppError> {
+    let root = get_root(&app)?;
+    let conn = open_conn_for_root(&app, &root)?;
+
+    let mut stmt = conn.prepare(
+        "SELECT t.type, t.name FROM item_tags it JOIN tags t ON t.tag_id = it.tag_id WHERE it.item_id = ? ORDER BY t.name"
+    )?;
+    let rows = stmt.query_map([item_id], |r| Ok((r.get::<_, String>(0)?, r.get::<_, String>(1)?)))?;
+
+    let mut out = TagsByType::default();
+    for row in rows {
+        let (tag_type, name) = row?;
+        let bucket = match tag_type.as_str() {
+            "artist" => &mut out.artist,
+            "copyright" => &mut out.copyright,
+            "character" => &mut out.character,
+            "species" => &mut out.species,
+            "general" => &mut out.general,
+            "lore" => &mut out.lore,
+            "meta" => &mut out.meta,
+            _ => &mut out.other,
+        };
+        bucket.push(name);
+    }
+    Ok(out)
+}
+
+pub fn keep_fa_original(conn: &Connection) -> bool {
+    settings_get(conn, "keep_fa_original").ok().flatten().is_some_and(|v| v == "true" || v == "1")
+}
+
+#[tauri::command]
+pub fn get_keep_fa_original(app: tauri::AppHandle) -> Result<bool, AppError> {
+    let root = get_root(&app)?;
+    let conn = open_conn_for_root(&app, &root)?;
+    Ok(keep_fa_original(&conn))
+}
+
+// When on, the FA sync also keeps the favorited FA file when it swaps in the
+// e621 copy, recording it in the item's fa_original_rel
+#[tauri::command]
+pub fn set_keep_fa_original(app: tauri::AppHandle, enabled: bool) -> Result<(), AppError> {
+    let root = get_root(&app)?;
+    let conn = open_conn_for_root(&app, &root)?;
+    settings_set(&conn, "keep_fa_original", if enabled { "true" } else { "false" })
+}
+
+// Bulk cull: trashes live items scoring below `min_score` and/or with fewer than
+// `min_favs` favorites (both must hold when both are given), optionally only
+// from one source. Items without a score or fav count (FA) never match.
+#[tauri::command]
+pub fn trash_below_threshold(
+    app: tauri::AppHandle,
+    min_score: Option<i64>,
+    min_favs: Option<i64>,
+    source: Option<String>,
+) -> Result<u32, AppError> {
+    if min_score.is_none() && min_favs.is_none() {
+        return Err(AppError::InvalidInput("Give a minimum score or favorite count".into()));
+    }
+    let root = get_root(&app)?;
+    let conn = open_conn_for_root(&app, &root)?;
+
+    let source = source.filter(|s| !s.is_empty() && s != "all");
+    let ids: Vec<i64> = {
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT item_id FROM items
+            WHERE trashed_at IS NULL
+              AND (?1 IS NULL OR score_total < ?1)
+              AND (?2 IS NULL OR fav_count < ?2)
+              AND (?3 IS NULL OR source = ?3)
+            "#,
+        )?;
+        let rows = stmt.query_map(params![min_score, min_favs, source], |r| r.get(0))?;
+        rows.collect::<Result<_, _>>()?
+    };
+    drop(conn);
+
+    Ok(trash_items(app, ids)?.len() as u32)
+}
+
+#[derive(Serialize)]
+pub struct NoteDto {
+    pub note_id: i64,
+    // box in the original image's pixels
+    pub x: i64,
+    pub y: i64,
+    pub width: i64,
+    pub height: i64,
+    // e621 DText, usually the translation
+    pub body: String,
+}
+
+fn cached_e621_notes(conn: &Connection, item_id: i64) -> Result<Vec<NoteDto>, AppError> {
+    let mut stmt = conn.prepare(
+        "SELECT note_id, x, y, width, height, body FROM e621_notes WHERE item_id = ? ORDER BY y, x"
+    )?;
+    let rows = stmt.query_map([item_id], |r| Ok(NoteDto {
+        note_id: r.get(0)?,
+        x: r.get(1)?,
+        y: r.get(2)?,
+        width: r.get(3)?,
+        height: r.get(4)?,
+        body: r.get(5)?,
+    }))?;
+    Ok(rows.collect::<Result<_, _>>()?)
+}
+
+// Translation notes for an e621 item, from the cache unless `refresh` or nothing
+// has been fetched yet. A post without notes is just an empty list.
+#[tauri::command]
+pub async fn e621_fetch_notes(app: tauri::AppHandle, item_id: i64, refresh: Option<bool>) -> Result<Vec<NoteDto>, AppError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let root = get_root(&app)?;
+        let conn = open_conn_for_root(&app, &root)?;
+
+        let (source, source_id): (String, String) = conn.query_row(
+            "SELECT source, source_id FROM items WHERE item_id = ?",
+            [item_id],
+            |r| Ok((r.get(0)?, r.get(1)?)),
+        )?;
+        if source != "e621" {
+            return Err(AppError::InvalidInput(format!("Item {item_id} is not an e621 post")));
+        }
+
+        let cached = cached_e621_notes(&conn, item_id)?;
+        if !cached.is_empty() && !refresh.unwrap_or(false) {
+            return Ok(cached);
+        }
+
+        let (username, api_key) = load_e621_creds(&conn)?;
+        let client = reqwest::blocking::Client::new();
+        let user_agent = e621_user_agent(&conn);
+        let notes_url = e621_api_url(&conn, "notes.json");
+        let resp = send_with_retry(|| {
+            client
+                .get(&notes_url)
+                .basic_auth(&username, Some(&api_key))
+                .header("User-Agent", &user_agent)
+                .query(&[("search[post_id]", source_id.as_str()), ("search[is_active]", "true"), ("limit", "1000")])
+        })?;
+        if !resp.status().is_success() {
+            return Err(e621_error("e621 notes error", resp));
+        }
+
+        // no notes comes back as {"notes": []} rather than []
+        let json: serde_json::Value = resp.json()?;
+        let notes = json.as_array().cloned().unwrap_or_default();
+
+        let tx = conn.unchecked_transaction()?;
+        tx.execute("DELETE FROM e621_notes WHERE item_id = ?", [item_id])?;
+        for n in &notes {
+            let int = |k: &str| n.get(k).and_then(|v| v.as_i64());
+            let (Some(note_id), Some(x), Some(y), Some(w), Some(h)) = (int("id"), int("x"), int("y"), int("width"), int("height")) else { continue };
+            let body = n.get("body").and_then(|v| v.as_str()).unwrap_or("");
+            tx.execute(
+                "INSERT OR REPLACE INTO e621_notes (note_id, item_id, x, y, width, height, body) VALUES (?, ?, ?, ?, ?, ?, ?)",
+                params![note_id, item_id, x, y, w, h, body],
+            )?;
+        }
+        tx.commit()?;
+
+        cached_e621_notes(&conn, item_id)
+    }).await?
+}
+
+// One-time cleanup for sources stored before URL normalization: rows that
+// normalize to the same URL collapse into one, their item links re-pointed onto
+// it. Returns how many redundant rows were removed.
+#[tauri::command]
+pub fn dedupe_sources(app: tauri::AppHandle) -> Result<u32, AppError> {
+    let root = get_root(&app)?;
+    let conn = open_conn_for_root(&app, &root)?;
+
+    let rows: Vec<(i64, String)> = {
+        let mut stmt = conn.prepare("SELECT source_row_id, url FROM sources ORDER BY source_row_id")?;
+        let rows = stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?)))?;
+        rows.collect::<Result<_, _>>()?
+    };
+
+    let mut groups: std::collections::BTreeMap<String, Vec<(i64, String)>> = Default::default();
+    for (id, url) in rows {
+        groups.entry(normalize_url(&url)).or_default().push((id, url));
+    }
+
+    let tx = conn.unchecked_transaction()?;
+    let mut merged = 0u32;
+    for (canonical, rows) in groups {
+        // a row already holding the canonical URL wins, so the rename below can't collide
+        let keep = rows.iter().find(|(_, url)| *url == canonical).unwrap_or(&rows[0]).0;
+        for (id, _) in rows.iter().filter(|(id, _)| *id != keep) {
+            tx.execute(
+                "INSERT OR IGNORE INTO item_sources (item_id, source_row_id) SELECT item_id, ?1 FROM item_sources WHERE source_row_id = ?2",
+                [keep, *id],
+            )?;
+            tx.execute("DELETE FROM item_sources WHERE source_row_id = ?", [id])?;
+            tx.execute("DELETE FROM sources WHERE source_row_id = ?", [id])?;
+            merged += 1;
+        }
+        tx.execute("UPDATE sources SET url = ? WHERE source_row_id = ? AND url <> ?1", params![canonical, keep])?;
+    }
+    tx.commit()?;
+    Ok(merged)
+}
+
+// Fires on every lightbox open, so it's a single keyed UPDATE and nothing else
+#[tauri::command]
+pub fn mark_viewed(app: tauri::AppHandle, item_id: i64) -> Result<(), AppError> {
+    let root = get_root(&app)?;
+    let conn = open_conn_for_root(&app, &root)?;
+
+    let n = conn.execute(
+        "UPDATE items SET last_viewed_at = ? WHERE item_id = ?",
+        params![Utc::now().to_rfc3339(), item_id],
+    )?;
+    if n == 0 {
+        return Err(AppError::NotFound(format!("Item {} not found", item_id)));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_e621_base_url(app: tauri::AppHandle) -> Result<String, AppError> {
+    let root = get_root(&app)?;
+    let conn = open_conn_for_root(&app, &root)?;
+    Ok(e621_base_url(&conn))
+}
+
+// e.g. https://e926.net for the SFW mirror; None or blank goes back to e621.net
+#[tauri::command]
+pub fn set_e621_base_url(app: tauri::AppHandle, url: Option<String>) -> Result<Status, AppError> {
+    let root = get_root(&app)?;
+    let conn = open_conn_for_root(&app, &root)?;
+
+    let Some(url) = url.map(|u| u.trim().trim_end_matches('/').to_string()).filter(|u| !u.is_empty()) else {
+        settings_delete(&conn, "e621_base_url")?;
+        return Ok(Status { ok: true, message: format!("Using {}", E621_DEFAULT_BASE_URL), item_id: None });
+    };
+
+    let parsed = reqwest::Url::parse(&url).map_err(|e| AppError::InvalidInput(format!("Invalid URL {}: {}", url, e)))?;
+    if !matches!(parsed.scheme(), "http" | "https") || parsed.host_str().is_none() {
+        return Err(AppError::InvalidInput(format!("Base URL must be http(s) with a host: {}", url)));
+    }
+
+    settings_set(&conn, "e621_base_url", &url)?;
+    Ok(Status { ok: true, message: format!("Using {}", url), item_id: None })
+}
+
+// On-demand version of the FA sync's e621 check for an item already in the
+// library: looks its md5 up on e621 and returns the matching post id. With
+// `upgrade`, the item becomes that post in place (file, tags, scores, sources)
+// while keeping its own tags, notes, collections and FA source link.
+#[tauri::command]
+pub async fn recheck_e621_match(app: tauri::AppHandle, item_id: i64, upgrade: Option<bool>) -> Result<Option<i64>, AppError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let root = get_root(&app)?;
+        let conn = open_conn_for_root(&app, &root)?;
+
+        let (source, md5): (String, Option<String>) = conn
+            .query_row(
+                "SELECT source, COALESCE(md5, file_md5) FROM items WHERE item_id = ?",
+                [item_id],
+                |r| Ok((r.get(0)?, r.get(1)?)),
+            )
+            .optional()?
+            .ok_or_else(|| AppError::NotFound(format!("Item {} not found", item_id)))?;
+        if source == "e621" {
+            return Err(AppError::InvalidInput(format!("Item {} is already an e621 post", item_id)));
+        }
+        let md5 = md5.ok_or_else(|| AppError::InvalidInput(format!("Item {} has no md5 yet; run backfill_md5 first", item_id)))?;
+
+        let client = reqwest::blocking::Client::new();
+        let mut req = client
+            .get(e621_api_url(&conn, "posts.json"))
+            .header("User-Agent", e621_user_agent(&conn))
+            .query(&[("tags", format!("md5:{}", md5)), ("limit", "1".to_string())]);
+        // anonymous lookups miss posts hidden from logged-out users
+        if let Ok((username, api_key)) = load_e621_creds(&conn) {
+            req = req.basic_auth(username, Some(api_key));
+        }
+        let resp = req.send()?;
+        if !resp.status().is_success() {
+            return Err(e621_error("e621 md5 lookup failed", resp));
+        }
+        let json: serde_json::Value = resp.json()?;
+        let Some(post) = json.get("posts").and_then(|p| p.as_array()).and_then(|a| a.first()) else {
+            return Ok(None);
+        };
+        let Some(post_id) = post.get("id").and_then(|x| x.as_i64()) else {
+            return Ok(None);
+        };
+        if !upgrade.unwrap_or(false) {
+            return Ok(Some(post_id));
+        }
+
+        let existing: Option<i64> = conn
+            .query_row(
+                "SELECT item_id FROM items WHERE source = 'e621' AND source_id = ?",
+                [post_id.to_string()],
+                |r| r.get(0),
+            )
+            .optional()?;
+        if let Some(other) = existing {
+            return Err(AppError::Duplicate(format!(
+                "e621 post {} is already item {}; merge_duplicate_items can combine them",
+                post_id, other
+            )));
+        }
+        let input = e621_post_from_json(post)
+            .ok_or_else(|| AppError::NotFound(format!("e621 post {} has no downloadable file (deleted or blocked)", post_id)))?;
+
+        ensure_free_space(&conn, &root)?;
+
+        // same choice as import_e621_post: samples only stand in for stills
+        let mut ext = input.file_ext.trim().to_lowercase();
+        let use_sample = input.sample_url.is_some()
+            && SAMPLE_SOURCE_EXTS.contains(&ext.as_str())
+            && prefer_sample(&conn)?;
+        let (download_url, variant) = match (&input.sample_url, use_sample) {
+            (Some(url), true) => {
+                ext = url.rsplit('.').next().filter(|e| e.len() <= 4).unwrap_or("jpg").to_lowercase();
+                (url.clone(), "sample")
+            }
+            _ => (input.file_url.clone(), "full"),
+        };
+
+        let tmp_dir = root.join(".cache").join("tmp");
+        fs::create_dir_all(&tmp_dir)?;
+        let tmp_path = tmp_dir.join(format!("recheck_{item_id}.part"));
+        let mut resp = send_with_retry(|| client.get(&download_url).header("User-Agent", e621_user_agent(&conn)))?;
+        if !resp.status().is_success() {
+            return Err(AppError::Network(format!("Download failed: HTTP {}", resp.status())));
+        }
+        // cancellable through cancel_download with the post id, and reports download-progress
+        let source_id = post_id.to_string();
+        let tracking = |active: bool| {
+            if let Ok(mut st) = app.state::<Arc<Mutex<SyncState>>>().lock() {
+                st.cancelled_downloads.remove(&source_id);
+                if active {
+                    st.recheck_downloads.insert(source_id.clone());
+                } else {
+                    st.recheck_downloads.remove(&source_id);
+                }
+            }
+        };
+        tracking(true);
+        let downloaded = fs::File::create(&tmp_path).map_err(AppError::from).and_then(|mut file| {
+            download_with_progress(&app, &source_id, &mut resp, &mut file)?;
+            Ok(file.flush()?)
+        });
+        tracking(false);
+        if let Err(e) = downloaded {
+            let _ = fs::remove_file(&tmp_path);
+            return Err(e);
+        }
+
+        let kind = infer::get_from_path(&tmp_path).ok().flatten();
+        if let Some(real) = corrected_ext(kind, &ext) {
+            ext = real.to_string();
+        }
+        let mime = kind.map(|k| k.mime_type().to_string());
+        let file_md5 = md5_file(&tmp_path);
+        let file_md5 = match file_md5 {
+            Ok(m) => m,
+            Err(e) => {
+                let _ = fs::remove_file(&tmp_path);
+                return Err(e);
+            }
+        };
+
+        // The new file moves in under a fresh name next to the old one, which stays
+        // until the row change has committed, so a failure leaves the item as it was
+        let old_rel: String = conn.query_row("SELECT file_rel FROM items WHERE item_id = ?", [item_id], |r| r.get(0))?;
+        let old_path = library::resolve_in_root(&root, &old_rel)?;
+        let media_dir = root.join("media");
+        let stem = old_path.file_stem().and_then(|s| s.to_str()).unwrap_or("item").to_string();
+        let filename = unique_media_name(&media_dir, &format!("{stem}.{ext}"));
+        let dest_path = media_dir.join(&filename);
+        if let Err(e) = fs::rename(&tmp_path, &dest_path) {
+            let _ = fs::remove_file(&tmp_path);
+            return Err(e.into());
+        }
+        let file_rel = format!("media/{filename}");
+
+        // same as the sync's upgrade path: the replaced FA file survives under fa_originals
+        let mut original: Option<(String, PathBuf)> = None;
+        let update = (|| -> Result<(), AppError> {
+            if source == "furaffinity" && keep_fa_original(&conn) {
+                let original_dir = root.join(library::FA_ORIGINALS_DIR);
+                fs::create_dir_all(&original_dir)?;
+                let name = unique_media_name(&original_dir, old_path.file_name().and_then(|n| n.to_str()).unwrap_or("original"));
+                fs::copy(&old_path, original_dir.join(&name))?;
+                original = Some((format!("{}/{}", library::FA_ORIGINALS_DIR, name), original_dir.join(&name)));
+            }
+
+            let tx = conn.unchecked_transaction()?;
+            let dims = image::image_dimensions(&dest_path).ok();
+            // SET expressions see the old row, so discovered_via keeps the original platform
+            tx.execute(
+                r#"
+                UPDATE items SET
+                  source = 'e621', source_id = ?, md5 = ?, remote_url = ?, variant = ?,
+                  file_rel = ?, ext = ?, mime = ?, size_bytes = ?, width = ?, height = ?, file_md5 = ?,
+                  rating = COALESCE(?, rating), fav_count = ?, score_total = ?, remote_status = ?,
+                  uploader_id = ?, approver_id = ?, quarantined = 0,
+                  fa_original_rel = COALESCE(?, fa_original_rel),
+                  discovered_via = COALESCE(discovered_via, source)
+                WHERE item_id = ?
+                "#,
+                params![
+                    post_id.to_string(),
+                    input.file_md5,
+                    download_url,
+                    variant,
+                    file_rel,
+                    ext,
+                    mime,
+                    fs::metadata(&dest_path)?.len() as i64,
+                    dims.map(|d| d.0),
+                    dims.map(|d| d.1),
+                    file_md5,
+                    input.rating,
+                    input.fav_count,
+                    input.score_total,
+                    input.remote_status,
+                    input.uploader_id,
+                    input.approver_id,
+                    original.as_ref().map(|(rel, _)| rel.as_str()),
+                    item_id
+                ],
+            )?;
+            if !input.tags.artist.is_empty() {
+                tx.execute(
+                    "UPDATE items SET primary_artist = ? WHERE item_id = ?",
+                    params![sanitize_slug(&pick_primary_artist(&input.tags.artist)), item_id],
+                )?;
+            }
+
+            let typed = [
+                ("general", &input.tags.general),
+                ("species", &input.tags.species),
+                ("character", &input.tags.character),
+                ("artist", &input.tags.artist),
+                ("meta", &input.tags.meta),
+                ("lore", &input.tags.lore),
+                ("copyright", &input.tags.copyright),
+            ];
+            for (tag_type, tags) in typed {
+                for t in tags {
+                    let id = upsert_tag(&tx, t, tag_type)?;
+                    tx.execute("INSERT OR IGNORE INTO item_tags (item_id, tag_id) VALUES (?, ?)", params![item_id, id])?;
+                }
+            }
+            db::reindex_item(&tx, item_id)?;
+
+            let page = canonical_source_url("e621", &post_id.to_string());
+            for u in page.iter().chain(input.sources.iter()) {
+                let sid = upsert_source(&tx, u)?;
+                tx.execute(
+                    "INSERT OR IGNORE INTO item_sources (item_id, source_row_id) VALUES (?, ?)",
+                    params![item_id, sid],
+                )?;
+            }
+            if let Some(raw) = &input.raw {
+                store_item_raw(&tx, item_id, raw)?;
+            }
+            tx.commit()?;
+            Ok(())
+        })();
+
+        if let Err(e) = update {
+            let _ = fs::remove_file(&dest_path);
+            if let Some((_, path)) = &original {
+                let _ = fs::remove_file(path);
+            }
+            return Err(e);
+        }
+
+        if let Err(e) = fs::remove_file(&old_path) {
+            log::warn!("recheck_e621_match: could not remove old file {}: {}", old_rel, e);
+        }
+        // the old thumbnail shows the FA file
+        remove_cached_thumb(&root, &old_rel);
+        remove_item_thumb(&root, &conn, item_id)?;
+
+        Ok(Some(post_id))
+    })
+    .await?
+}
+
+// Links `tag` to every live item the filter matches. A new tag gets `tag_type`
+// (default general); an existing one only changes type when `tag_type` is given.
+// Returns how many items newly got the tag.
+#[tauri::command]
+pub fn bulk_add_tag(
+    app: tauri::AppHandle,
+    search: Option<String>,
+    rating: Option<String>,
+    source: Option<String>,
+    tag: String,
+    tag_type: Option<String>,
+) -> Result<u32, AppError> {
+    let root = get_root(&app)?;
+    let conn = open_conn_for_root(&app, &root)?;
+
+    let name = tag.trim().to_lowercase();
+    if name.is_empty() {
+        return Err(AppError::InvalidInput("Tag is empty".into()));
+    }
+    let tag_type = tag_type.as_deref().map(normalize_tag_type).transpose()?;
+    let ids = search_item_ids(&conn, search, rating, source, None)?;
+
+    let tx = conn.unchecked_transaction()?;
+    let tag_id = match tag_type {
+        Some(t) => upsert_tag(&tx, &name, t)?,
+        None => {
+            tx.execute("INSERT OR IGNORE INTO tags (name, type) VALUES (?, 'general')", [&name])?;
+            tx.query_row("SELECT tag_id FROM tags WHERE name = ?", [&name], |r| r.get(0))?
+        }
+    };
+    let mut added = 0u32;
+    for id in ids {
+        if tx.execute("INSERT OR IGNORE INTO item_tags (item_id, tag_id) VALUES (?, ?)", [id, tag_id])? > 0 {
+            db::reindex_item(&tx, id)?;
+            added += 1;
+        }
+    }
+    tx.commit()?;
+    Ok(added)
+}
+
+// Unlinks `tag` from every live item the filter matches; the tag row itself stays.
+// Returns how many items lost it.
+#[tauri::command]
+pub fn bulk_remove_tag(
+    app: tauri::AppHandle,
+    search: Option<String>,
+    rating: Option<String>,
+    source: Option<String>,
+    tag: String,
+) -> Result<u32, AppError> {
+    let root = get_root(&app)?;
+    let conn = open_conn_for_root(&app, &root)?;
+
+    let name = tag.trim().to_lowercase();
+    let Some(tag_id) = conn
+        .query_row("SELECT tag_id FROM tags WHERE name = ?", [&name], |r| r.get::<_, i64>(0))
+        .optional()?
+    else {
+        return Err(AppError::NotFound(format!("Tag not found: {}", name)));
+    };
+    let ids = search_item_ids(&conn, search, rating, source, None)?;
+
+    let tx = conn.unchecked_transaction()?;
+    let mut removed = 0u32;
+    for id in ids {
+        if tx.execute("DELETE FROM item_tags WHERE item_id = ? AND tag_id = ?", [id, tag_id])? > 0 {
+            db::reindex_item(&tx, id)?;
+            removed += 1;
+        }
+    }
+    tx.commit()?;
+    Ok(removed)
+}
+
+// Page size for users.json lookups by id list
+const E621_USERS_CHUNK: usize = 100;
+
+// Looks up the names behind uploader and approver ids not in e621_users yet, so
+// get_item can show them and uploader:<name> can match. Returns how many were added.
+#[tauri::command]
+pub async fn e621_resolve_users(app: tauri::AppHandle) -> Result<u32, AppError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let root = get_root(&app)?;
+        let conn = open_conn_for_root(&app, &root)?;
+
+        let ids: Vec<i64> = {
+            let mut stmt = conn.prepare(
+                r#"
+                SELECT id FROM (
+                  SELECT uploader_id AS id FROM items WHERE uploader_id IS NOT NULL
+                  UNION SELECT approver_id FROM items WHERE approver_id IS NOT NULL
+                )
+                WHERE id NOT IN (SELECT user_id FROM e621_users)
+                "#,
+            )?;
+            let rows = stmt.query_map([], |r| r.get(0))?;
+            rows.collect::<Result<_, _>>()?
+        };
+        if ids.is_empty() {
+            return Ok(0);
+        }
+
+        let client = reqwest::blocking::Client::new();
+        let user_agent = e621_user_agent(&conn);
+        let users_url = e621_api_url(&conn, "users.json");
+        let creds = load_e621_creds(&conn).ok();
+        let mut added = 0u32;
+
+        for (n, chunk) in ids.chunks(E621_USERS_CHUNK).enumerate() {
+            if n > 0 {
+                std::thread::sleep(E621_REQUEST_INTERVAL);
+            }
+            let id_list = chunk.iter().map(|id| id.to_string()).collect::<Vec<_>>().join(",");
+            let limit = chunk.len().to_string();
+            let resp = send_with_retry(|| {
+                let req = client
+                    .get(&users_url)
+                    .header("User-Agent", &user_agent)
+                    .query(&[("search[id]", id_list.as_str()), ("limit", limit.as_str())]);
+                match &creds {
+                    Some((username, api_key)) => req.basic_auth(username, Some(api_key)),
+                    None => req,
+                }
+            })?;
+            if !resp.status().is_success() {
+                return Err(e621_error("e621 users error", resp));
+            }
+
+            let json: serde_json::Value = resp.json()?;
+            for u in json.as_array().cloned().unwrap_or_default() {
+                let (Some(id), Some(name)) = (u.get("id").and_then(|x| x.as_i64()), u.get("name").and_then(|x| x.as_str())) else {
+                    continue;
+                };
+                added += conn.execute(
+                    "INSERT INTO e621_users (user_id, name) VALUES (?, ?) ON CONFLICT(user_id) DO UPDATE SET name = excluded.name",
+                    params![id, name],
+                )? as u32;
+            }
+        }
+        Ok(added)
+    })
+    .await?
+}
+
+// Checks every live item's file against its recorded hash. Missing or mismatched
+// files are quarantined rather than deleted; quarantined items that check out
+// again are released. Returns how many items are quarantined afterwards.
+#[tauri::command]
+pub async fn verify_library(app: tauri::AppHandle) -> Result<u32, AppError> {
+    let cancel = app.state::<MaintenanceState>().cancel.clone();
+    cancel.reset();
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let root = get_root(&app)?;
+        let conn = open_conn_for_root(&app, &root)?;
+
+        // md5 is the upstream hash, so it only describes the file for full downloads
+        let rows: Vec<(i64, String, Option<String>, bool)> = {
+            let mut stmt = conn.prepare(
+                "SELECT item_id, file_rel, COALESCE(file_md5, CASE WHEN variant = 'full' THEN md5 END), quarantined FROM items WHERE trashed_at IS NULL"
+            )?;
+            let rows = stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?)))?;
+            rows.collect::<Result<_, _>>()?
+        };
+
+        let total = rows.len() as u32;
+        for (n, (item_id, file_rel, expected, was_quarantined)) in rows.into_iter().enumerate() {
+            if cancel.is_cancelled() {
+                break;
+            }
+
+            let bad = match library::resolve_in_root(&root, &file_rel) {
+                Ok(path) if path.is_file() => match &expected {
+                    Some(md5) => md5_file(&path).map_or(true, |h| !h.eq_ignore_ascii_case(md5)),
+                    None => false,
+                },
+                _ => true,
+            };
+            if bad != was_quarantined {
+                if bad {
+                    log::warn!("verify_library: quarantining item {} ({})", item_id, file_rel);
+                }
+                conn.execute("UPDATE items SET quarantined = ? WHERE item_id = ?", params![bad, item_id])?;
+            }
+
+            let done = n as u32 + 1;
+            if done.is_multiple_of(25) || done == total {
+                let _ = app.emit("verify-progress", TaskProgress { done, total });
+            }
+        }
+
+        let quarantined: u32 = conn.query_row(
+            "SELECT COUNT(*) FROM items WHERE trashed_at IS NULL AND quarantined = 1",
+            [],
+            |r| r.get(0),
+        )?;
+        Ok(quarantined)
+    }).await?
+}
+
+#[tauri::command]
+pub fn list_quarantined(app: tauri::AppHandle) -> Result<Vec<ItemDto>, AppError> {
+    let root = get_root(&app)?;
+    let conn = open_conn_for_root(&app, &root)?;
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {ITEM_DTO_COLUMNS} FROM items i WHERE i.trashed_at IS NULL AND i.quarantined = 1 ORDER BY i.added_at DESC"
+    ))?;
+    let rows = stmt.query_map([], |r| item_dto_from_row(&root, r))?;
+    Ok(rows.collect::<Result<_, _>>()?)
+}
+
+// Puts a quarantined item back into normal listings as-is, e.g. once a
+// mismatch turned out to be a deliberate edit
+#[tauri::command]
+pub fn release_quarantined(app: tauri::AppHandle, item_id: i64) -> Result<(), AppError> {
+    let root = get_root(&app)?;
+    let conn = open_conn_for_root(&app, &root)?;
+
+    let n = conn.execute("UPDATE items SET quarantined = 0 WHERE item_id = ?", [item_id])?;
+    if n == 0 {
+        return Err(AppError::NotFound(format!("Item {} not found", item_id)));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_url_upgrades_http() {
+        assert_eq!(normalize_url("http://example.com/a"), "https://example.com/a");
+    }
+
+    #[test]
+    fn normalize_url_lowercases_host() {
+        assert_eq!(normalize_url("https://FurAffinity.NET/view/1"), "https://furaffinity.net/view/1");
+    }
+
+    #[test]
+    fn normalize_url_drops_default_ports() {
+        assert_eq!(normalize_url("https://example.com:443/a"), "https://example.com/a");
+        assert_eq!(normalize_url("http://example.com:80/a"), "https://example.com/a");
+        assert_eq!(normalize_url("https://example.com:8080/a"), "https://example.com:8080/a");
+    }
+
+    #[test]
+    fn normalize_url_strips_trailing_slashes() {
+        assert_eq!(normalize_url("https://example.com/view/1/"), "https://example.com/view/1");
+        assert_eq!(normalize_url("https://example.com/"), "https://example.com");
+        assert_eq!(normalize_url("  https://example.com//  "), "https://example.com");
+    }
+
+    #[test]
+    fn normalize_url_keeps_query_and_fragment() {
+        assert_eq!(normalize_url("https://example.com/a/?b=1"), "https://example.com/a?b=1");
+        assert_eq!(normalize_url("https://example.com/a#c"), "https://example.com/a#c");
+        assert_eq!(normalize_url("https://example.com/?b=1#c"), "https://example.com/?b=1#c");
+    }
+
+    #[test]
+    fn normalize_url_leaves_www_alone() {
+        assert_eq!(normalize_url("https://www.furaffinity.net/view/1/"), "https://www.furaffinity.net/view/1");
+    }
+
+    #[test]
+    fn normalize_url_returns_unparsable_input_trimmed() {
+        assert_eq!(normalize_url("  not a url  "), "not a url");
+        assert_eq!(normalize_url("ftp://example.com/a/"), "ftp://example.com/a/");
+        assert_eq!(normalize_url(""), "");
+    }
+
+    fn parts<'a>(artist: &'a str, id: &'a str, md5: Option<&'a str>) -> FilenameParts<'a> {
+        FilenameParts { artist, source: "e621", id, md5, ext: "png" }
+    }
+
+    #[test]
+    fn render_filename_substitutes_every_token() {
+        let p = parts("some_artist", "123", Some("abc"));
+        assert_eq!(
+            render_filename("{artist}-{source}-{id}-{md5}.{ext}", &p).as_deref(),
+            Some("some_artist-e621-123-abc.png")
+        );
+    }
+
+    #[test]
+    fn render_filename_appends_missing_ext() {
+        assert_eq!(render_filename("{source}_{id}", &parts("a", "123", None)).as_deref(), Some("e621_123.png"));
+    }
+
+    #[test]
+    fn render_filename_rejects_unknown_or_unclosed_tokens() {
+        let p = parts("a", "123", None);
+        assert_eq!(render_filename("{id}_{title}", &p), None);
+        assert_eq!(render_filename("{id", &p), None);
+        assert_eq!(render_filename("{id}}", &p), None);
+    }
+
+    #[test]
+    fn render_filename_needs_id_or_md5() {
+        let p = parts("a", "123", Some("abc"));
+        assert_eq!(render_filename("{artist}.{ext}", &p), None);
+        assert_eq!(render_filename("{md5}", &p).as_deref(), Some("abc.png"));
+        // {md5} can't keep names unique when the hash isn't known
+        assert_eq!(render_filename("{md5}", &parts("a", "123", None)), None);
+    }
+
+    #[test]
+    fn render_filename_rejects_separators_in_literal_text() {
+        let p = parts("a", "123", None);
+        assert_eq!(render_filename("sub/{id}", &p), None);
+        assert_eq!(render_filename("sub\\{id}", &p), None);
+        assert_eq!(render_filename("{id}/x", &p), None);
+    }
+
+    #[test]
+    fn render_filename_sanitizes_interpolated_values() {
+        let p = parts("../evil/na:me", "1<2>|3", None);
+        assert_eq!(render_filename("{artist}_{id}", &p).as_deref(), Some("evilname_123.png"));
+        let p = parts("two words", "C:\\x?*\"", None);
+        assert_eq!(render_filename("{artist}_{id}", &p).as_deref(), Some("two_words_Cx.png"));
+        assert_eq!(sanitize_filename_value(" ..hidden. "), "hidden");
+    }
+
+    fn settings_conn(template: Option<&str>) -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        db::init_schema(&conn).unwrap();
+        if let Some(t) = template {
+            settings_set(&conn, "filename_template", t).unwrap();
+        }
+        conn
+    }
+
+    #[test]
+    fn filename_for_uses_the_setting_when_it_renders() {
+        let conn = settings_conn(Some("{id}_{artist}"));
+        assert_eq!(filename_for(&conn, DEFAULT_FILENAME_TEMPLATE, &parts("a", "123", None)), "123_a.png");
+    }
+
+    #[test]
+    fn filename_for_falls_back_when_unset_or_invalid() {
+        let p = parts("a", "123", None);
+        assert_eq!(filename_for(&settings_conn(None), DEFAULT_FILENAME_TEMPLATE, &p), "a_e621_123.png");
+        assert_eq!(filename_for(&settings_conn(Some("{artist}")), DEFAULT_FILENAME_TEMPLATE, &p), "a_e621_123.png");
+        assert_eq!(filename_for(&settings_conn(Some("{id}/{artist}")), FA_UPGRADE_FILENAME_TEMPLATE, &p), "e621_123.png");
+        // neither renders: bare id
+        assert_eq!(filename_for(&settings_conn(None), "{artist}", &p), "123.png");
+    }
+
+    #[test]
+    fn parse_e621_post_id_accepts_known_hosts() {
+        assert_eq!(parse_e621_post_id("https://e621.net/posts/123?q=fox#top", None), Some(123));
+        assert_eq!(parse_e621_post_id("www.e926.net/post/show/45", None), Some(45));
+        assert_eq!(parse_e621_post_id("https://example.com/posts/1", None), None);
+        assert_eq!(parse_e621_post_id("https://e621.net/pools/1", None), None);
+    }
+
+    #[test]
+    fn parse_e621_post_id_accepts_the_configured_base_host() {
+        assert_eq!(parse_e621_post_id("http://localhost:8080/posts/7", Some("localhost")), Some(7));
+        assert_eq!(parse_e621_post_id("https://Mirror.example/posts/7", Some("mirror.example")), Some(7));
+        assert_eq!(parse_e621_post_id("https://other.example/posts/7", Some("mirror.example")), None);
+    }
+
+    #[test]
+    fn backfill_sizes_fills_only_files_on_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("media")).unwrap();
+        fs::write(dir.path().join("media/a.png"), b"12345").unwrap();
+        let conn = settings_conn(None);
+        conn.execute_batch(
+            r#"
+            INSERT INTO items (source, source_id, file_rel, added_at) VALUES ('e621', '1', 'media/a.png', '2024-01-01');
+            INSERT INTO items (source, source_id, file_rel, added_at) VALUES ('e621', '2', 'media/gone.png', '2024-01-01');
+            "#,
+        ).unwrap();
+
+        assert_eq!(backfill_sizes(dir.path(), &conn).unwrap(), 1);
+        let sizes: Vec<Option<i64>> = conn
+            .prepare("SELECT size_bytes FROM items ORDER BY source_id").unwrap()
+            .query_map([], |r| r.get(0)).unwrap()
+            .collect::<Result<_, _>>().unwrap();
+        assert_eq!(sizes, vec![Some(5), None]);
+        assert_eq!(backfill_sizes(dir.path(), &conn).unwrap(), 0);
+    }
+
+    #[test]
+    fn relocate_batch_undoes_moves_when_a_row_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        library::ensure_layout(dir.path()).unwrap();
+        fs::write(dir.path().join("media/a.png"), b"a").unwrap();
+        fs::write(dir.path().join("media/b.png"), b"b").unwrap();
+        let conn = settings_conn(None);
+        conn.execute_batch(
+            r#"
+            INSERT INTO items (item_id, source, source_id, file_rel, added_at) VALUES (1, 'e621', '1', 'media/a.png', '2024-01-01');
+            INSERT INTO items (item_id, source, source_id, file_rel, added_at) VALUES (2, 'e621', '2', 'media/b.png', '2024-01-01');
+            "#,
+        ).unwrap();
+        let items = vec![(1, "media/a.png".to_string()), (2, "media/b.png".to_string())];
+
+        let res = relocate_batch(dir.path(), &conn, &items, library::trash_rel, |tx, id| {
+            if id == 2 {
+                return Err(AppError::Other("boom".into()));
+            }
+            tx.execute("UPDATE items SET trashed_at = 'now' WHERE item_id = ?", [id])?;
+            Ok(())
+        });
+
+        assert!(res.is_err());
+        assert!(dir.path().join("media/a.png").is_file());
+        assert!(dir.path().join("media/b.png").is_file());
+        let rows: Vec<(String, Option<String>)> = conn
+            .prepare("SELECT file_rel, trashed_at FROM items ORDER BY item_id").unwrap()
+            .query_map([], |r| Ok((r.get(0)?, r.get(1)?))).unwrap()
+            .collect::<Result<_, _>>().unwrap();
+        assert_eq!(rows, vec![("media/a.png".to_string(), None), ("media/b.png".to_string(), None)]);
+    }
+
+    #[test]
+    fn reindex_item_tracks_tag_changes() {
+        let conn = settings_conn(None);
+        conn.execute(
+            "INSERT INTO items (source, source_id, file_rel, added_at) VALUES ('e621', '1', 'media/a.png', '2024-01-01')",
+            [],
+        ).unwrap();
+        let item_id = conn.last_insert_rowid();
+        let matches = |term: &str| -> u32 {
+            conn.query_row("SELECT COUNT(*) FROM fts_items WHERE fts_items MATCH ?", [term], |r| r.get(0)).unwrap()
+        };
+
+        for name in ["fox", "forest"] {
+            let tag_id = upsert_tag(&conn, name, "general").unwrap();
+            conn.execute("INSERT INTO item_tags (item_id, tag_id) VALUES (?, ?)", [item_id, tag_id]).unwrap();
+        }
+        db::reindex_item(&conn, item_id).unwrap();
+        assert_eq!(matches("tags:fox"), 1);
+        assert_eq!(matches("tags:forest"), 1);
+
+        conn.execute("DELETE FROM item_tags WHERE item_id = ?", [item_id]).unwrap();
+        db::reindex_item(&conn, item_id).unwrap();
+        assert_eq!(matches("tags:fox"), 0);
+        let rows: u32 = conn.query_row("SELECT COUNT(*) FROM fts_items WHERE item_id = ?", [item_id], |r| r.get(0)).unwrap();
+        assert_eq!(rows, 1);
+    }
+}
@@ -1,32 +1,73 @@
+use crate::error::AppError;
 use serde::{Deserialize, Serialize};
 use std::{fs, path::PathBuf};
 use tauri::{AppHandle, Manager};
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct AppConfig {
+  // The active root, mirrored from `libraries` on save so older builds (and
+  // the frontend's get_config) keep reading a single path
   pub library_root: Option<String>,
+  // Every library the user has opened, in the order they were added
+  #[serde(default)]
+  pub libraries: Vec<String>,
+  // Index into `libraries`; None when no library is selected
+  #[serde(default)]
+  pub active: Option<usize>,
 }
 
-fn config_path(app: &AppHandle) -> Result<PathBuf, String> {
-  let dir = app
-    .path()
-    .app_data_dir()
-    .map_err(|e| e.to_string())?;
-  fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+impl AppConfig {
+  pub fn active_root(&self) -> Option<&str> {
+    self.active.and_then(|i| self.libraries.get(i)).map(|s| s.as_str())
+  }
+
+  // Index of `root`, adding it to the list when it's new
+  pub fn add_library(&mut self, root: &str) -> usize {
+    match self.libraries.iter().position(|l| l == root) {
+      Some(i) => i,
+      None => {
+        self.libraries.push(root.to_string());
+        self.libraries.len() - 1
+      }
+    }
+  }
+
+  // Configs written before multiple libraries only have library_root
+  fn migrate(&mut self) {
+    if self.libraries.is_empty() {
+      if let Some(root) = self.library_root.clone() {
+        self.active = Some(self.add_library(&root));
+      }
+    }
+    if self.active.is_some_and(|i| i >= self.libraries.len()) {
+      self.active = None;
+    }
+    self.library_root = self.active_root().map(|s| s.to_string());
+  }
+}
+
+fn config_path(app: &AppHandle) -> Result<PathBuf, AppError> {
+  let dir = app.path().app_data_dir()?;
+  fs::create_dir_all(&dir)?;
   Ok(dir.join("config.json"))
 }
 
-pub fn load_config(app: &AppHandle) -> Result<AppConfig, String> {
+pub fn load_config(app: &AppHandle) -> Result<AppConfig, AppError> {
   let path = config_path(app)?;
   if !path.exists() {
     return Ok(AppConfig::default());
   }
-  let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
-  serde_json::from_str(&text).map_err(|e| e.to_string())
+  let text = fs::read_to_string(path)?;
+  let mut cfg: AppConfig = serde_json::from_str(&text)?;
+  cfg.migrate();
+  Ok(cfg)
 }
 
-pub fn save_config(app: &AppHandle, cfg: &AppConfig) -> Result<(), String> {
+pub fn save_config(app: &AppHandle, cfg: &AppConfig) -> Result<(), AppError> {
+  let mut cfg = cfg.clone();
+  cfg.migrate();
   let path = config_path(app)?;
-  let text = serde_json::to_string_pretty(cfg).map_err(|e| e.to_string())?;
-  fs::write(path, text).map_err(|e| e.to_string())
-}
\ No newline at end of file
+  let text = serde_json::to_string_pretty(&cfg)?;
+  fs::write(path, text)?;
+  Ok(())
+}
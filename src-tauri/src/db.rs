@@ -1,18 +1,111 @@
+use crate::error::AppError;
 use rusqlite::Connection;
-use std::path::Path;
-
-pub fn open(db_path: &Path) -> Result<Connection, String> {
-  let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
-  conn
-    .pragma_update(None, "journal_mode", "WAL")
-    .map_err(|e| e.to_string())?;
-  conn
-    .pragma_update(None, "foreign_keys", "ON")
-    .map_err(|e| e.to_string())?;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+pub fn open(db_path: &Path) -> Result<Connection, AppError> {
+  let conn = Connection::open(db_path)?;
+  // parallel downloads write from several connections; wait instead of failing with SQLITE_BUSY
+  conn.busy_timeout(std::time::Duration::from_secs(5))?;
+  conn.pragma_update(None, "journal_mode", "WAL")?;
+  // checkpoint every ~4 MB of WAL and shrink the file back afterwards, so long
+  // syncs don't leave a huge -wal behind
+  conn.pragma_update(None, "wal_autocheckpoint", 1000)?;
+  conn.pragma_update(None, "journal_size_limit", 64 * 1024 * 1024)?;
+  conn.pragma_update(None, "foreign_keys", "ON")?;
   Ok(conn)
 }
 
-pub fn init_schema(conn: &Connection) -> Result<(), String> {
+// Idle connections kept per database; more than this are closed on return
+const POOL_MAX_IDLE: usize = 8;
+
+// Reuses connections across commands instead of opening one per call. Lives in
+// Tauri state as Arc<Pool>; connections are keyed by database path, so each
+// library gets its own.
+#[derive(Default)]
+pub struct Pool {
+  idle: Mutex<HashMap<PathBuf, Vec<Connection>>>,
+  // databases init_schema has already run on in this process
+  migrated: Mutex<HashSet<PathBuf>>,
+}
+
+impl Pool {
+  // An idle connection for `db_path`, or a fresh one (with open()'s pragmas)
+  pub fn get(self: &Arc<Self>, db_path: &Path) -> Result<PooledConn, AppError> {
+    let reused = self.idle.lock().ok().and_then(|mut idle| idle.get_mut(db_path).and_then(|v| v.pop()));
+    let conn = match reused {
+      Some(c) => c,
+      None => open(db_path)?,
+    };
+    Ok(PooledConn { conn: Some(conn), db_path: db_path.to_path_buf(), pool: self.clone() })
+  }
+
+  // Runs init_schema once per database per process
+  pub fn ensure_schema(&self, conn: &Connection, db_path: &Path) -> Result<(), AppError> {
+    if self.migrated.lock().is_ok_and(|m| m.contains(db_path)) {
+      return Ok(());
+    }
+    init_schema(conn)?;
+    if let Ok(mut m) = self.migrated.lock() {
+      m.insert(db_path.to_path_buf());
+    }
+    Ok(())
+  }
+
+  // Closes the idle connections of a library that is no longer active
+  pub fn close(&self, db_path: &Path) {
+    if let Ok(mut idle) = self.idle.lock() {
+      idle.remove(db_path);
+    }
+  }
+}
+
+// Derefs to the Connection; goes back to the pool when dropped
+pub struct PooledConn {
+  conn: Option<Connection>,
+  db_path: PathBuf,
+  pool: Arc<Pool>,
+}
+
+impl std::ops::Deref for PooledConn {
+  type Target = Connection;
+  fn deref(&self) -> &Connection {
+    self.conn.as_ref().expect("connection present until drop")
+  }
+}
+
+impl std::ops::DerefMut for PooledConn {
+  fn deref_mut(&mut self) -> &mut Connection {
+    self.conn.as_mut().expect("connection present until drop")
+  }
+}
+
+impl Drop for PooledConn {
+  fn drop(&mut self) {
+    let Some(conn) = self.conn.take() else { return };
+    // a connection left inside a transaction isn't safe to hand out again
+    if !conn.is_autocommit() {
+      return;
+    }
+    if let Ok(mut idle) = self.pool.idle.lock() {
+      let conns = idle.entry(std::mem::take(&mut self.db_path)).or_default();
+      if conns.len() < POOL_MAX_IDLE {
+        conns.push(conn);
+      }
+    }
+  }
+}
+
+// Copies the WAL into the main file and truncates it. Returns the frames still in
+// the WAL afterwards (0 unless a reader kept part of it alive).
+pub fn checkpoint(conn: &Connection) -> Result<i64, AppError> {
+  let (_busy, log_frames, checkpointed): (i64, i64, i64) =
+    conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))?;
+  Ok((log_frames - checkpointed).max(0))
+}
+
+pub fn init_schema(conn: &Connection) -> Result<(), AppError> {
   conn.execute_batch(
     r#"
     CREATE TABLE IF NOT EXISTS items (
@@ -71,7 +164,7 @@ pub fn init_schema(conn: &Connection) -> Result<(), String> {
     );
 
     CREATE VIRTUAL TABLE IF NOT EXISTS fts_items
-    USING fts5(item_id UNINDEXED, text);
+    USING fts5(item_id UNINDEXED, tags, notes);
 
     CREATE TABLE IF NOT EXISTS settings (
       key   TEXT PRIMARY KEY,
@@ -88,22 +181,214 @@ pub fn init_schema(conn: &Connection) -> Result<(), String> {
     );
     CREATE INDEX IF NOT EXISTS idx_unavailable_seen_at ON unavailable_posts(seen_at);
 
+    CREATE TABLE IF NOT EXISTS collections (
+      collection_id INTEGER PRIMARY KEY,
+      name          TEXT NOT NULL UNIQUE COLLATE NOCASE,
+      created_at    TEXT NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS collection_items (
+      collection_id INTEGER NOT NULL,
+      item_id       INTEGER NOT NULL,
+      added_at      TEXT NOT NULL,
+      PRIMARY KEY (collection_id, item_id),
+      FOREIGN KEY (collection_id) REFERENCES collections(collection_id) ON DELETE CASCADE,
+      FOREIGN KEY (item_id) REFERENCES items(item_id) ON DELETE CASCADE
+    );
+    CREATE INDEX IF NOT EXISTS idx_collection_items_item ON collection_items(item_id);
+
+    -- e621 implications: items tagged `antecedent` are implicitly `consequent`
+    CREATE TABLE IF NOT EXISTS tag_implications (
+      antecedent TEXT NOT NULL,
+      consequent TEXT NOT NULL,
+      PRIMARY KEY (antecedent, consequent)
+    );
+    CREATE INDEX IF NOT EXISTS idx_tag_implications_consequent ON tag_implications(consequent);
+
+    -- e621 post notes (translation boxes), cached by e621_fetch_notes
+    CREATE TABLE IF NOT EXISTS e621_notes (
+      note_id  INTEGER PRIMARY KEY,
+      item_id  INTEGER NOT NULL,
+      x        INTEGER NOT NULL,
+      y        INTEGER NOT NULL,
+      width    INTEGER NOT NULL,
+      height   INTEGER NOT NULL,
+      body     TEXT NOT NULL,
+      FOREIGN KEY (item_id) REFERENCES items(item_id) ON DELETE CASCADE
+    );
+    CREATE INDEX IF NOT EXISTS idx_e621_notes_item ON e621_notes(item_id);
+
+    -- e621 user names behind uploader_id / approver_id, filled by e621_resolve_users
+    CREATE TABLE IF NOT EXISTS e621_users (
+      user_id INTEGER PRIMARY KEY,
+      name    TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS idx_e621_users_name ON e621_users(name COLLATE NOCASE);
+
+    -- posts.json entry as received, kept apart so list queries never carry it
+    CREATE TABLE IF NOT EXISTS item_raw (
+      item_id    INTEGER PRIMARY KEY,
+      json       TEXT NOT NULL,
+      fetched_at TEXT NOT NULL,
+      FOREIGN KEY (item_id) REFERENCES items(item_id) ON DELETE CASCADE
+    );
+
     "#,
-  )
-  .map_err(|e| e.to_string())?;
+  )?;
 
   // Migration: Add file_md5 column if it doesn't exist
-  let count: u32 = conn.query_row(
-      "SELECT COUNT(*) FROM pragma_table_info('items') WHERE name='file_md5'",
+  if add_column_if_missing(conn, "items", "file_md5", "TEXT")? {
+      // Create index for fast lookups
+      conn.execute("CREATE INDEX IF NOT EXISTS idx_items_md5 ON items(file_md5)", [])?;
+  }
+
+  // Migration: which sync run brought the item in
+  if add_column_if_missing(conn, "items", "sync_batch", "TEXT")? {
+      conn.execute("CREATE INDEX IF NOT EXISTS idx_items_sync_batch ON items(sync_batch)", [])?;
+  }
+
+  // Migration: vote split, filled in by refresh_scores
+  add_column_if_missing(conn, "items", "score_up", "INTEGER")?;
+  add_column_if_missing(conn, "items", "score_down", "INTEGER")?;
+
+  // Migration: cached thumbnail location, set once ensure_thumbnail has produced it
+  add_column_if_missing(conn, "items", "thumb_rel", "TEXT")?;
+
+  // Migration: average color of the thumbnail ("#rrggbb"), a placeholder while it loads
+  add_column_if_missing(conn, "items", "thumb_color", "TEXT")?;
+
+  // Migration: pixel dimensions (NULL for videos and anything the decoder can't read)
+  add_column_if_missing(conn, "items", "width", "INTEGER")?;
+  add_column_if_missing(conn, "items", "height", "INTEGER")?;
+
+  // Migration: platform the item was favorited on, which can differ from `source`
+  // when the FA sync swapped in the e621 copy
+  if add_column_if_missing(conn, "items", "discovered_via", "TEXT")? {
+      conn.execute("UPDATE items SET discovered_via = 'furaffinity' WHERE source = 'furaffinity'", [])?;
+  }
+
+  // Migration: upstream state of e621 posts ('active' / 'deleted' / 'pending'), NULL until seen
+  add_column_if_missing(conn, "items", "remote_status", "TEXT")?;
+
+  // Migration: library-relative path of the FA file an e621 upgrade replaced,
+  // when keep_fa_original kept it
+  add_column_if_missing(conn, "items", "fa_original_rel", "TEXT")?;
+
+  // Migration: which e621 file was downloaded, 'full' or 'sample' (prefer_sample)
+  add_column_if_missing(conn, "items", "variant", "TEXT NOT NULL DEFAULT 'full'")?;
+
+  // Migration: free-form user notes, mirrored into fts_items for note: searches
+  add_column_if_missing(conn, "items", "notes", "TEXT")?;
+
+  // Migration: when the item was last opened in the viewer, NULL until then (seen:false)
+  add_column_if_missing(conn, "items", "last_viewed_at", "TEXT")?;
+
+  // Migration: e621 accounts that uploaded / approved the post (names live in e621_users)
+  add_column_if_missing(conn, "items", "uploader_id", "INTEGER")?;
+  add_column_if_missing(conn, "items", "approver_id", "INTEGER")?;
+
+  // Migration: set by verify_library when the file is missing or fails its md5;
+  // hidden from normal listings until it checks out again or the file is replaced
+  add_column_if_missing(conn, "items", "quarantined", "INTEGER NOT NULL DEFAULT 0")?;
+
+  // Migration: fts_items used to hold notes alone in a `text` column; FTS5 tables
+  // can't be altered, so recreate it with separate tags/notes columns and refill
+  let has_tags_column: u32 = conn.query_row(
+      "SELECT COUNT(*) FROM pragma_table_info('fts_items') WHERE name = 'tags'",
       [],
       |row| row.get(0),
-  ).unwrap_or(0);
-
-  if count == 0 {
-      conn.execute("ALTER TABLE items ADD COLUMN file_md5 TEXT", []).map_err(|e| e.to_string())?;
-      // Create index for fast lookups
-      conn.execute("CREATE INDEX IF NOT EXISTS idx_items_md5 ON items(file_md5)", []).map_err(|e| e.to_string())?;
+  )?;
+  if has_tags_column == 0 {
+      conn.execute_batch(
+        r#"
+        DROP TABLE fts_items;
+        CREATE VIRTUAL TABLE fts_items USING fts5(item_id UNINDEXED, tags, notes);
+        "#,
+      )?;
+      reindex_all(conn)?;
   }
 
+  // fts_items follows notes, tag renames and item deletes on its own. item_tags
+  // has no trigger: any code that inserts or deletes item_tags rows must call
+  // reindex_item for each item it touched, or tag: searches go stale. Once per
+  // item instead of once per row, so a 50-tag import doesn't rebuild it 50 times.
+  conn.execute_batch(
+    r#"
+    DROP TRIGGER IF EXISTS item_tags_fts_insert;
+    DROP TRIGGER IF EXISTS item_tags_fts_delete;
+
+    CREATE TRIGGER IF NOT EXISTS items_fts_delete AFTER DELETE ON items
+    BEGIN
+      DELETE FROM fts_items WHERE item_id = old.item_id;
+    END;
+
+    CREATE TRIGGER IF NOT EXISTS items_fts_notes AFTER UPDATE OF notes ON items
+    BEGIN
+      DELETE FROM fts_items WHERE item_id = new.item_id;
+      INSERT INTO fts_items (item_id, tags, notes)
+        SELECT new.item_id, (SELECT GROUP_CONCAT(t.name, ' ') FROM item_tags it JOIN tags t ON t.tag_id = it.tag_id WHERE it.item_id = new.item_id), new.notes;
+    END;
+
+    CREATE TRIGGER IF NOT EXISTS tags_fts_rename AFTER UPDATE OF name ON tags
+    BEGIN
+      DELETE FROM fts_items WHERE item_id IN (SELECT item_id FROM item_tags WHERE tag_id = new.tag_id);
+      INSERT INTO fts_items (item_id, tags, notes)
+        SELECT i.item_id, (SELECT GROUP_CONCAT(t.name, ' ') FROM item_tags it JOIN tags t ON t.tag_id = it.tag_id WHERE it.item_id = i.item_id), i.notes
+        FROM items i WHERE i.item_id IN (SELECT item_id FROM item_tags WHERE tag_id = new.tag_id);
+    END;
+    "#,
+  )?;
+
+  Ok(())
+}
+
+// Rebuilds one item's fts_items row from its current tags and notes. Call after
+// the item's item_tags rows change, in the same transaction.
+pub fn reindex_item(conn: &Connection, item_id: i64) -> Result<(), AppError> {
+  conn.execute("DELETE FROM fts_items WHERE item_id = ?", [item_id])?;
+  conn.execute(
+    r#"
+    INSERT INTO fts_items (item_id, tags, notes)
+    SELECT i.item_id,
+           (SELECT GROUP_CONCAT(t.name, ' ') FROM item_tags it JOIN tags t ON t.tag_id = it.tag_id WHERE it.item_id = i.item_id),
+           i.notes
+    FROM items i WHERE i.item_id = ?
+    "#,
+    [item_id],
+  )?;
   Ok(())
+}
+
+// Rebuilds fts_items from scratch for every item.
+pub fn reindex_all(conn: &Connection) -> Result<u32, AppError> {
+  let tx = conn.unchecked_transaction()?;
+  tx.execute("DELETE FROM fts_items", [])?;
+  let n = tx.execute(
+    r#"
+    INSERT INTO fts_items (item_id, tags, notes)
+    SELECT i.item_id,
+           (SELECT GROUP_CONCAT(t.name, ' ') FROM item_tags it JOIN tags t ON t.tag_id = it.tag_id WHERE it.item_id = i.item_id),
+           i.notes
+    FROM items i
+    "#,
+    [],
+  )?;
+  tx.execute("INSERT INTO fts_items (fts_items) VALUES ('optimize')", [])?;
+  tx.commit()?;
+  Ok(n as u32)
+}
+
+// Returns true when the column was added
+fn add_column_if_missing(conn: &Connection, table: &str, column: &str, decl: &str) -> Result<bool, AppError> {
+  let count: u32 = conn.query_row(
+      &format!("SELECT COUNT(*) FROM pragma_table_info('{table}') WHERE name=?"),
+      [column],
+      |row| row.get(0),
+  ).unwrap_or(0);
+
+  if count > 0 {
+      return Ok(false);
+  }
+  conn.execute(&format!("ALTER TABLE {table} ADD COLUMN {column} {decl}"), [])?;
+  Ok(true)
 }
\ No newline at end of file
@@ -0,0 +1,107 @@
+use serde::Serialize;
+use std::fmt;
+
+// Serialized as { "kind": "...", "message": "..." } so the UI can branch on `kind`
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", content = "message")]
+pub enum AppError {
+  NotConfigured(String),
+  // configured library root is missing, e.g. its external drive is unplugged
+  Unavailable(String),
+  Network(String),
+  NotFound(String),
+  Duplicate(String),
+  InvalidInput(String),
+  Io(String),
+  DiskFull(String),
+  Cancelled(String),
+  Db(String),
+  Other(String),
+}
+
+impl AppError {
+  pub fn message(&self) -> &str {
+    match self {
+      AppError::NotConfigured(m)
+      | AppError::Unavailable(m)
+      | AppError::Network(m)
+      | AppError::NotFound(m)
+      | AppError::Duplicate(m)
+      | AppError::InvalidInput(m)
+      | AppError::Io(m)
+      | AppError::DiskFull(m)
+      | AppError::Cancelled(m)
+      | AppError::Db(m)
+      | AppError::Other(m) => m,
+    }
+  }
+}
+
+impl fmt::Display for AppError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.message())
+  }
+}
+
+impl std::error::Error for AppError {}
+
+// Lets existing String-returning helpers keep working with `?`
+impl From<String> for AppError {
+  fn from(e: String) -> Self {
+    AppError::Other(e)
+  }
+}
+
+impl From<&str> for AppError {
+  fn from(e: &str) -> Self {
+    AppError::Other(e.to_string())
+  }
+}
+
+impl From<AppError> for String {
+  fn from(e: AppError) -> Self {
+    e.to_string()
+  }
+}
+
+impl From<rusqlite::Error> for AppError {
+  fn from(e: rusqlite::Error) -> Self {
+    match e {
+      rusqlite::Error::QueryReturnedNoRows => AppError::NotFound("No matching row".into()),
+      other => AppError::Db(other.to_string()),
+    }
+  }
+}
+
+impl From<std::io::Error> for AppError {
+  fn from(e: std::io::Error) -> Self {
+    match e.kind() {
+      std::io::ErrorKind::NotFound => AppError::NotFound(e.to_string()),
+      _ => AppError::Io(e.to_string()),
+    }
+  }
+}
+
+impl From<reqwest::Error> for AppError {
+  fn from(e: reqwest::Error) -> Self {
+    AppError::Network(e.to_string())
+  }
+}
+
+impl From<serde_json::Error> for AppError {
+  fn from(e: serde_json::Error) -> Self {
+    AppError::Other(e.to_string())
+  }
+}
+
+impl From<image::ImageError> for AppError {
+  fn from(e: image::ImageError) -> Self {
+    AppError::Other(e.to_string())
+  }
+}
+
+impl From<tauri::Error> for AppError {
+  fn from(e: tauri::Error) -> Self {
+    AppError::Other(e.to_string())
+  }
+}
@@ -2,12 +2,15 @@ use crate::{db, library};
 use rusqlite::{params, Connection};
 use scraper::{Html, Selector};
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use std::fs;
 use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use tauri::AppHandle;
 use tauri::Manager;
+use tokio::sync::Notify;
 
 // --- Data Structures ---
 
@@ -54,6 +57,9 @@ struct E621Post {
     score: serde_json::Value,
     created_at: String,
     sources: Option<Vec<String>>,
+    flags: Option<serde_json::Value>,
+    uploader_id: Option<i64>,
+    approver_id: Option<i64>,
 }
 
 #[derive(Deserialize)]
@@ -61,16 +67,62 @@ struct E621Response {
     posts: Vec<E621Post>,
 }
 
+// Cancel flag that can also be awaited, so sleeps and requests end as soon as it's set
+#[derive(Default)]
+pub struct CancelFlag {
+    flag: AtomicBool,
+    notify: Notify,
+}
+
+impl CancelFlag {
+    pub fn cancel(&self) {
+        self.flag.store(true, Ordering::SeqCst);
+        self.notify.notify_waiters();
+    }
+
+    pub fn reset(&self) {
+        self.flag.store(false, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.flag.load(Ordering::SeqCst)
+    }
+
+    async fn cancelled(&self) {
+        loop {
+            // register before checking so a cancel in between isn't missed
+            let notified = self.notify.notified();
+            if self.is_cancelled() { return; }
+            notified.await;
+        }
+    }
+
+    /// Run `fut` unless cancelled first; `None` means the sync should stop.
+    pub async fn race<F: std::future::Future>(&self, fut: F) -> Option<F::Output> {
+        if self.is_cancelled() { return None; }
+        tokio::select! {
+            out = fut => if self.is_cancelled() { None } else { Some(out) },
+            _ = self.cancelled() => None,
+        }
+    }
+}
+
 pub struct FAState {
     pub status: Arc<Mutex<FASyncStatus>>,
-    pub should_cancel: Arc<Mutex<bool>>,
+    pub cancel: Arc<CancelFlag>,
+}
+
+impl Default for FAState {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl FAState {
     pub fn new() -> Self {
         Self {
             status: Arc::new(Mutex::new(FASyncStatus::default())),
-            should_cancel: Arc::new(Mutex::new(false)),
+            cancel: Arc::new(CancelFlag::default()),
         }
     }
 }
@@ -95,36 +147,139 @@ fn check_local_md5(conn: &Connection, hash: &str) -> bool {
     count > 0
 }
 
-async fn check_e621_md5(client: &reqwest::Client, hash: &str) -> Option<E621Post> {
-    let url = format!("https://e621.net/posts.json?tags=md5:{}", hash);
-    match client.get(&url).header("User-Agent", "TailBurrow/0.2.1").send().await {
-        Ok(resp) => {
-            if let Ok(json) = resp.json::<E621Response>().await {
-                return json.posts.into_iter().next();
-            }
+// Posted date from the submission page. `.popup_date` carries the absolute date
+// in either its title or its text depending on the viewer's FA settings, and
+// newer layouts also add a unix `data-time`.
+fn parse_fa_posted_date(doc: &Html) -> Option<String> {
+    let sel = Selector::parse(".submission-id-sub-container .popup_date, span.popup_date").unwrap();
+    let el = doc.select(&sel).next()?;
+
+    if let Some(ts) = el.value().attr("data-time").and_then(|t| t.parse::<i64>().ok()) {
+        return chrono::DateTime::from_timestamp(ts, 0).map(|d| d.to_rfc3339());
+    }
+
+    let text = el.text().collect::<String>();
+    let candidates = [el.value().attr("title").unwrap_or(""), text.trim()];
+    for raw in candidates {
+        // "Jan 2nd, 2021 03:04 PM" -> "Jan 2, 2021 03:04 PM"
+        let cleaned = raw
+            .split_whitespace()
+            .map(|w| {
+                let comma = if w.ends_with(',') { "," } else { "" };
+                let core = w.trim_end_matches(',');
+                ["st", "nd", "rd", "th"]
+                    .iter()
+                    .find_map(|suf| core.strip_suffix(suf))
+                    .filter(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+                    .map(|n| format!("{}{}", n, comma))
+                    .unwrap_or_else(|| w.to_string())
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        if let Ok(dt) = chrono::NaiveDateTime::parse_from_str(&cleaned, "%b %d, %Y %I:%M %p") {
+            return Some(dt.and_utc().to_rfc3339());
         }
-        Err(_) => {}
-    };
+    }
+    None
+}
+
+async fn check_e621_md5(client: &reqwest::Client, base_url: &str, hash: &str) -> Option<E621Post> {
+    let url = format!("{}/posts.json?tags=md5:{}", base_url, hash);
+    if let Ok(resp) = client.get(&url).send().await {
+        if let Ok(json) = resp.json::<E621Response>().await {
+            return json.posts.into_iter().next();
+        }
+    }
     None
 }
 
+pub const FA_SESSION_EXPIRED: &str = "FurAffinity session expired — update cookies";
+
+// Logged-out pages carry a login form and none of the account nav (username
+// link, logout form) that both the classic and modern layouts show when signed in
+fn fa_logged_in(doc: &Html) -> bool {
+    let login_form = Selector::parse("form[action*='/login']").unwrap();
+    let account_nav = Selector::parse("#my-username, a[href*='/logout'], form[action*='/logout']").unwrap();
+    doc.select(&account_nav).next().is_some() && doc.select(&login_form).next().is_none()
+}
+
+const FA_FETCH_ATTEMPTS: u32 = 3;
+const FA_RETRY_DELAY: Duration = Duration::from_secs(2);
+// Favorites pages that fail in a row before the run gives up
+const FA_MAX_PAGE_FAILURES: u32 = 3;
+
+// GET with the session cookie, retrying network errors, 429 and 5xx with a growing
+// delay. None when cancelled; Err carries the last failure for the status line.
+async fn fetch_with_retry(
+    client: &reqwest::Client,
+    url: &str,
+    cookie: &str,
+    cancel: &CancelFlag,
+) -> Option<Result<Vec<u8>, String>> {
+    let mut last_err = String::new();
+    for attempt in 0..FA_FETCH_ATTEMPTS {
+        if attempt > 0 {
+            cancel.race(tokio::time::sleep(FA_RETRY_DELAY * attempt)).await?;
+        }
+
+        let resp = match cancel.race(client.get(url).header("Cookie", cookie).send()).await? {
+            Ok(r) => r,
+            Err(e) => {
+                last_err = format!("{}: {}", url, e);
+                continue;
+            }
+        };
+
+        let status = resp.status();
+        if !status.is_success() {
+            last_err = format!("{}: HTTP {}", url, status);
+            if status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+                continue;
+            }
+            return Some(Err(last_err));
+        }
+
+        match cancel.race(resp.bytes()).await? {
+            Ok(b) => return Some(Ok(b.to_vec())),
+            Err(e) => last_err = format!("{}: {}", url, e),
+        }
+    }
+    Some(Err(last_err))
+}
+
 // --- Main Logic ---
 
+// Extension from the URL's last path segment, then checked against the bytes
+fn fa_file_ext(download_url: &str, bytes: &[u8]) -> String {
+    let url_path = download_url.split(['?', '#']).next().unwrap_or("");
+    let ext = url_path
+        .rsplit('/')
+        .next()
+        .and_then(|name| name.rsplit_once('.'))
+        .map(|(_, e)| e.to_lowercase())
+        .filter(|e| !e.is_empty())
+        .unwrap_or_else(|| "jpg".to_string());
+    match crate::commands::corrected_ext(infer::get(bytes), &ext) {
+        Some(real) => real.to_string(),
+        None => ext,
+    }
+}
+
 pub async fn run_sync(app: AppHandle, cookie_a: String, cookie_b: String, stop_after: u32) {
     let state = app.state::<FAState>();
     
     {
         let mut s = state.status.lock().unwrap();
         *s = FASyncStatus { running: true, ..Default::default() };
-        *state.should_cancel.lock().unwrap() = false;
+        state.cancel.reset();
     }
+    let cancel = state.cancel.clone();
 
     let fa_client = reqwest::Client::builder()
         .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36")
         .build()
         .unwrap();
 
-    let e621_client = reqwest::Client::new();
     let cookie_header = format!("a={}; b={}", cookie_a, cookie_b);
 
     let root = match crate::commands::get_root(&app) {
@@ -137,16 +292,49 @@ pub async fn run_sync(app: AppHandle, cookie_a: String, cookie_b: String, stop_a
         },
     };
     let db_path = library::db_path(&root);
+    let pool = app.state::<std::sync::Arc<db::Pool>>().inner().clone();
+
+    // FA needs the browser-like UA above; e621 gets the descriptive one its API rules ask for
+    let e621_ua = pool.get(&db_path)
+        .map(|conn| crate::commands::e621_user_agent(&conn))
+        .unwrap_or_else(|_| crate::commands::build_user_agent(None));
+    let e621_base = pool.get(&db_path)
+        .map(|conn| crate::commands::e621_base_url(&conn))
+        .unwrap_or_else(|_| crate::commands::E621_DEFAULT_BASE_URL.to_string());
+    let pacing = pool.get(&db_path)
+        .map(|conn| crate::commands::load_sync_pacing(&conn))
+        .unwrap_or_default();
+    let keep_fa_original = pool.get(&db_path)
+        .map(|conn| crate::commands::keep_fa_original(&conn))
+        .unwrap_or(false);
+    let fa_delay = Duration::from_millis(pacing.fa_delay_ms);
+    let e621_delay = Duration::from_millis(pacing.e621_delay_ms);
+
+    let e621_client = reqwest::Client::builder()
+        .user_agent(e621_ua)
+        .build()
+        .unwrap();
 
     let media_dir = root.join("media");
     if !media_dir.exists() {
-        let _ = fs::create_dir_all(&media_dir);
+        if let Err(e) = fs::create_dir_all(&media_dir) {
+            log::error!("Creating {} failed: {}", media_dir.display(), e);
+        }
     }
 
+    let sync_batch = crate::commands::new_sync_batch("fa");
+    let started = std::time::Instant::now();
     let mut page = 1;
+    // FA can repeat a submission on adjacent pages while the list shifts
+    let mut seen_ids: HashSet<String> = HashSet::new();
     
+    // set when the run has to stop early (e.g. out of disk space)
+    let mut fatal: Option<String> = None;
+    let mut last_error: Option<String> = None;
+    let mut page_failures = 0u32;
+
     loop {
-        if *state.should_cancel.lock().unwrap() { break; }
+        if cancel.is_cancelled() || fatal.is_some() { break; }
 
         {
             let mut s = state.status.lock().unwrap();
@@ -159,33 +347,60 @@ pub async fn run_sync(app: AppHandle, cookie_a: String, cookie_b: String, stop_a
             format!("https://www.furaffinity.net/controls/favorites/{}/", page)
         };
 
-        let resp = match fa_client.get(&url).header("Cookie", &cookie_header).send().await {
-            Ok(r) => r,
-            Err(_) => break,
+        let html = match fetch_with_retry(&fa_client, &url, &cookie_header, &cancel).await {
+            None => break,
+            Some(Ok(body)) => {
+                page_failures = 0;
+                String::from_utf8_lossy(&body).into_owned()
+            }
+            Some(Err(e)) => {
+                // A failed page isn't an empty one; move on instead of ending the run
+                page_failures += 1;
+                {
+                    let mut s = state.status.lock().unwrap();
+                    s.errors += 1;
+                    s.current_message = format!("Error on page {}: {}", page, e);
+                }
+                log::warn!("FA favorites page {} failed: {}", page, e);
+                last_error = Some(e);
+                if page_failures >= FA_MAX_PAGE_FAILURES {
+                    fatal = last_error.clone();
+                    break;
+                }
+                page += 1;
+                if page > 50 { break; }
+                continue;
+            }
         };
-
-        let html = resp.text().await.unwrap_or_default();
         
-        let ids: Vec<String> = {
+        let (ids, logged_in): (Vec<String>, bool) = {
             let document = Html::parse_document(&html);
             let figure_selector = Selector::parse("figure.t-image").unwrap();
             
-            document.select(&figure_selector)
+            let ids = document.select(&figure_selector)
                 .filter_map(|figure| {
                     figure.value().attr("id")
                         .map(|id| id.replace("sid-", ""))
                 })
-                .collect()
+                .collect();
+            (ids, fa_logged_in(&document))
         };
 
+        // An expired cookie serves the logged-out page, which also has no figures
+        if ids.is_empty() && !logged_in {
+            fatal = Some(FA_SESSION_EXPIRED.to_string());
+            break;
+        }
+
         if ids.is_empty() {
-            println!("No favorites found on page {}. Ending.", page);
+            log::info!("No favorites found on page {}. Ending.", page);
             break; 
         }
 
         for id_str in ids {
-            if *state.should_cancel.lock().unwrap() { break; }
+            if cancel.is_cancelled() { break; }
             if id_str.is_empty() { continue; }
+            if !seen_ids.insert(id_str.clone()) { continue; }
 
             {
                 let mut s = state.status.lock().unwrap();
@@ -193,7 +408,7 @@ pub async fn run_sync(app: AppHandle, cookie_a: String, cookie_b: String, stop_a
                 s.current_message = format!("Processing #{}...", id_str);
             }
 
-            let conn = db::open(&db_path).unwrap();
+            let conn = pool.get(&db_path).unwrap();
 
             // 1. FAST LOCAL CHECK
             if check_db_exists(&conn, "furaffinity", &id_str) {
@@ -202,29 +417,31 @@ pub async fn run_sync(app: AppHandle, cookie_a: String, cookie_b: String, stop_a
                 continue; 
             }
 
-            tokio::time::sleep(Duration::from_millis(800)).await; 
+            if cancel.race(tokio::time::sleep(fa_delay)).await.is_none() { break; }
 
             // 2. Fetch Submission Page
             let view_url = format!("https://www.furaffinity.net/view/{}/", id_str);
-            let view_resp = match fa_client.get(&view_url).header("Cookie", &cookie_header).send().await {
-                Ok(r) => r,
-                Err(_) => {
-                    state.status.lock().unwrap().errors += 1;
+            let view_html = match fetch_with_retry(&fa_client, &view_url, &cookie_header, &cancel).await {
+                None => break,
+                Some(Ok(body)) => String::from_utf8_lossy(&body).into_owned(),
+                Some(Err(e)) => {
+                    let mut s = state.status.lock().unwrap();
+                    s.errors += 1;
+                    s.current_message = format!("Error on #{}: {}", id_str, e);
+                    log::warn!("FA submission {} failed: {}", id_str, e);
+                    last_error = Some(e);
                     continue;
                 }
             };
-            
-            let view_html = view_resp.text().await.unwrap_or_default();
-            
+
             // Extract Data
-            let (download_url, fa_tags, artist_name, rating_char) = {
+            let (download_url, fa_tags, artist_name, rating_char, posted_at) = {
                 let view_doc = Html::parse_document(&view_html);
+                let posted_at = parse_fa_posted_date(&view_doc);
                 
                 let download_selector = Selector::parse("div.download > a").unwrap();
-                let dl = match view_doc.select(&download_selector).next() {
-                    Some(el) => Some(format!("https:{}", el.value().attr("href").unwrap_or(""))),
-                    None => None,
-                };
+                let dl = view_doc.select(&download_selector).next()
+                    .map(|el| format!("https:{}", el.value().attr("href").unwrap_or("")));
 
                 let tag_selector = Selector::parse("section.tags-row span.tags a").unwrap();
                 let tags: Vec<String> = view_doc.select(&tag_selector)
@@ -274,24 +491,35 @@ pub async fn run_sync(app: AppHandle, cookie_a: String, cookie_b: String, stop_a
                     _ => "s",
                 };
                 
-                (dl, tags, clean_artist, rating_char.to_string())
+                (dl, tags, clean_artist, rating_char.to_string(), posted_at)
             };
 
             let download_url = match download_url {
                 Some(url) => url,
                 None => {
+                    log::warn!("FA submission {} has no download link ({})", id_str, view_url);
                     state.status.lock().unwrap().errors += 1;
                     continue;
                 }
             };
 
+            if let Err(e) = crate::commands::ensure_free_space(&conn, &root) {
+                fatal = Some(e.to_string());
+                break;
+            }
+
             // 3. Download FA File
-            let fa_bytes = match fa_client.get(&download_url).header("Cookie", &cookie_header).send().await {
-                Ok(r) => match r.bytes().await {
-                    Ok(b) => b,
-                    Err(_) => continue,
-                },
-                Err(_) => continue,
+            let fa_bytes = match fetch_with_retry(&fa_client, &download_url, &cookie_header, &cancel).await {
+                None => break,
+                Some(Ok(b)) => b,
+                Some(Err(e)) => {
+                    let mut s = state.status.lock().unwrap();
+                    s.errors += 1;
+                    s.current_message = format!("Error downloading #{}: {}", id_str, e);
+                    log::warn!("FA download for {} failed: {}", id_str, e);
+                    last_error = Some(e);
+                    continue;
+                }
             };
 
             let digest = md5::compute(&fa_bytes);
@@ -305,9 +533,14 @@ pub async fn run_sync(app: AppHandle, cookie_a: String, cookie_b: String, stop_a
             }
 
             // 5. CHECK E621
-            tokio::time::sleep(Duration::from_millis(500)).await;
-            
-            if let Some(e621_post) = check_e621_md5(&e621_client, &hash_str).await {
+            if cancel.race(tokio::time::sleep(e621_delay)).await.is_none() { break; }
+
+            let e621_match = match cancel.race(check_e621_md5(&e621_client, &e621_base, &hash_str)).await {
+                Some(m) => m,
+                None => break,
+            };
+
+            if let Some(e621_post) = e621_match {
                 // --- FOUND ON E621 (UPGRADE PATH) ---
                 
                 // Double check ID to prevent unique constraint crash
@@ -318,41 +551,84 @@ pub async fn run_sync(app: AppHandle, cookie_a: String, cookie_b: String, stop_a
                 }
 
                 if let Some(file_url) = e621_post.file.url {
-                    let e621_bytes = match e621_client.get(&file_url).header("User-Agent", "TailBurrow/0.2.1").send().await {
-                        Ok(r) => match r.bytes().await {
-                            Ok(b) => b,
-                            Err(_) => continue,
-                        },
-                        Err(_) => continue,
+                    let e621_download = async {
+                        e621_client.get(&file_url).send().await?.bytes().await
+                    };
+                    let e621_bytes = match cancel.race(e621_download).await {
+                        None => break,
+                        Some(Ok(b)) => b,
+                        Some(Err(e)) => {
+                            log::warn!("e621 download for post {} ({}) failed: {}", e621_post.id, file_url, e);
+                            continue;
+                        }
                     };
 
-                    let ext = e621_post.file.ext.unwrap_or("jpg".to_string());
-                    let filename = format!("e621_{}.{}", e621_post.id, ext);
+                    let mut ext = e621_post.file.ext.unwrap_or("jpg".to_string());
+                    if let Some(real) = crate::commands::corrected_ext(infer::get(&e621_bytes), &ext) {
+                        ext = real.to_string();
+                    }
+                    let primary_artist = crate::commands::sanitize_slug(&crate::commands::pick_primary_artist(&e621_post.tags.artist));
+                    let post_id = e621_post.id.to_string();
+                    let filename = crate::commands::unique_media_name(&media_dir, &crate::commands::filename_for(&conn, crate::commands::FA_UPGRADE_FILENAME_TEMPLATE, &crate::commands::FilenameParts {
+                        artist: &primary_artist,
+                        source: "e621",
+                        id: &post_id,
+                        md5: e621_post.file.md5.as_deref(),
+                        ext: &ext,
+                    }));
                     let target_path = media_dir.join(&filename);
-                    if let Ok(mut file) = fs::File::create(&target_path) {
-                        let _ = file.write_all(&e621_bytes);
+                    if let Err(e) = fs::File::create(&target_path).and_then(|mut file| file.write_all(&e621_bytes)) {
+                        log::error!("Writing {} failed: {}", target_path.display(), e);
                     }
 
                     let file_rel_for_thumb = format!("media/{}", filename);
-                    crate::commands::generate_and_save_thumb(&root, &file_rel_for_thumb);
+                    crate::commands::generate_and_save_thumb(&root, &file_rel_for_thumb, crate::commands::thumb_width(&conn));
 
                     let now = chrono::Local::now().to_rfc3339();
                     let file_rel = format!("media/{}", filename);
+                    let mime = crate::commands::detect_mime(&e621_bytes);
+                    let dims = crate::commands::image_dimensions_of(&e621_bytes);
                     let tx = conn.unchecked_transaction().unwrap();
 
                     // PROTECTED INSERT
                     let insert_res = tx.execute(
-                        "INSERT INTO items (source, source_id, file_rel, file_md5, ext, rating, fav_count, score_total, created_at, added_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
-                        params!["e621", e621_post.id.to_string(), file_rel, hash_str, ext, e621_post.rating, e621_post.fav_count, 0, e621_post.created_at, now],
+                        "INSERT INTO items (source, source_id, file_rel, file_md5, ext, mime, rating, fav_count, score_total, created_at, added_at, primary_artist, sync_batch, width, height, discovered_via, remote_status, size_bytes, uploader_id, approver_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, 'furaffinity', ?16, ?17, ?18, ?19)",
+                        params!["e621", e621_post.id.to_string(), file_rel, hash_str, ext, mime, e621_post.rating, e621_post.fav_count, 0, e621_post.created_at, now, primary_artist, sync_batch, dims.map(|d| d.0), dims.map(|d| d.1), crate::commands::e621_remote_status(e621_post.flags.as_ref()), e621_bytes.len() as i64, e621_post.uploader_id, e621_post.approver_id],
                     );
 
                     if insert_res.is_err() {
-                        println!("Skipping duplicate e621 insert: {}", e621_post.id);
+                        log::info!("Skipping duplicate e621 insert: {}", e621_post.id);
                         continue; // Skip if db constraint fails
                     }
 
                     let item_id = tx.last_insert_rowid();
 
+                    // keep_fa_original: the exact favorited file goes next to the library, linked from the item
+                    if keep_fa_original {
+                        let original_dir = root.join(crate::library::FA_ORIGINALS_DIR);
+                        let ext = fa_file_ext(&download_url, &fa_bytes);
+                        let name = crate::commands::unique_media_name(&original_dir, &crate::commands::filename_for(&conn, crate::commands::DEFAULT_FILENAME_TEMPLATE, &crate::commands::FilenameParts {
+                            artist: &artist_name,
+                            source: "fa",
+                            id: &id_str,
+                            md5: Some(&hash_str),
+                            ext: &ext,
+                        }));
+                        let written = fs::create_dir_all(&original_dir)
+                            .and_then(|_| fs::File::create(original_dir.join(&name)))
+                            .and_then(|mut file| file.write_all(&fa_bytes));
+                        match written {
+                            Ok(()) => {
+                                let original_rel = format!("{}/{}", crate::library::FA_ORIGINALS_DIR, name);
+                                if let Err(e) = tx.execute("UPDATE items SET fa_original_rel = ? WHERE item_id = ?", params![original_rel, item_id]) {
+                                    log::error!("Linking FA original of {} failed: {}", id_str, e);
+                                    let _ = fs::remove_file(original_dir.join(&name));
+                                }
+                            }
+                            Err(e) => log::error!("Keeping FA original of {} failed: {}", id_str, e),
+                        }
+                    }
+
                     // Add Tags (Correctly Typed)
                     let insert_tags_with_type = |tags: Vec<String>, t_type: &str, tx: &rusqlite::Transaction| {
                         for tag in tags {
@@ -371,6 +647,7 @@ pub async fn run_sync(app: AppHandle, cookie_a: String, cookie_b: String, stop_a
                     insert_tags_with_type(e621_post.tags.general, "general", &tx);
                     insert_tags_with_type(e621_post.tags.meta, "meta", &tx);
                     insert_tags_with_type(e621_post.tags.lore, "lore", &tx);
+                    db::reindex_item(&tx, item_id).unwrap();
 
                     // Add Sources
                     let e621_src = format!("https://e621.net/posts/{}", e621_post.id);
@@ -378,8 +655,9 @@ pub async fn run_sync(app: AppHandle, cookie_a: String, cookie_b: String, stop_a
                     let sid1: i64 = tx.query_row("SELECT source_row_id FROM sources WHERE url = ?", [&e621_src], |r| r.get(0)).unwrap();
                     tx.execute("INSERT INTO item_sources (item_id, source_row_id) VALUES (?, ?)", [item_id, sid1]).unwrap();
 
-                    tx.execute("INSERT OR IGNORE INTO sources (url) VALUES (?)", [&view_url]).unwrap();
-                    let sid2: i64 = tx.query_row("SELECT source_row_id FROM sources WHERE url = ?", [&view_url], |r| r.get(0)).unwrap();
+                    let fa_src = crate::commands::normalize_url(&view_url);
+                    tx.execute("INSERT OR IGNORE INTO sources (url) VALUES (?)", [&fa_src]).unwrap();
+                    let sid2: i64 = tx.query_row("SELECT source_row_id FROM sources WHERE url = ?", [&fa_src], |r| r.get(0)).unwrap();
                     tx.execute("INSERT INTO item_sources (item_id, source_row_id) VALUES (?, ?)", [item_id, sid2]).unwrap();
 
                     tx.commit().unwrap();
@@ -396,33 +674,42 @@ pub async fn run_sync(app: AppHandle, cookie_a: String, cookie_b: String, stop_a
 
             // --- NOT ON E621 (EXCLUSIVE PATH) ---
             
-            let ext = download_url.split('.').last().unwrap_or("jpg");
-            let filename = format!("{}_fa_{}.{}", artist_name, id_str, ext);
+            let ext = fa_file_ext(&download_url, &fa_bytes);
+            let filename = crate::commands::unique_media_name(&media_dir, &crate::commands::filename_for(&conn, crate::commands::DEFAULT_FILENAME_TEMPLATE, &crate::commands::FilenameParts {
+                artist: &artist_name,
+                source: "fa",
+                id: &id_str,
+                md5: Some(&hash_str),
+                ext: &ext,
+            }));
             let target_path = media_dir.join(&filename);
 
-            if let Ok(mut file) = fs::File::create(&target_path) {
-                let _ = file.write_all(&fa_bytes);
+            if let Err(e) = fs::File::create(&target_path).and_then(|mut file| file.write_all(&fa_bytes)) {
+                log::error!("Writing {} failed: {}", target_path.display(), e);
             }
 
             let now = chrono::Local::now().to_rfc3339();
+            let mime = crate::commands::detect_mime(&fa_bytes);
+            let dims = crate::commands::image_dimensions_of(&fa_bytes);
             let tx = conn.unchecked_transaction().unwrap();
             let file_rel = format!("media/{}", filename);
 
             // PROTECTED INSERT
             let insert_res = tx.execute(
-                "INSERT INTO items (source, source_id, file_rel, file_md5, ext, rating, created_at, added_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
-                params!["furaffinity", id_str, file_rel, hash_str, ext, rating_char, now, now],
+                "INSERT INTO items (source, source_id, file_rel, file_md5, ext, mime, rating, created_at, added_at, primary_artist, sync_batch, width, height, discovered_via, size_bytes) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, 'furaffinity', ?14)",
+                params!["furaffinity", id_str, file_rel, hash_str, ext, mime, rating_char, posted_at.as_deref().unwrap_or(&now), now, artist_name, sync_batch, dims.map(|d| d.0), dims.map(|d| d.1), fa_bytes.len() as i64],
             );
 
             if insert_res.is_err() {
-                println!("Skipping duplicate FA insert: {}", id_str);
+                log::info!("Skipping duplicate FA insert: {}", id_str);
                 continue; 
             }
 
             let item_id = tx.last_insert_rowid();
 
-            tx.execute("INSERT OR IGNORE INTO sources (url) VALUES (?)", [&view_url]).unwrap();
-            let source_row_id: i64 = tx.query_row("SELECT source_row_id FROM sources WHERE url = ?", [&view_url], |r| r.get(0)).unwrap();
+            let fa_src = crate::commands::normalize_url(&view_url);
+            tx.execute("INSERT OR IGNORE INTO sources (url) VALUES (?)", [&fa_src]).unwrap();
+            let source_row_id: i64 = tx.query_row("SELECT source_row_id FROM sources WHERE url = ?", [&fa_src], |r| r.get(0)).unwrap();
             tx.execute("INSERT INTO item_sources (item_id, source_row_id) VALUES (?, ?)", [item_id, source_row_id]).unwrap();
 
             // Artist Tag
@@ -441,6 +728,7 @@ pub async fn run_sync(app: AppHandle, cookie_a: String, cookie_b: String, stop_a
                 let tag_id: i64 = tx.query_row("SELECT tag_id FROM tags WHERE name = ?", [&clean], |r| r.get(0)).unwrap();
                 tx.execute("INSERT OR IGNORE INTO item_tags (item_id, tag_id) VALUES (?, ?)", [item_id, tag_id]).unwrap();
             }
+            db::reindex_item(&tx, item_id).unwrap();
 
             tx.commit().unwrap();
 
@@ -463,7 +751,37 @@ pub async fn run_sync(app: AppHandle, cookie_a: String, cookie_b: String, stop_a
         if page > 50 { break; } 
     }
 
-    let mut s = state.status.lock().unwrap();
-    s.running = false;
-    s.current_message = "Done.".to_string();
+    let summary = {
+        let mut s = state.status.lock().unwrap();
+        s.running = false;
+        s.current_message = match &fatal {
+            Some(e) if e == FA_SESSION_EXPIRED => e.clone(),
+            Some(e) => format!("Stopped: {}", e),
+            None if cancel.is_cancelled() => "Cancelled.".to_string(),
+            None => match &last_error {
+                Some(e) => format!("Done with {} error(s). Last: {}", s.errors, e),
+                None => "Done.".to_string(),
+            },
+        };
+
+        crate::commands::SyncSummary {
+            source: "furaffinity".into(),
+            batch: sync_batch,
+            downloaded: s.imported + s.upgraded,
+            skipped: s.skipped_url + s.skipped_md5,
+            failed: s.errors,
+            imported: Some(s.imported),
+            upgraded: Some(s.upgraded),
+            cancelled: cancel.is_cancelled(),
+            error: fatal,
+            elapsed_secs: started.elapsed().as_secs(),
+            ..Default::default()
+        }
+    };
+    if let Some(e) = &summary.error {
+        log::error!("FA sync {} stopped: {}", summary.batch, e);
+    }
+    if let Err(e) = crate::commands::publish_sync_summary(&app, summary) {
+        log::warn!("Saving FA sync summary failed: {}", e);
+    }
 }
\ No newline at end of file
@@ -1,32 +1,57 @@
 mod commands;
 mod config;
 mod db;
+mod error;
 mod library;
+mod logging;
 pub mod fa; 
 
 use tauri::Manager; 
-use tauri_plugin_fs::FsExt;
 use std::sync::{Arc, Mutex};
 
 pub fn run() {
+  logging::init();
+
   tauri::Builder::default()
     .plugin(tauri_plugin_dialog::init())
     .plugin(tauri_plugin_shell::init())
+    .plugin(tauri_plugin_opener::init())
     .plugin(tauri_plugin_fs::init())
     .manage(Arc::new(Mutex::new(commands::SyncState::default())))
     .manage(crate::fa::FAState::new())
+    .manage(commands::MaintenanceState::default())
+    .manage(commands::ThumbnailPool::default())
+    .manage(Arc::new(db::Pool::default()))
     .setup(|app| {
       let handle = app.handle().clone();
 
       if let Ok(cfg) = crate::config::load_config(&handle) {
-        if let Some(root) = cfg.library_root {
+        if let Some(root) = cfg.active_root() {
           let root = std::path::PathBuf::from(root);
 
-          // Re-apply scopes on startup
-          let _ = handle.fs_scope().allow_directory(&root, true);
-          let _ = handle.asset_protocol_scope().allow_directory(&root, true);
+          if !root.is_dir() {
+            // unplugged drive: commands report Unavailable until it's back or re-selected
+            log::warn!("Library root {} is unavailable; skipping startup setup", root.display());
+          } else {
+            // Re-apply scopes on startup
+            logging::set_root(&root);
+            if let Err(e) = commands::allow_root_scopes(&handle, &root) {
+              log::error!("{}", e);
+            }
+
+            // A second instance just fails its commands with the lock error
+            if crate::library::acquire_lock(&root).is_ok() {
+              // leftovers from a crashed run; nothing is downloading yet
+              commands::sweep_part_files(&root, Some(commands::STALE_PART_AGE));
+              commands::spawn_size_backfill(handle.clone());
+            }
+          }
         }
       }
+      commands::spawn_lock_heartbeat(handle.clone());
+
+      let sync_state = app.state::<Arc<Mutex<commands::SyncState>>>().inner().clone();
+      commands::spawn_auto_sync_scheduler(handle.clone(), sync_state);
 
       Ok(())
     })
@@ -49,6 +74,7 @@ pub fn run() {
       commands::auto_clean_trash,
       commands::fa_get_cred_info,
       commands::update_item_rating,
+      commands::update_items_rating,
       commands::update_item_sources,
       commands::get_trash_count,
       commands::ensure_thumbnail,
@@ -62,7 +88,114 @@ pub fn run() {
       commands::e621_sync_status,
       commands::e621_sync_cancel,
       commands::e621_unavailable_list,
+      commands::get_auto_sync_interval,
+      commands::set_auto_sync_interval,
+      commands::reveal_in_explorer,
+      commands::get_redownload_trashed,
+      commands::set_redownload_trashed,
+      commands::open_source_page,
+      commands::list_sources,
+      commands::clear_thumbnail_cache,
+      commands::list_sync_batches,
+      commands::export_items,
+      commands::trash_items,
+      commands::restore_last_trashed,
+      commands::refresh_scores,
+      commands::get_sfw_mode,
+      commands::set_sfw_mode,
+      commands::backfill_md5,
+      commands::e621_sync_pause,
+      commands::e621_sync_resume,
+      commands::get_last_sync_summary,
+      commands::set_tag_type,
+      commands::update_item_tags_typed,
+      commands::export_jsonl,
+      commands::find_duplicate_items,
+      commands::merge_duplicate_items,
+      commands::get_sync_pacing,
+      commands::set_sync_pacing,
+      commands::library_health,
+      commands::e621_add_by_url,
+      commands::e621_favorites_diff,
+      commands::get_thumb_width,
+      commands::set_thumb_width,
+      commands::cancel_maintenance,
+      commands::rebuild_thumbnails,
+      commands::cleanup_temp,
+      commands::export_settings,
+      commands::import_settings,
+      commands::item_neighbors,
+      commands::get_filename_template,
+      commands::set_filename_template,
+      commands::trash_by_artist,
+      commands::related_tags,
+      commands::library_counts,
+      commands::reapply_scopes,
+      commands::list_missing_files,
+      commands::get_log_path,
+      commands::get_item,
+      commands::set_item_notes,
+      commands::list_collections,
+      commands::create_collection,
+      commands::rename_collection,
+      commands::delete_collection,
+      commands::add_to_collection,
+      commands::remove_from_collection,
+      commands::list_collection_items,
+      commands::get_item_raw,
+      commands::cancel_download,
+      commands::checkpoint_db,
+      commands::get_expand_implications,
+      commands::set_expand_implications,
+      commands::e621_sync_implications,
+      commands::export_search,
+      commands::rating_breakdown,
+      commands::add_derived_sources,
+      commands::get_skip_unsupported_formats,
+      commands::set_skip_unsupported_formats,
+      commands::replace_item_file,
+      commands::recompute_primary_artists,
+      commands::next_untagged,
+      commands::recompress_item,
+      commands::list_settings,
+      commands::set_setting,
+      commands::delete_setting,
+      commands::reindex_all,
+      commands::random_item,
+      commands::list_libraries,
+      commands::add_library,
+      commands::switch_library,
+      commands::get_prefer_sample,
+      commands::set_prefer_sample,
+      commands::list_extension_mismatches,
+      commands::normalize_extensions,
+      commands::item_tags_typed,
+      commands::get_keep_fa_original,
+      commands::set_keep_fa_original,
+      commands::trash_below_threshold,
+      commands::e621_fetch_notes,
+      commands::dedupe_sources,
+      commands::mark_viewed,
+      commands::get_e621_base_url,
+      commands::set_e621_base_url,
+      commands::recheck_e621_match,
+      commands::bulk_add_tag,
+      commands::bulk_remove_tag,
+      commands::e621_resolve_users,
+      commands::list_items_compact,
+      commands::verify_library,
+      commands::list_quarantined,
+      commands::release_quarantined,
     ])
-    .run(tauri::generate_context!())
-    .expect("error while running tauri application");
+    .build(tauri::generate_context!())
+    .expect("error while building tauri application")
+    .run(|handle, event| match event {
+      tauri::RunEvent::ExitRequested { api, code, .. } => commands::on_exit_requested(handle, &api, code),
+      tauri::RunEvent::Exit => {
+        if let Ok(root) = commands::get_root(handle) {
+          crate::library::release_lock(&root);
+        }
+      }
+      _ => {}
+    });
 }
@@ -1,12 +1,240 @@
-use std::{fs, path::{Path, PathBuf}};
+use std::{fs, io::Write, path::{Component, Path, PathBuf}, time::Duration};
 
 pub fn ensure_layout(root: &Path) -> Result<(), String> {
   fs::create_dir_all(root.join("db")).map_err(|e| e.to_string())?;
   fs::create_dir_all(root.join("media")).map_err(|e| e.to_string())?;
-  // Removed .trash creation
+  fs::create_dir_all(root.join(".trash").join("media")).map_err(|e| e.to_string())?;
   Ok(())
 }
 
+const TRASH_PREFIX: &str = ".trash/";
+
+// FA files kept alongside their e621 upgrade (keep_fa_original); outside media/
+// so they never show up as items of their own
+pub const FA_ORIGINALS_DIR: &str = "fa_originals";
+
+// Where a file lives while its item is trashed: media/x.png -> .trash/media/x.png
+pub fn trash_rel(file_rel: &str) -> String {
+  if file_rel.starts_with(TRASH_PREFIX) {
+    file_rel.to_string()
+  } else {
+    format!("{TRASH_PREFIX}{file_rel}")
+  }
+}
+
+// Inverse of trash_rel; rows trashed before files were moved are returned unchanged
+pub fn untrash_rel(file_rel: &str) -> String {
+  file_rel.strip_prefix(TRASH_PREFIX).unwrap_or(file_rel).to_string()
+}
+
 pub fn db_path(root: &Path) -> PathBuf {
   root.join("db").join("library.sqlite")
-}
\ No newline at end of file
+}
+
+// Resolve a library-relative path and make sure it can't escape the root.
+// The file itself doesn't have to exist yet.
+pub fn resolve_in_root(root: &Path, file_rel: &str) -> Result<PathBuf, String> {
+  let rel = Path::new(file_rel);
+  // Windows drive and UNC prefixes are plain names to a Unix Path, so check them by hand
+  let bytes = file_rel.as_bytes();
+  let windows_prefix = (bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':')
+    || file_rel.starts_with("\\\\");
+  // absolute paths, drive prefixes and `..` are never valid in file_rel
+  if windows_prefix
+    || rel
+      .components()
+      .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir))
+  {
+    return Err("Path is outside the library root".into());
+  }
+
+  let root = root.canonicalize().map_err(|e| e.to_string())?;
+  let path = root.join(rel);
+
+  // a symlink inside the library could still point elsewhere; for a file that
+  // doesn't exist yet, check the deepest ancestor that does
+  let mut existing = path.as_path();
+  let real = loop {
+    match existing.canonicalize() {
+      Ok(real) => break real,
+      Err(_) => existing = existing.parent().unwrap_or(&root),
+    }
+  };
+  if !real.starts_with(&root) {
+    return Err("Path is outside the library root".into());
+  }
+  match path.strip_prefix(existing) {
+    Ok(rest) if !rest.as_os_str().is_empty() => Ok(real.join(rest)),
+    _ => Ok(real),
+  }
+}
+
+// --- Instance lock ---
+// db/.lock holds the pid of the instance using this library. The owner rewrites
+// it periodically, so a lock that hasn't been touched for a while belongs to a
+// crashed instance and can be taken over.
+pub const LOCK_HEARTBEAT: Duration = Duration::from_secs(60);
+const LOCK_STALE_AFTER: Duration = Duration::from_secs(180);
+
+fn lock_path(root: &Path) -> PathBuf {
+  root.join("db").join(".lock")
+}
+
+fn lock_holder(root: &Path) -> Option<u32> {
+  let path = lock_path(root);
+  let age = fs::metadata(&path).ok()?.modified().ok()?.elapsed().unwrap_or_default();
+  if age > LOCK_STALE_AFTER {
+    return None;
+  }
+  fs::read_to_string(&path).ok()?.trim().parse().ok()
+}
+
+// Take the lock (or keep it, if we already hold it). The file is only ever created
+// with create_new, so of two instances starting together exactly one gets it; a
+// stale lock is removed first and then raced for the same way.
+pub fn acquire_lock(root: &Path) -> Result<(), String> {
+  let me = std::process::id();
+  let path = lock_path(root);
+  fs::create_dir_all(root.join("db")).map_err(|e| e.to_string())?;
+  for _ in 0..2 {
+    match fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+      Ok(mut file) => return file.write_all(me.to_string().as_bytes()).map_err(|e| e.to_string()),
+      Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+      Err(e) => return Err(e.to_string()),
+    }
+
+    let stale = fs::metadata(&path)
+      .and_then(|m| m.modified())
+      .is_ok_and(|t| t.elapsed().unwrap_or_default() > LOCK_STALE_AFTER);
+    if !stale {
+      return match fs::read_to_string(&path).ok().and_then(|s| s.trim().parse::<u32>().ok()) {
+        Some(pid) if pid == me => Ok(()),
+        Some(pid) => Err(locked_message(pid)),
+        // just created by someone else who hasn't written their pid yet
+        None => Err("This library is being opened by another instance.".into()),
+      };
+    }
+    let _ = fs::remove_file(&path);
+  }
+  Err("Could not take the library lock".into())
+}
+
+// Like acquire_lock, but only checks: Err while another live instance holds the lock
+pub fn check_lock(root: &Path) -> Result<(), String> {
+  match lock_holder(root) {
+    Some(pid) if pid != std::process::id() => Err(locked_message(pid)),
+    _ => Ok(()),
+  }
+}
+
+fn locked_message(pid: u32) -> String {
+  format!("This library is already open in another instance (pid {pid}). Close it first.")
+}
+
+// Keep our lock fresh; no-op when someone else holds it
+pub fn refresh_lock(root: &Path) {
+  let me = std::process::id();
+  if lock_holder(root).is_none_or(|pid| pid == me) {
+    let _ = fs::write(lock_path(root), me.to_string());
+  }
+}
+
+pub fn holds_lock(root: &Path) -> bool {
+  lock_holder(root) == Some(std::process::id())
+}
+
+pub fn release_lock(root: &Path) {
+  if lock_holder(root) == Some(std::process::id()) {
+    let _ = fs::remove_file(lock_path(root));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn library() -> tempfile::TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    ensure_layout(dir.path()).unwrap();
+    dir
+  }
+
+  #[test]
+  fn rejects_parent_components() {
+    let dir = library();
+    assert!(resolve_in_root(dir.path(), "../x").is_err());
+    assert!(resolve_in_root(dir.path(), "media/../../x").is_err());
+  }
+
+  #[test]
+  fn rejects_absolute_paths() {
+    let dir = library();
+    let outside = std::env::temp_dir().join("x.png");
+    assert!(resolve_in_root(dir.path(), &outside.to_string_lossy()).is_err());
+    assert!(resolve_in_root(dir.path(), "/etc/passwd").is_err());
+  }
+
+  #[test]
+  fn rejects_drive_prefixes() {
+    let dir = library();
+    assert!(resolve_in_root(dir.path(), "C:\\Windows\\x.png").is_err());
+    assert!(resolve_in_root(dir.path(), "C:x.png").is_err());
+    assert!(resolve_in_root(dir.path(), "\\\\server\\share\\x.png").is_err());
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn rejects_symlinks_out_of_the_root() {
+    let dir = library();
+    let outside = tempfile::tempdir().unwrap();
+    fs::write(outside.path().join("secret.png"), b"x").unwrap();
+    std::os::unix::fs::symlink(outside.path(), dir.path().join("media").join("link")).unwrap();
+
+    assert!(resolve_in_root(dir.path(), "media/link/secret.png").is_err());
+    // not created yet, but its directory already leads outside
+    assert!(resolve_in_root(dir.path(), "media/link/new.png").is_err());
+  }
+
+  #[test]
+  fn allows_files_that_dont_exist_yet() {
+    let dir = library();
+    let path = resolve_in_root(dir.path(), ".trash/media/x.png").unwrap();
+    assert_eq!(path, dir.path().canonicalize().unwrap().join(".trash").join("media").join("x.png"));
+  }
+
+  #[test]
+  fn acquire_lock_is_reentrant_for_the_holder() {
+    let dir = library();
+    acquire_lock(dir.path()).unwrap();
+    acquire_lock(dir.path()).unwrap();
+    assert!(holds_lock(dir.path()));
+  }
+
+  #[test]
+  fn acquire_lock_refuses_a_live_holder() {
+    let dir = library();
+    fs::write(lock_path(dir.path()), (std::process::id() + 1).to_string()).unwrap();
+    assert!(acquire_lock(dir.path()).is_err());
+    assert!(!holds_lock(dir.path()));
+  }
+
+  #[test]
+  fn acquire_lock_takes_over_a_stale_lock() {
+    let dir = library();
+    fs::write(lock_path(dir.path()), (std::process::id() + 1).to_string()).unwrap();
+    let old = std::time::SystemTime::now() - LOCK_STALE_AFTER * 2;
+    fs::File::options().write(true).open(lock_path(dir.path())).unwrap().set_modified(old).unwrap();
+
+    acquire_lock(dir.path()).unwrap();
+    assert!(holds_lock(dir.path()));
+  }
+
+  #[test]
+  fn resolves_existing_files_to_the_file_itself() {
+    let dir = library();
+    fs::write(dir.path().join("media").join("a.png"), b"x").unwrap();
+    let path = resolve_in_root(dir.path(), "media/a.png").unwrap();
+    assert_eq!(path, dir.path().canonicalize().unwrap().join("media").join("a.png"));
+    assert!(path.is_file());
+  }
+}
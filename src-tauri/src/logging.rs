@@ -0,0 +1,84 @@
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+// app.log rolls to app.log.1 .. app.log.3 once it passes this size
+const MAX_LOG_BYTES: u64 = 5 * 1024 * 1024;
+const KEEP_ROTATED: u32 = 3;
+
+// Writes to <root>/logs/app.log once a library root is known, stderr until then
+struct FileLogger {
+  path: Mutex<Option<PathBuf>>,
+}
+
+static LOGGER: FileLogger = FileLogger { path: Mutex::new(None) };
+
+pub fn init() {
+  if log::set_logger(&LOGGER).is_ok() {
+    log::set_max_level(if cfg!(debug_assertions) { LevelFilter::Debug } else { LevelFilter::Info });
+  }
+}
+
+pub fn log_path(root: &Path) -> PathBuf {
+  root.join("logs").join("app.log")
+}
+
+// Called whenever the library root is (re)set
+pub fn set_root(root: &Path) {
+  let path = log_path(root);
+  if let Some(dir) = path.parent() {
+    let _ = fs::create_dir_all(dir);
+  }
+  if let Ok(mut p) = LOGGER.path.lock() {
+    *p = Some(path);
+  }
+}
+
+fn rotate(path: &Path) {
+  let rotated = |n: u32| PathBuf::from(format!("{}.{}", path.display(), n));
+  let _ = fs::remove_file(rotated(KEEP_ROTATED));
+  for n in (1..KEEP_ROTATED).rev() {
+    let _ = fs::rename(rotated(n), rotated(n + 1));
+  }
+  let _ = fs::rename(path, rotated(1));
+}
+
+impl Log for FileLogger {
+  fn enabled(&self, metadata: &Metadata) -> bool {
+    // keep dependency chatter (reqwest, hyper, ...) out unless it's a warning
+    metadata.level() <= Level::Warn || metadata.target().starts_with("tail_burrow")
+  }
+
+  fn log(&self, record: &Record) {
+    if !self.enabled(record.metadata()) {
+      return;
+    }
+    let line = format!(
+      "{} {:<5} [{}] {}\n",
+      chrono::Local::now().format("%Y-%m-%d %H:%M:%S%.3f"),
+      record.level(),
+      record.target(),
+      record.args()
+    );
+
+    // the lock also serializes writers so lines don't interleave
+    let Ok(guard) = self.path.lock() else { return };
+    let Some(path) = guard.as_ref() else {
+      eprint!("{line}");
+      return;
+    };
+    if fs::metadata(path).is_ok_and(|m| m.len() >= MAX_LOG_BYTES) {
+      rotate(path);
+    }
+    match OpenOptions::new().create(true).append(true).open(path) {
+      Ok(mut f) => {
+        let _ = f.write_all(line.as_bytes());
+      }
+      Err(_) => eprint!("{line}"),
+    }
+  }
+
+  fn flush(&self) {}
+}
@@ -13,15 +13,22 @@ import Masonry from "react-masonry-css";
 
 // --- TYPE DEFINITIONS ---
 type AppConfig = { library_root?: string | null };
-type ItemDto = { item_id: number; source: string; source_id: string; remote_url?: string | null; file_abs: string; file_rel: string; ext?: string | null; tags: string[]; artists: string[]; sources: string[]; rating?: string | null; fav_count?: number | null; score_total?: number | null; timestamp?: string | null; added_at: string; };
+type ItemDto = { item_id: number; source: string; source_id: string; remote_url?: string | null; file_abs: string; file_rel: string; ext?: string | null; mime?: string | null; tags: string[]; artists: string[]; primary_artist?: string | null; sources: string[]; rating?: string | null; fav_count?: number | null; score_total?: number | null; timestamp?: string | null; added_at: string; notes?: string | null; uploader_id?: number | null; uploader_name?: string | null; approver_id?: number | null; approver_name?: string | null; };
 type LibraryItem = { id?: number; item_id: number; source: string; source_id: string; remote_url?: string | null; url: string; ext?: string | null; tags: string[]; artist: string[]; sources: string[]; rating?: string | null; fav_count?: number | null; score?: { total: number }; timestamp?: string | null; file_rel: string; };
-type SyncStatus = { running: boolean; cancelled: boolean; max_new_downloads?: number | null; scanned_pages: number; scanned_posts: number; skipped_existing: number; new_attempted: number; downloaded_ok: number; failed_downloads: number; unavailable: number; last_error?: string | null; };
+type SyncStatus = { running: boolean; cancelled: boolean; paused: boolean; max_new_downloads?: number | null; scanned_pages: number; scanned_posts: number; skipped_existing: number; new_attempted: number; downloaded_ok: number; failed_downloads: number; unavailable: number; last_error?: string | null; };
 type UnavailableDto = { source: string; source_id: string; seen_at: string; reason: string; sources: string[]; };
 type Feed = { id: number; name: string; query: string };
 type FeedPagingState = { beforeId: number | null; done: boolean };
-type E621CredInfo = { username?: string | null; has_api_key: boolean };
+type E621CredInfo = { username?: string | null; has_api_key: boolean; verified_at?: string | null };
 type FASyncStatus = { running: boolean; scanned: number; skipped_url: number; skipped_md5: number; imported: number; upgraded: number; errors: number; current_message: string;};
 type FACreds = { a: string; b: string };
+type AppError = { kind: string; message: string };
+
+// Commands reject with a tagged AppError; fall back to plain strings/Errors
+const errorMessage = (e: unknown): string => {
+  if (e && typeof e === 'object' && 'message' in e) return String((e as AppError).message);
+  return String(e);
+};
 
 
 // --- THE COMPONENT ---
@@ -59,6 +66,8 @@ export default function FavoritesViewer() {
   const [showSettings, setShowSettings] = useState(false);
   const [libraryRoot, setLibraryRoot] = useState("");
   const [syncMaxNew, setSyncMaxNew] = useState<string>("");
+  const [syncIncremental, setSyncIncremental] = useState(false);
+  const [syncFavUser, setSyncFavUser] = useState<string>("");
   const [syncStatus, setSyncStatus] = useState<SyncStatus | null>(null);
   const [showUnavailable, setShowUnavailable] = useState(false);
   const [unavailableList, setUnavailableList] = useState<UnavailableDto[]>([]);
@@ -153,7 +162,11 @@ export default function FavoritesViewer() {
       setItems(prev => append ? [...prev, ...mapped] : mapped);
     } catch (error) {
       console.error("Failed to load library:", error);
-      alert("Failed to load library. Please check your library settings.");
+      if ((error as AppError)?.kind === "Unavailable") {
+        alert(`${errorMessage(error)}\nReconnect the drive, or pick another library in Settings.`);
+      } else {
+        alert("Failed to load library. Please check your library settings.");
+      }
     }
   };
 
@@ -196,11 +209,12 @@ export default function FavoritesViewer() {
   const startSync = async () => {
     const n = syncMaxNew.trim() === "" ? null : Number(syncMaxNew);
     if (syncMaxNew.trim() !== "" && (!Number.isFinite(n) || n! <= 0)) { alert("Stop-after-N must be a positive number or blank."); return; }
-    await invoke("e621_sync_start", { maxNewDownloads: n });
+    await invoke("e621_sync_start", { maxNewDownloads: n, incremental: syncIncremental, favUser: syncFavUser.trim() || null });
     syncWasRunningRef.current = true;
     await refreshSyncStatus();
   };
   const cancelSync = async () => { await invoke("e621_sync_cancel"); await refreshSyncStatus(); };
+  const togglePauseSync = async () => { await invoke(syncStatus?.paused ? "e621_sync_resume" : "e621_sync_pause"); await refreshSyncStatus(); };
   const loadUnavailable = async () => { setUnavailableList(await invoke<UnavailableDto[]>("e621_unavailable_list", { limit: 200 })); setShowUnavailable(true); };
   const refreshE621CredInfo = async () => {
     const info = await invoke<E621CredInfo>("e621_get_cred_info");
@@ -208,10 +222,10 @@ export default function FavoritesViewer() {
     if (info.username) setApiUsername(info.username);
   };
   const saveE621Credentials = async () => {
-    await invoke("e621_set_credentials", { username: apiUsername, apiKey: apiKey });
+    const status = await invoke<{ ok: boolean; message: string }>("e621_set_credentials", { username: apiUsername, apiKey: apiKey, verify: true });
     setApiKey("");
     await refreshE621CredInfo();
-    alert("Saved e621 credentials.");
+    alert(status.message);
   };
   const favoriteOnE621 = async (postId: number) => { await invoke("e621_favorite", { postId }); };
   const ensureFavorite = async (feedId: number, post: any) => {
@@ -220,14 +234,18 @@ export default function FavoritesViewer() {
       setFeedActionBusy((prev) => ({ ...prev, [id]: true }));
       if (!downloadedE621Ids.has(id)) {
         if (!post?.file?.url) throw new Error("This post has no original file URL (deleted/blocked).");
-        await invoke("add_e621_post", { post: { id: post.id, file_url: post.file.url, file_ext: post.file.ext, file_md5: post.file.md5, rating: post.rating, fav_count: post.fav_count, score_total: post.score?.total, created_at: post.created_at, sources: post.sources || [], tags: { general: post.tags?.general || [], species: post.tags?.species || [], character: post.tags?.character || [], artist: post.tags?.artist || [], meta: post.tags?.meta || [], lore: post.tags?.lore || [], copyright: post.tags?.copyright || [] } } });
+        const status = await invoke<{ ok: boolean; message: string; item_id?: number }>("add_e621_post", { post: { id: post.id, file_url: post.file.url, file_ext: post.file.ext, file_md5: post.file.md5, rating: post.rating, fav_count: post.fav_count, score_total: post.score?.total, created_at: post.created_at, sources: post.sources || [], tags: { general: post.tags?.general || [], species: post.tags?.species || [], character: post.tags?.character || [], artist: post.tags?.artist || [], meta: post.tags?.meta || [], lore: post.tags?.lore || [], copyright: post.tags?.copyright || [] }, raw: post, sample_url: post.sample?.has ? post.sample.url : undefined, uploader_id: post.uploader_id, approver_id: post.approver_id } });
+        // same content is already in the trash: offer it back instead of a second copy
+        if (!status.ok && status.item_id != null) {
+          const restore = await confirmDialog(status.message, { title: "Already in Trash", okLabel: "Restore", cancelLabel: "Cancel" });
+          if (restore) await invoke("restore_item", { itemId: status.item_id });
+        }
         await loadData();
       }
       await favoriteOnE621(id);
       setFeedPosts((prev) => ({ ...prev, [feedId]: (prev[feedId] || []).map((p: any) => p.id === id ? { ...p, is_favorited: true } : p) }));
     } catch (e) {
-      const msg = e instanceof Error ? e.message : String(e);
-      alert(msg);
+      alert(errorMessage(e));
     } finally {
       setFeedActionBusy((prev) => ({ ...prev, [id]: false }));
     }
@@ -322,7 +340,7 @@ export default function FavoritesViewer() {
       setFeedPosts(prev => { const existing = reset ? [] : (prev[feedId] || []); const uniqueMap = new Map(); [...existing, ...newPosts].forEach(p => uniqueMap.set(p.id, p)); return { ...prev, [feedId]: Array.from(uniqueMap.values()) }; });
       const minId = newPosts.reduce((m: number, p: any) => Math.min(m, p.id), Number.POSITIVE_INFINITY);
       setFeedPaging(prev => ({ ...prev, [feedId]: { beforeId: (minId !== Number.POSITIVE_INFINITY) ? minId : feedPaging[feedId]?.beforeId, done: newPosts.length < LIMIT } }));
-    } catch (e) { console.error('Error fetching feed:', e); alert("Error fetching feed: " + (e instanceof Error ? e.message : String(e))); } 
+    } catch (e) { console.error('Error fetching feed:', e); alert("Error fetching feed: " + errorMessage(e)); } 
     finally { setLoadingFeeds(prev => ({ ...prev, [feedId]: false })); }
   };
   const toggleTag = (tag: string) => setSelectedTags(prev => prev.includes(tag) ? prev.filter(t => t !== tag) : [...prev, tag]);
@@ -502,7 +520,7 @@ export default function FavoritesViewer() {
       setShowEditModal(false);
     } catch (error) {
       console.error("Failed to save metadata:", error);
-      alert("Failed to save: " + String(error));
+      alert("Failed to save: " + errorMessage(error));
     }
   };
   // --- EFFECTS ---
@@ -651,6 +669,10 @@ export default function FavoritesViewer() {
   }, [activeTab, viewerOverlay, pokeHud, goToPrev, goToNext, currentItem, showSettings, showTagModal]);
   
   useEffect(() => { if (viewerOverlay) pokeHud(); }, [viewerOverlay, pokeHud]);
+  useEffect(() => {
+    if (!viewerOverlay || !currentItem) return;
+    invoke("mark_viewed", { itemId: currentItem.item_id }).catch(() => {});
+  }, [viewerOverlay, currentItem?.item_id]);
   useEffect(() => { return () => { if (hudTimerRef.current) clearTimeout(hudTimerRef.current); }; }, []);
   useEffect(() => {
     const handleFullscreenChange = () => { if (!document.fullscreenElement && viewerOverlay) setViewerOverlay(false); };
@@ -746,6 +768,7 @@ export default function FavoritesViewer() {
                   <option value="score">By Score</option>
                   <option value="newest">Newest First</option>
                   <option value="oldest">Oldest First</option>
+                  <option value="created">Posted Date</option>
                 </select>
                 <select 
                   value={filterSource} 
@@ -1130,7 +1153,7 @@ export default function FavoritesViewer() {
                   <Trash2 className="w-4 h-4" />
                   Trash ({trashCount})
                 </button>
-                <button onClick={async () => { const ok = await confirmDialog("Unload the current library?", { title: "Unload Library", okLabel: "Yes, unload", cancelLabel: "Cancel" }); if (!ok) return; try { await invoke("clear_library_root"); setLibraryRoot(""); setItems([]); setAllTags([]); setTotalDatabaseItems(0); setHasMoreItems(true); setDownloadedE621Ids(new Set()); setShowSettings(false); } catch (e) { console.error("Failed to unload:", e); alert("Failed to unload: " + String(e)); } }} className="px-4 py-2 bg-red-600 hover:bg-red-700 rounded">Unload Library</button></div>
+                <button onClick={async () => { const ok = await confirmDialog("Unload the current library?", { title: "Unload Library", okLabel: "Yes, unload", cancelLabel: "Cancel" }); if (!ok) return; try { await invoke("clear_library_root"); setLibraryRoot(""); setItems([]); setAllTags([]); setTotalDatabaseItems(0); setHasMoreItems(true); setDownloadedE621Ids(new Set()); setShowSettings(false); } catch (e) { console.error("Failed to unload:", e); alert("Failed to unload: " + errorMessage(e)); } }} className="px-4 py-2 bg-red-600 hover:bg-red-700 rounded">Unload Library</button></div>
               </div>
               <div className="border-t border-gray-700 pt-4">
                 <h3 className="text-lg font-semibold mb-2">Viewer</h3>
@@ -1190,8 +1213,8 @@ export default function FavoritesViewer() {
                 {e621CredInfo.has_api_key && !isEditingE621 ? (
                   <div className="flex items-center justify-between bg-gray-900 p-3 rounded border border-green-900/50 mb-3">
                     <div className="flex items-center gap-2">
-                      <div className="w-2 h-2 rounded-full bg-green-500"></div>
-                      <span className="text-sm text-gray-300">Credentials Saved ({e621CredInfo.username})</span>
+                      <div className={`w-2 h-2 rounded-full ${e621CredInfo.verified_at ? "bg-green-500" : "bg-yellow-500"}`}></div>
+                      <span className="text-sm text-gray-300">Credentials Saved ({e621CredInfo.username}){e621CredInfo.verified_at ? ` · verified ${new Date(e621CredInfo.verified_at).toLocaleString()}` : " · not verified"}</span>
                     </div>
                     <button onClick={async () => {
                       const ok = await confirmDialog("Clear e621 credentials?", { title: "Clear", okLabel: "Clear", cancelLabel: "Cancel" });
@@ -1226,13 +1249,27 @@ export default function FavoritesViewer() {
                     onChange={(e) => setSyncMaxNew(e.target.value)} 
                     className="flex-1 px-4 py-2 bg-gray-700 border border-gray-600 rounded focus:outline-none focus:border-purple-500" 
                   />
+                  <input 
+                    type="text" 
+                    placeholder="Favorites of (default: you)" 
+                    value={syncFavUser} 
+                    onChange={(e) => setSyncFavUser(e.target.value)} 
+                    className="flex-1 px-4 py-2 bg-gray-700 border border-gray-600 rounded focus:outline-none focus:border-purple-500" 
+                  />
+                  <label className="flex items-center gap-1 text-sm text-gray-300" title="Stop at the newest post from the last completed sync">
+                    <input type="checkbox" checked={syncIncremental} onChange={(e) => setSyncIncremental(e.target.checked)} />
+                    New only
+                  </label>
                   <button 
                     onClick={startSync} 
                     disabled={!!syncStatus?.running || (!e621CredInfo.has_api_key && !isEditingE621)} 
                     className="px-4 py-2 bg-purple-600 hover:bg-purple-700 rounded disabled:opacity-50"
                   >
-                    {syncStatus?.running ? "Scanning..." : "Start Import"}
+                    {syncStatus?.running ? (syncStatus.paused ? "Paused" : "Scanning...") : "Start Import"}
                   </button>
+                  {syncStatus?.running && (
+                    <button onClick={togglePauseSync} className="px-4 py-2 bg-gray-600 hover:bg-gray-500 rounded">{syncStatus.paused ? "Resume" : "Pause"}</button>
+                  )}
                   {syncStatus?.running && (
                     <button onClick={cancelSync} className="px-4 py-2 bg-red-600 hover:bg-red-700 rounded">Stop</button>
                   )}
@@ -1581,25 +1618,31 @@ export default function FavoritesViewer() {
   );
 }
 
+type ThumbInfo = { path: string; width?: number; height?: number; color?: string; unsupported: boolean };
+
 const Thumbnail = ({ item, className }: { item: LibraryItem, className?: string }) => {
   const [src, setSrc] = useState<string>("");
+  const [color, setColor] = useState<string | undefined>(undefined);
+  const [unsupported, setUnsupported] = useState(false);
 
   useEffect(() => {
     let active = true;
     
-    // Fallback immediately for videos/gifs to avoid backend call overhead
+    // Fallback immediately for videos to avoid backend call overhead
     const ext = (item.ext || "").toLowerCase();
-    if (["mp4", "webm", "gif"].includes(ext)) {
+    if (["mp4", "webm"].includes(ext)) {
       setSrc(item.url);
       return;
     }
 
     const fetchThumb = async () => {
       try {
-        const thumbPath = await invoke<string>("ensure_thumbnail", { fileRel: item.file_rel });
+        const thumb = await invoke<ThumbInfo>("ensure_thumbnail", { fileRel: item.file_rel });
         if (active) {
-          if (thumbPath) {
-             setSrc(convertFileSrc(thumbPath));
+          setColor(thumb.color);
+          setUnsupported(thumb.unsupported);
+          if (thumb.path) {
+             setSrc(convertFileSrc(thumb.path));
           } else {
              // Backend returned empty (e.g. video or error handled gracefully)
              setSrc(item.url);
@@ -1613,8 +1656,9 @@ const Thumbnail = ({ item, className }: { item: LibraryItem, className?: string
     return () => { active = false; };
   }, [item]);
 
+  if (unsupported) return <div className={`bg-gray-800 flex items-center justify-center text-xs text-gray-500 uppercase ${className}`}>{item.ext || "unsupported"}</div>;
   if (!src) return <div className={`bg-gray-800 animate-pulse ${className}`} />;
-  return <img src={src} className={className} loading="lazy" alt="" />;
+  return <img src={src} className={className} style={color ? { backgroundColor: color } : undefined} loading="lazy" alt="" />;
 };
 
 function InfiniteSentinel({ onVisible, disabled }: { onVisible: () => void; disabled?: boolean; }) {